@@ -14,9 +14,34 @@ WIFI_PASSWORD = ""
 # TCP server information
 SERVER_PORT = 5000
 
+# The secret clients must send before any instruction (leave empty to accept any client)
+AUTH_TOKEN = ""
+# The certificate and key to encrypt connections with, in DER (leave empty for plain TCP)
+TLS_CERT = ""
+TLS_KEY = ""
+
 # Define the struct constants for binary reading.
 STRUCT_FORMAT = "<BiiB"
 
+# Acknowledgements are "ACK", a status, the sequence number, then the cursor position.
+ACK_FORMAT = "<3sBIii"
+SEQUENCE_FORMAT = "<I"
+
+# The protocol version, and a bit for each supported opcode (1 through 10)
+# These are sent in place of the cursor position when acknowledging the handshake
+PROTOCOL_VERSION = 1
+SUPPORTED_OPCODES = 0b10011111110
+
+# Status codes sent in acknowledgements.
+STATUS_OK = 0
+STATUS_INVALID_OPCODE = 1
+STATUS_MALFORMED = 2
+STATUS_FAILED = 3
+STATUS_UNAUTHORIZED = 4
+
+# The cursor position since the last normalize.
+cursor = [0, 0]
+
 def connect_wlan():
     wlan0 = network.WLAN(network.STA_IF)
 
@@ -49,20 +74,59 @@ net.setsockopt(socket.SOL_SOCKET, socket.SO_REUSEADDR, 1)
 net.bind(socket.getaddrinfo("0.0.0.0", SERVER_PORT)[0][-1])
 net.listen(64)
 
+# Create the TLS context, if connections are encrypted
+tls = None
+if TLS_CERT:
+    import ssl
+    tls = ssl.SSLContext(ssl.PROTOCOL_TLS_SERVER)
+    tls.load_cert_chain(TLS_CERT, TLS_KEY)
+
+# Reads exactly size bytes, or fewer if the connection closes
+def recv_exact(conn0, size):
+    data = b""
+    while len(data) < size:
+        chunk = conn0.recv(size - len(data))
+        if not chunk:
+            break
+        data += chunk
+
+    return data
+
+# Checks the token sent by a new client
+# The handshake is "AUTH", the token's length, then the token
+def authenticate(conn0):
+    if not AUTH_TOKEN:
+        return True
+
+    header = recv_exact(conn0, 5)
+    token = recv_exact(conn0, header[4]) if len(header) == 5 else b""
+    valid = header[:4] == b"AUTH" and token == AUTH_TOKEN.encode()
+
+    status = STATUS_OK if valid else STATUS_UNAUTHORIZED
+    conn0.send(struct.pack(ACK_FORMAT, b"ACK", status, 0, cursor[0], cursor[1]))
+    return valid
+
 # Create a mouse instance
 mouse = MouseInterface()
 usb.device.get().init(mouse, builtin_driver = True)
 
+# Scrolling needs a mouse with a wheel (opcodes 8 and 9)
+if hasattr(mouse, "scroll"):
+    SUPPORTED_OPCODES |= 0b1100000000
+
 # Utility method for moving more than 128 units
-def move_relative(x0, y0):
+# step0 is the most units moved at once
+def move_relative(x0, y0, step0 = 127):
     rx = x0
     ry = y0
 
     while rx != 0 or ry != 0:
-        dx = min(127, max(-127, rx))
-        dy = min(127, max(-127, ry))
+        dx = min(step0, max(-step0, rx))
+        dy = min(step0, max(-step0, ry))
 
         mouse.move_by(dx, dy)
+        cursor[0] += dx
+        cursor[1] += dy
 
         rx -= dx
         ry -= dy
@@ -74,7 +138,11 @@ GROUP_FORMAT = "<I"
 SECTOR_FORMAT = "<ii"
 
 # bytes0 starts with a byte, then 2 signed integers
-def move_group(group_size, bytes0):
+# dwell0 is the milliseconds to wait on each point, and step0 the most units moved at once
+# Zero uses the defaults
+def move_group(group_size, bytes0, dwell0, step0):
+    dwell = dwell0 / 1000 if dwell0 > 0 else 0.06
+    step = min(step0, 127) if step0 > 0 else 127
     pressed = False
 
     offset = 0
@@ -84,14 +152,14 @@ def move_group(group_size, bytes0):
         offset += 8
 
         # Move the mouse
-        move_relative(x0, y0)
+        move_relative(x0, y0, step)
 
         if not pressed:
             time.sleep(0.01)
             pressed = True
             mouse.click_left(True)
 
-        time.sleep(0.06)
+        time.sleep(dwell)
 
     time.sleep(0.01)
     mouse.click_left(False)
@@ -101,6 +169,9 @@ def normalize():
         mouse.move_by(-100, -100)
         time.sleep(0.01)
 
+    cursor[0] = 0
+    cursor[1] = 0
+
 # Handles any incoming messages
 def handle_message(opcode0, x0, y0, groups0, remaining):
     if opcode == 1: # Left click down
@@ -112,20 +183,43 @@ def handle_message(opcode0, x0, y0, groups0, remaining):
     elif opcode == 4: # Normalize to (0, 0)
         normalize()
     elif opcode == 5: # Moves the mouse with the provided array of points
-        move_group(groups0, remaining)
+        move_group(groups0, remaining, x0, y0)
     elif opcode == 6:
         normalize()
         time.sleep(0.01)
         move_relative(x0, y0)
+    elif opcode == 7: # Handshake, which is answered with the server's capabilities
+        pass
+    elif opcode == 8 and hasattr(mouse, "scroll"): # Scroll up by x0 notches
+        for i in range(x0):
+            mouse.scroll(1)
+            time.sleep(0.02)
+    elif opcode == 9 and hasattr(mouse, "scroll"): # Scroll down by x0 notches
+        for i in range(x0):
+            mouse.scroll(-1)
+            time.sleep(0.02)
+    elif opcode == 10: # Right click
+        mouse.click_right(True)
+        time.sleep(0.01)
+        mouse.click_right(False)
     else:
         print("Invalid opcode:", opcode0)
-        return
+        return STATUS_INVALID_OPCODE
+
+    return STATUS_OK
 
 # Listen for messages
 while True:
     try:
         # Try accepting a connection
         conn, addr = net.accept()
+        if tls:
+            conn = tls.wrap_socket(conn, server_side = True)
+
+        if not authenticate(conn):
+            print("Rejected a client with the wrong token:", addr)
+            conn.close()
+            continue
 
         # While the connection is alive, read messages
         while True:
@@ -133,16 +227,39 @@ while True:
                 break
 
             try:
-                # Read any messages provided.
-                message = conn.recv(512)
-
-                # Destructure the message
-                opcode, x, y, groups = struct.unpack(STRUCT_FORMAT, message[:10])
-
-                # Handle the message accordingly
-                handle_message(opcode, x, y, groups, message[10:])
-
-                conn.send(b"OKAY")
+                # Read the header, then the group and the sequence number
+                message = recv_exact(conn, 10)
+                if len(message) < 10:
+                    break
+
+                length = message[9] * 8 + 4
+                message += recv_exact(conn, length)
+                if len(message) < 10 + length:
+                    break
+
+                sequence = 0
+                opcode = 0
+                try:
+                    # Destructure the message
+                    opcode, x, y, groups = struct.unpack(STRUCT_FORMAT, message[:10])
+
+                    # The sequence number follows the group
+                    end = 10 + groups * 8
+                    if len(message) >= end + 4:
+                        sequence = struct.unpack(SEQUENCE_FORMAT, message[end:end + 4])[0]
+
+                    # Handle the message accordingly
+                    status = handle_message(opcode, x, y, groups, message[10:])
+                except ValueError:
+                    status = STATUS_MALFORMED
+                except Exception as ex:
+                    print("Failed to handle message:", ex)
+                    status = STATUS_FAILED
+
+                if opcode == 7 and status == STATUS_OK:
+                    conn.send(struct.pack(ACK_FORMAT, b"ACK", status, sequence, PROTOCOL_VERSION, SUPPORTED_OPCODES))
+                else:
+                    conn.send(struct.pack(ACK_FORMAT, b"ACK", status, sequence, cursor[0], cursor[1]))
 
             except Exception as ex:
                 print("Failed to read message:", ex)
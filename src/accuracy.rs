@@ -0,0 +1,177 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+use serde::{Deserialize, Serialize};
+use crate::limits::DAY;
+
+/// The number of days counted as recent when comparing accuracy.
+pub const RECENT_DAYS: u64 = 7;
+/// The fewest recent tiles a letter needs before its accuracy is judged.
+pub const MIN_SAMPLES: u32 = 20;
+
+/// How often each letter was recognized correctly, persisted between runs.
+/// The user's corrections in the board editor are taken as the truth.
+#[derive(Default, Debug, Serialize, Deserialize)]
+pub struct RecognitionStats {
+    /// The daily counts for each letter, keyed by the correct letter.
+    letters: BTreeMap<String, Vec<DailyCount>>
+}
+
+/// The tiles of a letter seen on a single day.
+#[derive(Clone, Copy, Default, Debug, Serialize, Deserialize)]
+pub struct DailyCount {
+    /// The number of days since the UNIX epoch.
+    pub day: u64,
+    /// The number of tiles with this letter.
+    pub total: u32,
+    /// The number of those tiles recognized correctly.
+    pub correct: u32
+}
+
+/// A letter whose recognition has gotten worse recently.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Degradation {
+    /// The letter.
+    pub letter: String,
+    /// The accuracy over the recent days. (0 to 1)
+    pub recent: f64,
+    /// The accuracy before the recent days. (0 to 1)
+    pub previous: f64
+}
+
+impl RecognitionStats {
+    /// Loads the statistics from a file.
+    /// A missing or invalid file is treated as empty statistics.
+    /// path: The path to the statistics file.
+    pub fn load<P: AsRef<Path>>(path: P) -> Self {
+        fs::read_to_string(path)
+            .ok()
+            .and_then(|contents| serde_json::from_str(&contents).ok())
+            .unwrap_or_default()
+    }
+
+    /// Saves the statistics to a file.
+    /// path: The path to the statistics file.
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> anyhow::Result<()> {
+        fs::write(path, serde_json::to_string(self)?)?;
+        Ok(())
+    }
+
+    /// Records the tiles of a board.
+    /// now: The current UNIX timestamp.
+    /// recognized: The tiles as they were recognized.
+    /// corrected: The tiles after the user's corrections.
+    pub fn record(&mut self, now: u64, recognized: &[Vec<String>], corrected: &[Vec<String>]) {
+        let day = now / DAY;
+        let tiles = recognized.iter().flatten().zip(corrected.iter().flatten());
+
+        for (recognized, corrected) in tiles {
+            let days = self.letters.entry(corrected.clone()).or_default();
+            if days.last().is_none_or(|count| count.day != day) {
+                days.push(DailyCount { day, ..DailyCount::default() });
+            }
+
+            let count = days.last_mut().unwrap();
+            count.total += 1;
+            if recognized == corrected {
+                count.correct += 1;
+            }
+        }
+    }
+
+    /// Forgets the counts from before a number of days ago.
+    /// Letters without any remaining counts are removed.
+    /// now: The current UNIX timestamp.
+    /// days: The number of days to keep. 0 keeps every count.
+    pub fn retain_days(&mut self, now: u64, days: u64) {
+        if days == 0 {
+            return;
+        }
+
+        let start = (now / DAY).saturating_sub(days);
+        for counts in self.letters.values_mut() {
+            counts.retain(|count| count.day > start);
+        }
+        self.letters.retain(|_, counts| !counts.is_empty());
+    }
+
+    /// Finds letters recognized less accurately recently than before.
+    /// Returns the letters with the largest drop first.
+    /// now: The current UNIX timestamp.
+    /// threshold: The smallest drop in accuracy to report. (0 to 1)
+    pub fn degraded(&self, now: u64, threshold: f64) -> Vec<Degradation> {
+        let start = (now / DAY).saturating_sub(RECENT_DAYS);
+
+        let mut degraded: Vec<Degradation> = self.letters.iter()
+            .filter_map(|(letter, days)| {
+                let (recent, previous): (Vec<DailyCount>, Vec<DailyCount>) =
+                    days.iter().partition(|count| count.day > start);
+
+                let (recent_total, recent) = accuracy(&recent);
+                let (previous_total, previous) = accuracy(&previous);
+                if recent_total < MIN_SAMPLES || previous_total < MIN_SAMPLES {
+                    return None;
+                }
+
+                (previous - recent >= threshold).then(|| Degradation {
+                    letter: letter.clone(),
+                    recent,
+                    previous
+                })
+            })
+            .collect();
+
+        degraded.sort_by(|a, b| (b.previous - b.recent).total_cmp(&(a.previous - a.recent)));
+        degraded
+    }
+
+    /// Returns each letter's overall (total, accuracy).
+    pub fn overall(&self) -> impl Iterator<Item = (&str, (u32, f64))> {
+        self.letters.iter().map(|(letter, days)| (letter.as_str(), accuracy(days)))
+    }
+}
+
+/// Sums daily counts into the (total, accuracy) of the tiles.
+/// counts: The daily counts to sum.
+fn accuracy(counts: &[DailyCount]) -> (u32, f64) {
+    let total: u32 = counts.iter().map(|count| count.total).sum();
+    let correct: u32 = counts.iter().map(|count| count.correct).sum();
+
+    (total, correct as f64 / total.max(1) as f64)
+}
+
+#[cfg(test)]
+mod test {
+    use crate::accuracy::RecognitionStats;
+    use crate::limits::DAY;
+
+    #[test]
+    pub fn finds_degraded_letters() {
+        let tiles = |letter: &str| vec![vec![letter.to_string(); 10]];
+        let mut stats = RecognitionStats::default();
+        let now = 100 * DAY;
+
+        // Both letters were always correct a month ago.
+        for _ in 0..3 {
+            stats.record(now - 30 * DAY, &tiles("a"), &tiles("a"));
+            stats.record(now - 30 * DAY, &tiles("b"), &tiles("b"));
+        }
+
+        // Recently, half of the 'a' tiles were misread.
+        for _ in 0..2 {
+            stats.record(now, &tiles("a"), &tiles("a"));
+            stats.record(now, &tiles("o"), &tiles("a"));
+            stats.record(now, &tiles("b"), &tiles("b"));
+        }
+
+        let degraded = stats.degraded(now, 0.1);
+        assert_eq!(degraded.len(), 1);
+        assert_eq!(degraded[0].letter, "a");
+        assert_eq!((degraded[0].recent, degraded[0].previous), (0.5, 1.0));
+
+        // Without the older counts, there's nothing to compare against.
+        stats.retain_days(now, 14);
+        assert!(stats.degraded(now, 0.1).is_empty());
+        assert_eq!(stats.overall().count(), 2);
+    }
+}
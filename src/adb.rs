@@ -0,0 +1,224 @@
+use std::io::{Error, ErrorKind};
+use std::time::Duration;
+use async_trait::async_trait;
+use log::info;
+use tokio::process::Command;
+use tokio::time::timeout;
+use crate::config::Config;
+use crate::mouse::{Ack, Capabilities, Instruction, MouseBackend, Opcode, Status};
+use crate::planner::CoordinateMapper;
+
+/// How long to wait on each point of a group by default, in milliseconds. (the server's default)
+pub const DEFAULT_DWELL: i32 = 60;
+/// How long a press which moved is swiped for, in milliseconds.
+pub const SWIPE_TIME: u32 = 100;
+/// How far the screen is swiped for each notch of scrolling, in screen pixels.
+pub const SCROLL_PIXELS: i32 = 120;
+/// How long to wait for the device to come back after a failed command.
+pub const RECONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Touches the screen of an Android device over ADB.
+/// Presses become taps and swipes, and groups are sent as raw motion events,
+/// with mouse units mapped onto the screen through the board's tile spacing.
+pub struct AdbBackend {
+    /// The path to the adb executable.
+    adb: String,
+    /// The serial of the device, or empty for the only connected device.
+    serial: String,
+    /// The size of a captured frame, which the screen is scaled from.
+    frame: (i32, i32),
+    /// Converts mouse units into frame pixels.
+    mapper: CoordinateMapper,
+
+    // The device's screen size in pixels, once it's known.
+    screen: Option<(i32, i32)>,
+    // The cursor's position since the last normalize, in mouse units.
+    cursor: (i32, i32),
+    // Where the button was pressed, if it's held.
+    pressed: Option<(i32, i32)>
+}
+
+impl AdbBackend {
+    /// Creates a backend for the configured device.
+    /// config: The application configuration.
+    pub fn new(config: &Config) -> Self {
+        AdbBackend {
+            adb: config.adb_path.clone(),
+            serial: config.adb_serial.clone(),
+            frame: (config.screen_width.max(1), config.screen_height.max(1)),
+            mapper: CoordinateMapper::new(config.layout()),
+            screen: None,
+            cursor: (0, 0),
+            pressed: None
+        }
+    }
+
+    /// Runs a shell command on the device.
+    /// Returns the command's output.
+    /// command: The shell command to run.
+    async fn shell(&self, command: &str) -> std::io::Result<String> {
+        let output = self.adb_command().arg("shell").arg(command).output().await?;
+        if !output.status.success() {
+            let message = String::from_utf8_lossy(&output.stderr);
+            return Err(Error::other(format!("adb failed: {}", message.trim())));
+        }
+
+        Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+    }
+
+    /// Creates an adb command for the configured device.
+    fn adb_command(&self) -> Command {
+        let mut command = Command::new(&self.adb);
+        if !self.serial.is_empty() {
+            command.arg("-s").arg(&self.serial);
+        }
+
+        command
+    }
+
+    /// Converts mouse coordinates into screen pixels.
+    /// The screen's size is read from the device the first time.
+    /// position: The absolute mouse coordinates.
+    async fn screen_position(&mut self, position: (i32, i32)) -> std::io::Result<(i32, i32)> {
+        let screen = match self.screen {
+            Some(screen) => screen,
+            None => {
+                let output = self.shell("wm size").await?;
+                let screen = parse_screen_size(&output)
+                    .ok_or_else(|| Error::new(ErrorKind::InvalidData, "Failed to read the screen size."))?;
+                info!("The device's screen is {}x{}.", screen.0, screen.1);
+
+                self.screen = Some(screen);
+                screen
+            }
+        };
+
+        let (x, y) = self.mapper.frame_pixel(position);
+        Ok((x * screen.0 / self.frame.0, y * screen.1 / self.frame.1))
+    }
+
+    /// Releases the button, tapping or swiping from where it was pressed.
+    async fn release(&mut self) -> std::io::Result<()> {
+        let Some(start) = self.pressed.take() else {
+            return Ok(());
+        };
+
+        let (x0, y0) = self.screen_position(start).await?;
+        let (x1, y1) = self.screen_position(self.cursor).await?;
+        if (x0, y0) == (x1, y1) {
+            self.shell(&format!("input tap {} {}", x0, y0)).await?;
+        } else {
+            self.shell(&format!("input swipe {} {} {} {} {}", x0, y0, x1, y1, SWIPE_TIME)).await?;
+        }
+
+        Ok(())
+    }
+
+    /// Scrolls by swiping from the cursor.
+    /// Scrolling down swipes upwards, the same as a finger.
+    /// notches: The notches to scroll. Positive values scroll down, and negative values scroll up.
+    async fn scroll(&mut self, notches: i32) -> std::io::Result<()> {
+        let (x, y) = self.screen_position(self.cursor).await?;
+        let end = y - notches * SCROLL_PIXELS;
+        self.shell(&format!("input swipe {} {} {} {} {}", x, y, x, end, SWIPE_TIME)).await?;
+
+        Ok(())
+    }
+
+    /// Touches a group of points in one gesture, starting at the first.
+    /// group: The points, relative to each other.
+    /// dwell: The milliseconds to wait on each point.
+    async fn move_group(&mut self, group: &[(i32, i32)], dwell: i32) -> std::io::Result<()> {
+        let dwell = if dwell > 0 { dwell } else { DEFAULT_DWELL };
+
+        let mut commands = Vec::new();
+        let mut last = None;
+        for (i, (dx, dy)) in group.iter().enumerate() {
+            self.cursor = (self.cursor.0 + dx, self.cursor.1 + dy);
+            let (x, y) = self.screen_position(self.cursor).await?;
+
+            let action = if i == 0 { "DOWN" } else { "MOVE" };
+            commands.push(format!("input motionevent {} {} {}", action, x, y));
+            commands.push(format!("sleep {:.3}", dwell as f64 / 1000.0));
+            last = Some((x, y));
+        }
+
+        let Some((x, y)) = last else {
+            return Ok(());
+        };
+        commands.push(format!("input motionevent UP {} {}", x, y));
+
+        // Sending the gesture at once keeps it from stalling between points.
+        self.shell(&commands.join("; ")).await?;
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl MouseBackend for AdbBackend {
+    async fn transmit(&mut self, instruction: &Instruction, sequence: u32) -> std::io::Result<Ack> {
+        let position = instruction.position.unwrap_or_default();
+
+        match instruction.opcode {
+            Opcode::Hello => {
+                // Every opcode is performed locally.
+                let capabilities = Capabilities::all().as_position();
+                return Ok(Ack { status: Status::Ok, sequence: Some(sequence), position: Some(capabilities) });
+            },
+            Opcode::LeftDown => self.pressed = Some(self.cursor),
+            Opcode::LeftUp => self.release().await?,
+            Opcode::Move => self.cursor = (self.cursor.0 + position.0, self.cursor.1 + position.1),
+            Opcode::Normalize => self.cursor = (0, 0),
+            Opcode::NormalMove => self.cursor = position,
+            Opcode::MoveGroup => {
+                let group = instruction.group.as_deref().unwrap_or_default();
+                self.move_group(group, position.0).await?;
+            },
+            Opcode::ScrollUp => self.scroll(-position.0).await?,
+            Opcode::ScrollDown => self.scroll(position.0).await?,
+            // A mouse's right button goes back on Android.
+            Opcode::RightClick => {
+                self.shell("input keyevent KEYCODE_BACK").await?;
+            }
+        }
+
+        Ok(Ack { status: Status::Ok, sequence: Some(sequence), position: Some(self.cursor) })
+    }
+
+    async fn reconnect(&mut self) -> std::io::Result<()> {
+        // A dropped press can't be finished, so it's forgotten.
+        self.pressed = None;
+
+        let wait = self.adb_command().arg("wait-for-device").status();
+        let status = timeout(RECONNECT_TIMEOUT, wait).await
+            .map_err(|_| Error::new(ErrorKind::TimedOut, "The device didn't come back."))??;
+        if !status.success() {
+            return Err(Error::new(ErrorKind::NotConnected, "The device didn't come back."));
+        }
+
+        Ok(())
+    }
+}
+
+/// Reads the screen size from the output of `wm size`.
+/// An override size is preferred, since it's what input is scaled to.
+/// output: The command's output. (e.g. "Physical size: 1080x2400")
+pub fn parse_screen_size(output: &str) -> Option<(i32, i32)> {
+    output.lines()
+        .filter_map(|line| line.split_once(':'))
+        .filter_map(|(_, size)| size.trim().split_once('x'))
+        .filter_map(|(width, height)| Some((width.parse().ok()?, height.parse().ok()?)))
+        .next_back()
+}
+
+#[cfg(test)]
+mod test {
+    use crate::adb::parse_screen_size;
+
+    #[test]
+    pub fn reads_screen_sizes() {
+        assert_eq!(parse_screen_size("Physical size: 1080x2400\n"), Some((1080, 2400)));
+        assert_eq!(parse_screen_size("Physical size: 1440x3120\nOverride size: 1080x2340\n"), Some((1080, 2340)));
+        assert_eq!(parse_screen_size("error: no devices/emulators found"), None);
+    }
+}
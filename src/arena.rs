@@ -0,0 +1,176 @@
+use serde::{Deserialize, Serialize};
+use crate::dictionary::{Cursor, Dictionary, Entry};
+use crate::trie::{fold, split_groups};
+
+/// The bytes an arena trie spends on each node, including the edge leading to it.
+pub const NODE_BYTES: usize = std::mem::size_of::<ArenaNode>() + std::mem::size_of::<(char, u32)>();
+
+/// A node in an arena trie.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+struct ArenaNode {
+    /// The index of this node's first edge.
+    first_edge: u32,
+    /// The number of edges leaving this node.
+    edge_count: u32,
+    /// Does a word end at this node?
+    is_end_of_word: bool,
+    /// How common the word ending here is. Higher is more common.
+    frequency: u32
+}
+
+/// A trie stored in two flat vectors, instead of a map per node.
+/// Each node's edges are contiguous and sorted, so lookups touch
+/// far less memory than the HashMap trie.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct ArenaTrie {
+    /// Every node, with the root first.
+    nodes: Vec<ArenaNode>,
+    /// Every edge, as the character and the node it leads to.
+    edges: Vec<(char, u32)>
+}
+
+impl ArenaTrie {
+    /// Builds an arena trie from sorted, deduplicated words.
+    /// The words are expected to already be normalized.
+    /// words: The words to insert with their frequencies, in sorted order.
+    pub fn from_sorted<S: AsRef<str>>(words: &[(S, u32)]) -> Self {
+        let mut trie = ArenaTrie::default();
+        trie.build(words, 0);
+
+        trie.nodes.shrink_to_fit();
+        trie.edges.shrink_to_fit();
+        trie
+    }
+
+    /// Adds the node for words sharing the first `depth` bytes.
+    /// Returns the index of the new node.
+    /// words: The sorted words under this node.
+    /// depth: The byte offset of the node's character.
+    fn build<S: AsRef<str>>(&mut self, words: &[(S, u32)], depth: usize) -> u32 {
+        let (frequency, groups) = split_groups(words, depth);
+
+        let index = self.nodes.len() as u32;
+        let first_edge = self.edges.len() as u32;
+        self.nodes.push(ArenaNode {
+            first_edge,
+            edge_count: groups.len() as u32,
+            is_end_of_word: frequency.is_some(),
+            frequency: frequency.unwrap_or(0)
+        });
+
+        // Reserve the edges, so they stay contiguous.
+        self.edges.extend(groups.iter().map(|(ch, _)| (*ch, 0)));
+        for (offset, (ch, group)) in groups.into_iter().enumerate() {
+            let child = self.build(group, depth + ch.len_utf8());
+            self.edges[first_edge as usize + offset].1 = child;
+        }
+
+        index
+    }
+
+    /// Finds the child of a node.
+    /// node: The index of the parent node.
+    /// ch: The character of the edge to follow.
+    fn child(&self, node: u32, ch: char) -> Option<u32> {
+        let edges = self.edges(node);
+        edges.binary_search_by_key(&ch, |(edge, _)| *edge)
+            .ok()
+            .map(|index| edges[index].1)
+    }
+
+    /// Returns the edges leaving a node.
+    /// node: The index of the node.
+    fn edges(&self, node: u32) -> &[(char, u32)] {
+        let node = &self.nodes[node as usize];
+        let start = node.first_edge as usize;
+        &self.edges[start..start + node.edge_count as usize]
+    }
+}
+
+/// Finds the node index of a cursor.
+/// cursor: A cursor from an arena trie.
+fn node_index(cursor: Cursor<'_>) -> u32 {
+    let Cursor::Node(node, _) = cursor else {
+        unreachable!("The cursor isn't from an arena trie.");
+    };
+
+    node
+}
+
+impl Dictionary for ArenaTrie {
+    fn root(&self) -> Option<Cursor<'_>> {
+        (!self.nodes.is_empty()).then_some(Cursor::Node(0, 0))
+    }
+
+    fn step<'a>(&'a self, cursor: Cursor<'a>, letters: &str) -> Option<Cursor<'a>> {
+        let mut node = node_index(cursor);
+        for ch in fold(letters) {
+            node = self.child(node, ch)?;
+        }
+
+        Some(Cursor::Node(node, 0))
+    }
+
+    fn entry(&self, cursor: Cursor<'_>) -> Entry {
+        let node = &self.nodes[node_index(cursor) as usize];
+        Entry { is_word: node.is_end_of_word, frequency: node.frequency }
+    }
+
+    fn letters(&self, cursor: Cursor<'_>) -> Vec<char> {
+        self.edges(node_index(cursor)).iter()
+            .map(|(ch, _)| *ch)
+            .collect()
+    }
+
+    fn iter(&self) -> Box<dyn Iterator<Item = (String, u32)> + '_> {
+        if self.nodes.is_empty() {
+            return Box::new(std::iter::empty());
+        }
+
+        // Walk the nodes depth-first, visiting the edges in order.
+        let mut stack = vec![(String::new(), 0u32)];
+        Box::new(std::iter::from_fn(move || {
+            while let Some((prefix, index)) = stack.pop() {
+                let node = &self.nodes[index as usize];
+                let start = node.first_edge as usize;
+                let edges = &self.edges[start..start + node.edge_count as usize];
+                for (ch, child) in edges.iter().rev() {
+                    let mut word = prefix.clone();
+                    word.push(*ch);
+                    stack.push((word, *child));
+                }
+
+                if node.is_end_of_word {
+                    return Some((prefix, node.frequency));
+                }
+            }
+
+            None
+        }))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::arena::ArenaTrie;
+    use crate::dictionary::Dictionary;
+    use crate::trie::TrieNode;
+
+    #[test]
+    pub fn matches_hashmap_trie() {
+        let mut words: Vec<(&str, u32)> = ["car", "card", "cards", "cart", "dog", "do", "ça"].iter()
+            .map(|word| (*word, word.len() as u32))
+            .collect();
+        words.sort_unstable();
+
+        let arena = ArenaTrie::from_sorted(&words);
+        let trie = TrieNode::from_sorted(&words);
+
+        for prefix in ["", "c", "car", "cards", "CART", "ca", "cat", "d", "do", "dogs", "ç", "ça"] {
+            assert_eq!(Dictionary::lookup(&arena, prefix), Dictionary::lookup(&trie, prefix), "{}", prefix);
+        }
+        assert!(arena.iter().eq(trie.iter()));
+        assert_eq!(arena.iter().count(), words.len());
+        assert_eq!(ArenaTrie::default().lookup("a"), None);
+    }
+}
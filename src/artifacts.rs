@@ -0,0 +1,257 @@
+use std::fs::{self, File};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use image::RgbaImage;
+use lazy_static::lazy_static;
+use log::{Log, Metadata, Record};
+use serde::Serialize;
+use crate::config::Config;
+use crate::limits;
+
+lazy_static! {
+    /// The log file of the current run, if runs are saved.
+    static ref LOG_FILE: Mutex<Option<File>> = Mutex::new(None);
+}
+
+/// The name of the index written to every run directory.
+pub const MANIFEST: &str = "manifest.json";
+/// The name of the log file written to every run directory.
+pub const LOG: &str = "log.txt";
+
+/// A file saved during a run.
+#[derive(Clone, Debug, Serialize)]
+pub struct Artifact {
+    /// The file's path, relative to the run directory.
+    pub path: String,
+    /// What the file holds. ("config", "layout", "log", "image", "board", "stats", "traces", or "recording")
+    pub kind: String,
+    /// The round the file is from, if any.
+    pub round: Option<u32>,
+    /// When the file was saved, in seconds since the Unix epoch.
+    pub saved: u64
+}
+
+/// The index of a run directory.
+#[derive(Clone, Debug, Serialize)]
+pub struct Manifest {
+    /// The version of the program which made the run.
+    pub version: String,
+    /// When the run started, in seconds since the Unix epoch.
+    pub started: u64,
+    /// The files saved so far, in order.
+    pub artifacts: Vec<Artifact>
+}
+
+/// A timestamped directory holding everything needed to look into a run.
+/// The manifest is rewritten after every file, so it's complete even if the run crashes.
+pub struct RunDirectory {
+    /// The directory's path.
+    path: PathBuf,
+    /// The index of the saved files.
+    manifest: Manifest,
+    /// The current round, counting from 1. (0 before the first round)
+    round: u32
+}
+
+impl RunDirectory {
+    /// Creates a directory for this run, if runs are saved.
+    /// The configuration is saved, and the log starts being copied into the directory.
+    /// config: The application configuration.
+    pub fn create(config: &Config) -> anyhow::Result<Option<Self>> {
+        if config.runs_dir.is_empty() {
+            return Ok(None);
+        }
+
+        // Runs started in the same second get a suffix.
+        let started = limits::now();
+        let mut path = Path::new(&config.runs_dir).join(started.to_string());
+        let mut attempt = 1;
+        while path.exists() {
+            attempt += 1;
+            path = Path::new(&config.runs_dir).join(format!("{}-{}", started, attempt));
+        }
+        fs::create_dir_all(&path)?;
+
+        let mut run = RunDirectory {
+            path,
+            manifest: Manifest {
+                version: env!("CARGO_PKG_VERSION").to_string(),
+                started,
+                artifacts: Vec::new()
+            },
+            round: 0
+        };
+
+        // The server's secret stays out of the run.
+        let mut saved = config.clone();
+        if !saved.server_token.is_empty() {
+            saved.server_token = "<redacted>".to_string();
+        }
+        run.write("config.json", "config", serde_json::to_string_pretty(&saved)?)?;
+
+        // Copy the log into the run from now on.
+        *LOG_FILE.lock().unwrap() = Some(File::create(run.path.join(LOG))?);
+        run.record(LOG, "log")?;
+
+        Ok(Some(run))
+    }
+
+    /// Returns the directory's path.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Starts a new round. Files saved afterwards are part of it.
+    pub fn start_round(&mut self) {
+        self.round += 1;
+    }
+
+    /// Saves a frame or debug image.
+    /// name: The image's file name, within the round.
+    /// image: The image to save.
+    pub fn save_image(&mut self, name: &str, image: &RgbaImage) -> anyhow::Result<()> {
+        let name = self.round_name(name);
+        image.save(self.path.join(&name))?;
+        self.record(&name, "image")
+    }
+
+    /// Saves a value as JSON.
+    /// name: The file name, within the round.
+    /// kind: What the file holds.
+    /// value: The value to save.
+    pub fn save_json<T: Serialize>(&mut self, name: &str, kind: &str, value: &T) -> anyhow::Result<()> {
+        let name = self.round_name(name);
+        self.write(&name, kind, serde_json::to_string_pretty(value)?)
+    }
+
+    /// Saves a value as JSON, on a single line.
+    /// This keeps large files small, when they aren't meant to be read by hand.
+    /// name: The file name, within the round.
+    /// kind: What the file holds.
+    /// value: The value to save.
+    pub fn save_compact_json<T: Serialize>(&mut self, name: &str, kind: &str, value: &T) -> anyhow::Result<()> {
+        let name = self.round_name(name);
+        self.write(&name, kind, serde_json::to_string(value)?)
+    }
+
+    /// Copies a file into the directory. Missing files are skipped.
+    /// name: The copy's file name, within the round.
+    /// kind: What the file holds.
+    /// source: The path of the file to copy.
+    pub fn copy(&mut self, name: &str, kind: &str, source: &str) -> anyhow::Result<()> {
+        if !Path::new(source).exists() {
+            return Ok(());
+        }
+
+        let name = self.round_name(name);
+        fs::copy(source, self.path.join(&name))?;
+        self.record(&name, kind)
+    }
+
+    /// Prefixes a file name with the current round, if one has started.
+    /// name: The file name.
+    fn round_name(&self, name: &str) -> String {
+        match self.round {
+            0 => name.to_string(),
+            round => format!("round-{}-{}", round, name)
+        }
+    }
+
+    /// Writes a file, then adds it to the manifest.
+    /// name: The file's name.
+    /// kind: What the file holds.
+    /// contents: The file's contents.
+    fn write(&mut self, name: &str, kind: &str, contents: String) -> anyhow::Result<()> {
+        fs::write(self.path.join(name), contents)?;
+        self.record(name, kind)
+    }
+
+    /// Adds a saved file to the manifest, then rewrites the manifest.
+    /// name: The file's name.
+    /// kind: What the file holds.
+    fn record(&mut self, name: &str, kind: &str) -> anyhow::Result<()> {
+        self.manifest.artifacts.push(Artifact {
+            path: name.to_string(),
+            kind: kind.to_string(),
+            round: (self.round > 0).then_some(self.round),
+            saved: limits::now()
+        });
+
+        fs::write(self.path.join(MANIFEST), serde_json::to_string_pretty(&self.manifest)?)?;
+        Ok(())
+    }
+}
+
+/// Logs to the terminal, and to the current run's log file once there is one.
+struct RunLogger {
+    /// The terminal logger.
+    inner: Box<dyn Log>
+}
+
+impl Log for RunLogger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        self.inner.enabled(metadata)
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.inner.enabled(record.metadata()) {
+            return;
+        }
+        self.inner.log(record);
+
+        if let Some(file) = LOG_FILE.lock().unwrap().as_mut() {
+            let _ = writeln!(file, "{} {:<5} [{}] {}",
+                limits::now(), record.level(), record.target(), record.args());
+        }
+    }
+
+    fn flush(&self) {
+        self.inner.flush();
+        if let Some(file) = LOG_FILE.lock().unwrap().as_mut() {
+            let _ = file.flush();
+        }
+    }
+}
+
+/// Initializes the logger, which also copies the log into run directories.
+/// The level is read from `RUST_LOG`, like `pretty_env_logger::init`.
+pub fn init_logger() {
+    let inner = pretty_env_logger::formatted_builder()
+        .parse_env("RUST_LOG")
+        .build();
+    let level = inner.filter();
+
+    log::set_boxed_logger(Box::new(RunLogger { inner: Box::new(inner) }))
+        .expect("The logger was already initialized.");
+    log::set_max_level(level);
+}
+
+#[cfg(test)]
+mod test {
+    use std::fs;
+    use crate::artifacts::{RunDirectory, LOG_FILE, MANIFEST};
+    use crate::config::Config;
+
+    #[test]
+    pub fn indexes_run_files() {
+        let dir = std::env::temp_dir().join(format!("word-terminator-runs-{}", std::process::id()));
+        let config = Config { runs_dir: dir.to_string_lossy().into_owned(), ..Config::default() };
+
+        let mut run = RunDirectory::create(&config).unwrap().unwrap();
+        run.start_round();
+        run.save_json("traces.json", "traces", &vec![(30, 33)]).unwrap();
+        run.copy("stats.json", "stats", "missing.json").unwrap();
+
+        let manifest = fs::read_to_string(run.path().join(MANIFEST)).unwrap();
+        assert!(manifest.contains("\"config.json\"") && manifest.contains("\"round-1-traces.json\""));
+        assert!(!manifest.contains("stats.json"));
+        assert!(run.path().join("round-1-traces.json").exists());
+
+        // Nothing is saved without a runs directory.
+        assert!(RunDirectory::create(&Config::default()).unwrap().is_none());
+
+        *LOG_FILE.lock().unwrap() = None;
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,106 @@
+use std::collections::HashSet;
+use crate::arena;
+use crate::trie;
+
+/// The letters which can appear on a tile.
+pub const TILE_ALPHABET: &str = "abcdefghijklmnopqrstuvwxyz";
+
+/// The entries of a dictionary which can never be played, and the words worth keeping.
+#[derive(Clone, Debug, Default)]
+pub struct AuditReport {
+    /// The number of entries read.
+    pub entries: usize,
+    /// The entries needing more tiles than the board has.
+    pub too_long: Vec<String>,
+    /// The entries with characters which aren't on any tile.
+    pub outside_alphabet: Vec<String>,
+    /// The entries which are the same as an earlier entry, once normalized.
+    pub duplicates: Vec<String>,
+    /// The lines of the entries which can be played, as they were written.
+    pub kept: Vec<String>,
+    /// The trie nodes built only for entries which can never be played.
+    pub wasted_nodes: usize
+}
+
+impl AuditReport {
+    /// Returns the memory the unplayable entries take up in an arena trie, in bytes.
+    pub fn wasted_bytes(&self) -> usize {
+        self.wasted_nodes * arena::NODE_BYTES
+    }
+}
+
+/// Finds the entries of a dictionary which can never be played.
+/// contents: The dictionary file. Each line is a word, optionally followed by its frequency.
+/// tiles: The number of tiles on the board.
+/// strip_diacritics: Should diacritics be removed from words?
+pub fn audit(contents: &str, tiles: usize, strip_diacritics: bool) -> AuditReport {
+    let mut report = AuditReport::default();
+    let mut seen = HashSet::new();
+    let mut loaded = Vec::new();
+    let mut playable = Vec::new();
+
+    for line in contents.lines() {
+        let Some(entry) = line.split_whitespace().next() else {
+            continue;
+        };
+        report.entries += 1;
+
+        let word = trie::normalize(entry, strip_diacritics);
+        if !seen.insert(word.clone()) {
+            report.duplicates.push(entry.to_string());
+            continue;
+        }
+        loaded.push(word.clone());
+
+        // A "Qu" tile holds two letters.
+        if word.chars().any(|c| !TILE_ALPHABET.contains(c)) {
+            report.outside_alphabet.push(entry.to_string());
+        } else if word.chars().count() - word.matches("qu").count() > tiles {
+            report.too_long.push(entry.to_string());
+        } else {
+            playable.push(word);
+            report.kept.push(line.trim().to_string());
+        }
+    }
+
+    report.wasted_nodes = count_nodes(loaded) - count_nodes(playable);
+    report
+}
+
+/// Counts the nodes in a trie of the given words, including the root.
+/// words: The unique words.
+fn count_nodes(mut words: Vec<String>) -> usize {
+    words.sort_unstable();
+
+    let mut nodes = 1;
+    let mut previous = "";
+    for word in &words {
+        // Each word adds a node for every character after its shared prefix.
+        let shared = word.chars().zip(previous.chars()).take_while(|(a, b)| a == b).count();
+        nodes += word.chars().count() - shared;
+        previous = word;
+    }
+
+    nodes
+}
+
+#[cfg(test)]
+mod test {
+    use crate::audit::audit;
+
+    #[test]
+    pub fn finds_unplayable_entries() {
+        let contents = "cat 90\ncats\nCat 12\ndéjà\nantidisestablishmentarianism\nquantum\n\n";
+        let report = audit(contents, 6, false);
+
+        assert_eq!(report.entries, 6);
+        assert_eq!(report.duplicates, vec!["Cat"]);
+        assert_eq!(report.outside_alphabet, vec!["déjà"]);
+        assert_eq!(report.too_long, vec!["antidisestablishmentarianism"]);
+        assert_eq!(report.kept, vec!["cat 90", "cats", "quantum"]);
+        assert_eq!(report.wasted_nodes, 4 + 28);
+
+        // Stripped diacritics leave playable letters.
+        assert!(audit(contents, 6, true).outside_alphabet.is_empty());
+    }
+}
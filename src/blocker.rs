@@ -0,0 +1,101 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Once;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use log::{info, warn};
+use windows::Win32::Foundation::{HINSTANCE, HWND, LPARAM, LRESULT, WPARAM};
+use windows::Win32::UI::WindowsAndMessaging::*;
+use crate::config::Config;
+use crate::mouse::MouseBackendKind;
+
+/// The longest input stays blocked, in case a trace never finishes.
+pub const MAX_BLOCK: Duration = Duration::from_secs(10);
+
+/// When physical input is let through again, in milliseconds since the UNIX epoch. (0 when not blocked)
+static BLOCKED_UNTIL: AtomicU64 = AtomicU64::new(0);
+/// Installs the mouse hook once, however many blockers are created.
+static HOOK: Once = Once::new();
+
+/// Keeps the user's mouse from moving the cursor while words are traced.
+/// A low-level mouse hook drops events which weren't injected by software,
+/// so moves sent through SendInput still reach the mirror.
+/// The keyboard isn't blocked, so the bot can always be stopped.
+pub struct InputBlocker {
+    /// Should input be blocked?
+    enabled: bool
+}
+
+/// Lets physical input through again when dropped.
+pub struct BlockGuard;
+
+impl InputBlocker {
+    /// Creates a blocker, installing the mouse hook if it's enabled.
+    /// config: The application configuration.
+    pub fn new(config: &Config) -> Self {
+        let enabled = config.block_input;
+        if enabled {
+            HOOK.call_once(|| {
+                std::thread::spawn(run_hook);
+            });
+            info!("Physical mouse input will be blocked while tracing.");
+
+            // The server's mouse isn't injected, so it's blocked too if it's plugged into this PC.
+            if MouseBackendKind::parse(&config.mouse_backend) == MouseBackendKind::Server {
+                warn!("Blocking input also blocks the mouse server if it's plugged into this PC.");
+            }
+        }
+
+        InputBlocker { enabled }
+    }
+
+    /// Blocks physical mouse input until the guard is dropped, or for at most `MAX_BLOCK`.
+    pub fn block(&self) -> Option<BlockGuard> {
+        if !self.enabled {
+            return None;
+        }
+
+        BLOCKED_UNTIL.store(now_millis() + MAX_BLOCK.as_millis() as u64, Ordering::SeqCst);
+        Some(BlockGuard)
+    }
+}
+
+impl Drop for BlockGuard {
+    fn drop(&mut self) {
+        BLOCKED_UNTIL.store(0, Ordering::SeqCst);
+    }
+}
+
+/// Installs the mouse hook, then handles messages for it until the program exits.
+/// Low-level hooks are called on the thread which installed them, which must handle messages.
+fn run_hook() {
+    unsafe {
+        if let Err(error) = SetWindowsHookExW(WH_MOUSE_LL, Some(mouse_hook), HINSTANCE::default(), 0) {
+            warn!("Failed to install the mouse hook, input won't be blocked: {:?}", error);
+            return;
+        }
+
+        let mut message = MSG::default();
+        while GetMessageW(&mut message, HWND::default(), 0, 0).as_bool() {
+            let _ = TranslateMessage(&message);
+            DispatchMessageW(&message);
+        }
+    }
+}
+
+/// Drops physical mouse events while input is blocked.
+unsafe extern "system" fn mouse_hook(code: i32, wparam: WPARAM, lparam: LPARAM) -> LRESULT {
+    if code == HC_ACTION as i32 && now_millis() < BLOCKED_UNTIL.load(Ordering::SeqCst) {
+        let event = &*(lparam.0 as *const MSLLHOOKSTRUCT);
+        if event.flags & LLMHF_INJECTED == 0 {
+            return LRESULT(1);
+        }
+    }
+
+    CallNextHookEx(HHOOK::default(), code, wparam, lparam)
+}
+
+/// Returns the milliseconds since the UNIX epoch.
+fn now_millis() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |time| time.as_millis() as u64)
+}
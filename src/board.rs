@@ -1,7 +1,12 @@
+use image::imageops::FilterType;
 use image::{DynamicImage, RgbImage, RgbaImage};
 use log::warn;
-use crate::{screen, solver};
-use crate::solver::Word;
+use std::fs;
+use std::path::Path;
+use crossterm::style::Stylize;
+use serde::{Deserialize, Serialize};
+use crate::letters::Letters;
+use crate::{hash, screen};
 
 /// This is the pair where the board starts.
 pub const BOARD_TOP: (u32, u32) = (57, 480);
@@ -16,43 +21,534 @@ pub const TILE_SIZE: u32 = 83;
 /// The gap between tiles is 12 pixels.
 pub const TILE_GAP: u32 = 12;
 
+/// The fewest letter pixels in a row or column of the board for it to count as part of a letter.
+pub const MIN_INK: u32 = 2;
+/// Parts of a letter closer than this fraction of the tile pitch are joined. (e.g. the dot of an 'i')
+pub const INK_JOIN: f64 = 0.25;
+/// How far the detected tile pitch may be from the layout's, as a fraction of it.
+pub const PITCH_TOLERANCE: f64 = 0.25;
+
 /// This is the mouse coordinates for the first tile on the board.
 pub const START_POS: (i32, i32) = (35, 165);
 
+/// This is the row where the Anagrams rack's tiles start.
+pub const RACK_TOP: u32 = 700;
+/// The rack is centered on this column.
+pub const RACK_CENTER: u32 = 261;
+/// A rack tile is a 64 pixel square.
+pub const RACK_TILE_SIZE: u32 = 64;
+/// The gap between rack tiles is 8 pixels.
+pub const RACK_TILE_GAP: u32 = 8;
+
 /// This is the error for color comparison.
 pub const ERROR: u8 = 16;
 
-/// Identifies valid words in the image, sorted by length.
-/// image: The image to process.
-pub fn words_in_image(image: &RgbaImage) -> Vec<Word> {
-    // Process the image.
+/// The largest supported number of rows or columns.
+/// Tile indices and visited masks are limited to 64 tiles.
+pub const MAX_SIDE: usize = 8;
+
+/// A tile position on the game board.
+#[derive(Clone, Copy, Debug, Default)]
+#[derive(Eq, Hash, PartialEq, Serialize, Deserialize)]
+pub struct GridPos {
+    /// The row index. (y-coordinate)
+    pub row: usize,
+    /// The column index. (x-coordinate)
+    pub col: usize
+}
+
+impl GridPos {
+    /// Creates a new grid position.
+    /// row: The row index.
+    /// col: The column index.
+    pub fn new(row: usize, col: usize) -> Self {
+        GridPos { row, col }
+    }
+
+    /// Returns this position's tile index. (row * MAX_SIDE + col)
+    /// Positions outside of the largest board don't have an index.
+    pub fn index(&self) -> u8 {
+        debug_assert!(self.row < MAX_SIDE && self.col < MAX_SIDE);
+        (self.row * MAX_SIDE + self.col) as u8
+    }
+
+    /// Creates a grid position from a tile index.
+    /// index: The tile index.
+    pub fn from_index(index: u8) -> Self {
+        GridPos::new(index as usize / MAX_SIDE, index as usize % MAX_SIDE)
+    }
+
+    /// Offsets this position by a (row, column) delta.
+    /// Returns None if the result would be negative.
+    /// delta: The change in row and column.
+    pub fn offset(&self, (dr, dc): (i32, i32)) -> Option<GridPos> {
+        let row = self.row as i32 + dr;
+        let col = self.col as i32 + dc;
+
+        if row < 0 || col < 0 {
+            return None;
+        }
+
+        Some(GridPos::new(row as usize, col as usize))
+    }
+}
+
+/// The number of rows and columns on a board, and which tiles are missing.
+/// Larger boards fit into the same area with smaller tiles,
+/// so the tile size and spacing are scaled from the 4x4 board.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct Layout {
+    /// The number of rows.
+    pub rows: usize,
+    /// The number of columns.
+    pub cols: usize,
+    /// The tiles which aren't part of the board, as a mask of tile indices.
+    /// This is used by shaped boards, like the cross and donut layouts.
+    pub holes: u64
+}
+
+impl Layout {
+    /// Creates a board layout.
+    /// Each side is limited to between 1 and `MAX_SIDE` tiles.
+    /// rows: The number of rows.
+    /// cols: The number of columns.
+    pub fn new(rows: usize, cols: usize) -> Self {
+        Layout {
+            rows: rows.clamp(1, MAX_SIDE),
+            cols: cols.clamp(1, MAX_SIDE),
+            holes: 0
+        }
+    }
+
+    /// Marks tiles as missing from the board.
+    /// Holes which aren't tiles of the board are ignored. (see `invalid_holes`)
+    /// holes: The missing tiles, as (row, col) pairs. (e.g. "0,0 0,4 4,0 4,4")
+    pub fn with_holes(mut self, holes: &str) -> Self {
+        for hole in holes.split_whitespace() {
+            if let Some(pos) = self.parse_hole(hole) {
+                self.holes |= 1 << pos.index();
+            }
+        }
+
+        self
+    }
+
+    /// Returns the holes which aren't tiles of the board.
+    /// holes: The missing tiles, as (row, col) pairs.
+    pub fn invalid_holes<'a>(&self, holes: &'a str) -> Vec<&'a str> {
+        holes.split_whitespace()
+            .filter(|hole| self.parse_hole(hole).is_none())
+            .collect()
+    }
+
+    /// Parses a hole, if it's a tile of the board.
+    /// hole: The missing tile, as a (row, col) pair.
+    fn parse_hole(&self, hole: &str) -> Option<GridPos> {
+        hole.split_once(',')
+            .and_then(|(row, col)| Some(GridPos::new(row.trim().parse().ok()?, col.trim().parse().ok()?)))
+            .filter(|pos| pos.row < self.rows && pos.col < self.cols)
+    }
+
+    /// Checks if a tile is part of the board.
+    /// pos: The position of the tile.
+    pub fn is_playable(&self, pos: GridPos) -> bool {
+        pos.row < self.rows && pos.col < self.cols && self.holes & (1 << pos.index()) == 0
+    }
+
+    /// Returns how many times smaller the tiles are than on a 4x4 board.
+    /// The tiles and gaps along the longer side fill the board, inside its padding.
+    pub fn scale(&self) -> f64 {
+        let side = self.rows.max(self.cols) as u32;
+        ((TILE_SIZE + TILE_GAP) * side - TILE_GAP) as f64 / (BOARD_SIZE - 2 * BOARD_PADDING) as f64
+    }
+
+    /// Returns the distance between the starts of adjacent tiles, in pixels.
+    pub fn pitch(&self) -> f64 {
+        (TILE_SIZE + TILE_GAP) as f64 / self.scale()
+    }
+
+    /// Returns the side length of a tile, in pixels.
+    pub fn tile_size(&self) -> u32 {
+        (TILE_SIZE as f64 / self.scale()).floor() as u32
+    }
+
+    /// Returns the top-left pixel of a tile, relative to the board.
+    /// row: The row of the tile.
+    /// column: The column of the tile.
+    pub fn tile_origin(&self, row: u32, column: u32) -> (u32, u32) {
+        let offset = |index: u32| BOARD_PADDING + (index as f64 * self.pitch()).round() as u32;
+        (offset(column), offset(row))
+    }
+}
+
+impl Default for Layout {
+    fn default() -> Self {
+        Layout::new(4, 4)
+    }
+}
+
+/// Where the tiles are in a board image.
+/// The grid is detected from the letters each round, so scaled captures are still cropped correctly.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct TileGrid {
+    /// The center of the first tile, in pixels.
+    pub first: (f64, f64),
+    /// The distance between the centers of adjacent tiles along each axis, in pixels.
+    pub pitch: (f64, f64)
+}
+
+impl TileGrid {
+    /// Creates the grid expected from the layout's constants.
+    /// layout: The number of rows and columns on the board.
+    pub fn from_layout(layout: Layout) -> Self {
+        let center = BOARD_PADDING as f64 + TILE_SIZE as f64 / layout.scale() / 2.0;
+        TileGrid { first: (center, center), pitch: (layout.pitch(), layout.pitch()) }
+    }
+
+    /// Finds the tiles in a filtered board image.
+    /// The layout's constants are used if the tiles can't be detected.
+    /// image: The filtered board image.
+    /// layout: The number of rows and columns on the board.
+    pub fn find(image: &RgbImage, layout: Layout) -> Self {
+        TileGrid::detect(image, layout).unwrap_or_else(|| TileGrid::from_layout(layout))
+    }
+
+    /// Detects the tiles in a filtered board image from its projection profiles.
+    /// The letters' pixels are counted along each row and column, then grouped into a band per tile,
+    /// and a grid is fit through the centers of the bands.
+    /// Returns None if a row or column of tiles has no letters, or the grid doesn't fit the layout.
+    /// image: The filtered board image.
+    /// layout: The number of rows and columns on the board.
+    pub fn detect(image: &RgbImage, layout: Layout) -> Option<Self> {
+        let mut columns = vec![0u32; image.width() as usize];
+        let mut rows = vec![0u32; image.height() as usize];
+        for (x, y, pixel) in image.enumerate_pixels() {
+            if pixel.0 == [0, 0, 0] {
+                columns[x as usize] += 1;
+                rows[y as usize] += 1;
+            }
+        }
+
+        let nominal = layout.pitch();
+        let (x, pitch_x) = fit_grid(&ink_centers(&columns, nominal), layout.cols, nominal)?;
+        let (y, pitch_y) = fit_grid(&ink_centers(&rows, nominal), layout.rows, nominal)?;
+
+        Some(TileGrid { first: (x, y), pitch: (pitch_x, pitch_y) })
+    }
+
+    /// Returns the rectangle to crop a tile from. (x, y, width, height)
+    /// row: The row of the tile.
+    /// column: The column of the tile.
+    pub fn tile_rect(&self, row: u32, column: u32) -> (u32, u32, u32, u32) {
+        // Tiles fill the pitch, except for the gap.
+        let size = |pitch: f64| pitch * TILE_SIZE as f64 / (TILE_SIZE + TILE_GAP) as f64;
+        let (width, height) = (size(self.pitch.0), size(self.pitch.1));
+
+        let x = self.first.0 + column as f64 * self.pitch.0 - width / 2.0;
+        let y = self.first.1 + row as f64 * self.pitch.1 - height / 2.0;
+        (x.round().max(0.0) as u32, y.round().max(0.0) as u32, width.round() as u32, height.round() as u32)
+    }
+}
+
+/// A board read from a screenshot.
+#[derive(Clone, Debug)]
+pub struct RecognizedBoard {
+    /// The rows of lowercase tiles.
+    pub tiles: Vec<Vec<String>>,
+    /// The similarity score of each tile's letter. (0 to 1)
+    pub confidence: Vec<Vec<f64>>,
+    /// The closest matching letters for each tile, best first.
+    pub candidates: Vec<Vec<Vec<(String, f64)>>>
+}
+
+impl RecognizedBoard {
+    /// Lists the most probable readings of the board's ambiguous tiles.
+    /// A letter is an alternative if it scores within the margin of the tile's best.
+    /// Tiles which were edited by the user are never ambiguous.
+    /// Readings are ordered by the product of their letters' scores,
+    /// so the first reading always uses each tile's best letter.
+    /// margin: The largest score difference between ambiguous letters.
+    /// width: The number of readings to keep.
+    pub fn hypotheses(&self, margin: f64, width: usize) -> Vec<Vec<Vec<String>>> {
+        // Each reading is its tiles, and the log of its probability.
+        let mut beam = vec![(self.tiles.clone(), 0f64)];
+
+        for (row, line) in self.candidates.iter().enumerate() {
+            for (col, candidates) in line.iter().enumerate() {
+                let Some((best, score)) = candidates.first() else {
+                    continue;
+                };
+                if *best != self.tiles[row][col] {
+                    continue;
+                }
+
+                // Branch every reading on each close letter.
+                let mut next = Vec::with_capacity(beam.len() * candidates.len());
+                for (tiles, probability) in &beam {
+                    for (letter, other) in candidates {
+                        if score - other >= margin && letter != best {
+                            continue;
+                        }
+
+                        let mut tiles = tiles.clone();
+                        tiles[row][col] = letter.clone();
+                        next.push((tiles, probability + other.max(f64::EPSILON).ln()));
+                    }
+                }
+
+                // Only keep the most probable readings.
+                next.sort_by(|a, b| b.1.total_cmp(&a.1));
+                next.truncate(width.max(1));
+                beam = next;
+            }
+        }
+
+        beam.into_iter().map(|(tiles, _)| tiles).collect()
+    }
+}
+
+/// A board saved to a `.board` file.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct BoardFile {
+    /// The number of rows on the board.
+    pub rows: usize,
+    /// The number of columns on the board.
+    pub cols: usize,
+    /// The rows of lowercase tiles.
+    pub tiles: Vec<Vec<String>>,
+    /// The bonus on each tile which has one. (e.g. "2x")
+    #[serde(default)]
+    pub bonuses: Vec<(GridPos, String)>,
+    /// A hash of the screenshot the board was read from.
+    #[serde(default)]
+    pub source_hash: Option<String>
+}
+
+impl BoardFile {
+    /// Creates a board file from a board's tiles.
+    /// tiles: The rows of tiles.
+    /// source: The screenshot the board was read from, if any.
+    pub fn new(tiles: Vec<Vec<String>>, source: Option<&RgbaImage>) -> Self {
+        BoardFile {
+            rows: tiles.len(),
+            cols: tiles.first().map_or(0, Vec::len),
+            tiles,
+            bonuses: Vec::new(),
+            source_hash: source.map(hash_image)
+        }
+    }
+
+    /// Loads a board from a file.
+    /// path: The path to the `.board` file.
+    pub fn load<P: AsRef<Path>>(path: P) -> anyhow::Result<Self> {
+        let board: BoardFile = serde_json::from_str(&fs::read_to_string(path)?)?;
+
+        // Make sure the layout matches the tiles.
+        if board.tiles.len() != board.rows ||
+            board.tiles.iter().any(|row| row.len() != board.cols) {
+            anyhow::bail!("The board's tiles don't match its {}x{} layout.", board.rows, board.cols);
+        }
+        if board.rows > MAX_SIDE || board.cols > MAX_SIDE {
+            anyhow::bail!("Boards larger than {}x{} aren't supported.", MAX_SIDE, MAX_SIDE);
+        }
+
+        Ok(board)
+    }
+
+    /// Saves the board to a file.
+    /// path: The path to the `.board` file.
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> anyhow::Result<()> {
+        fs::write(path, serde_json::to_string_pretty(self)?)?;
+        Ok(())
+    }
+}
+
+/// Hashes an image's pixels. (64-bit FNV-1a, as hex)
+/// image: The image to hash.
+pub fn hash_image(image: &RgbaImage) -> String {
+    hash::fnv_hex(image.as_raw())
+}
+
+/// Filters a screenshot and crops it down to the board.
+/// image: The screenshot to process.
+pub fn board_image(image: &RgbaImage) -> RgbImage {
     let filtered = filter_image(image);
-    let image = DynamicImage::ImageRgb8(filtered)
+    DynamicImage::ImageRgb8(filtered)
         .crop(BOARD_TOP.0, BOARD_TOP.1, BOARD_SIZE, BOARD_SIZE)
-        .to_rgb8();
+        .to_rgb8()
+}
+
+/// Reads the letters on the board in the image.
+/// image: The image to process.
+/// letters: The letters library to match tiles against.
+/// layout: The number of rows and columns on the board.
+/// count: The number of candidate letters to keep for each tile.
+#[tracing::instrument(skip_all)]
+pub fn read_board(image: &RgbaImage, letters: &Letters, layout: Layout, count: usize) -> RecognizedBoard {
+    // Process the image.
+    let image = board_image(image);
 
     // Use OCR to process the board.
-    let lines = screen::process_board(&image);
+    let lines = screen::process_candidates(&image, letters, layout, count);
 
     // Convert the lines into the proper board layout.
-    let mut board: Vec<Vec<String>> = Vec::new();
-    for line in lines.split('\n') {
-        let mut row: Vec<String> = Vec::new();
-        for c in line.chars() {
-            row.push(c.to_lowercase().to_string());
+    let candidates: Vec<Vec<Vec<(String, f64)>>> = lines.iter()
+        .map(|line| line.iter()
+            .map(|tile| tile.iter()
+                .map(|(c, score)| (letters.tile_text(*c), *score))
+                .collect())
+            .collect())
+        .collect();
+    let tiles = candidates.iter()
+        .map(|line| line.iter()
+            .map(|tile| tile.first().map_or(" ".to_string(), |(c, _)| c.clone()))
+            .collect())
+        .collect();
+    let confidence = candidates.iter()
+        .map(|line| line.iter()
+            .map(|tile| tile.first().map_or(0f64, |(_, score)| *score))
+            .collect())
+        .collect();
+
+    RecognizedBoard { tiles, confidence, candidates }
+}
+
+/// Reads the letters in an Anagrams rack.
+/// Returns each lowercase letter, with its similarity score.
+/// image: The image to process.
+/// letters: The letters library to match tiles against.
+/// size: The number of tiles in the rack.
+#[tracing::instrument(skip_all)]
+pub fn read_rack(image: &RgbaImage, letters: &Letters, size: usize) -> Vec<(String, f64)> {
+    let image = DynamicImage::ImageRgb8(filter_image(image));
+
+    (0..size as u32)
+        .map(|index| {
+            let (x, y) = rack_tile_origin(size, index);
+            let tile = image.crop_imm(x, y, RACK_TILE_SIZE, RACK_TILE_SIZE)
+                .resize_exact(TILE_SIZE, TILE_SIZE, FilterType::Triangle)
+                .to_rgb8();
+
+            let (letter, score) = letters.compare(&tile);
+            (letters.tile_text(letter), score)
+        })
+        .collect()
+}
+
+/// Returns the top-left pixel of a rack tile.
+/// The rack's tiles are centered, so their position depends on the rack's size.
+/// size: The number of tiles in the rack.
+/// index: The index of the tile, from the left.
+pub fn rack_tile_origin(size: usize, index: u32) -> (u32, u32) {
+    let pitch = RACK_TILE_SIZE + RACK_TILE_GAP;
+    let width = (size as u32 * pitch).saturating_sub(RACK_TILE_GAP);
+
+    (RACK_CENTER.saturating_sub(width / 2) + index * pitch, RACK_TOP)
+}
+
+/// Formats a board as a grid, colored by confidence.
+/// Green tiles are confident, yellow are uncertain, and red are likely wrong.
+/// board: The board to format.
+pub fn format_board(board: &RecognizedBoard) -> String {
+    let columns = board.tiles.first().map_or(0, Vec::len);
+    let separator = format!("+{}", "---+".repeat(columns));
+
+    let mut output = separator.clone();
+    for (tiles, confidence) in board.tiles.iter().zip(&board.confidence) {
+        output.push_str("\n|");
+        for (tile, score) in tiles.iter().zip(confidence) {
+            let tile = format!("{:^3}", tile.to_uppercase());
+            let tile = match *score {
+                score if score >= 0.9 => tile.green(),
+                score if score >= 0.75 => tile.yellow(),
+                _ => tile.red()
+            };
+            output.push_str(&format!("{tile}|"));
         }
-        board.push(row);
+        output.push('\n');
+        output.push_str(&separator);
+    }
+
+    output
+}
+
+/// Finds the tile under a pixel of a captured frame.
+/// Returns None if the pixel is outside the board, between tiles, or over a hole.
+/// layout: The number of rows and columns on the board.
+/// pixel: The (x, y) pixel coordinates.
+pub fn tile_at(layout: Layout, (x, y): (u32, u32)) -> Option<GridPos> {
+    let offset = |pixel: u32, top: u32, count: usize| {
+        let pixel = pixel.checked_sub(top)?;
+        (0..count).find(|index| {
+            let start = layout.tile_origin(*index as u32, *index as u32).0;
+            pixel >= start && pixel < start + layout.tile_size()
+        })
+    };
+
+    let pos = GridPos::new(
+        offset(y, BOARD_TOP.1, layout.rows)?,
+        offset(x, BOARD_TOP.0, layout.cols)?
+    );
+    layout.is_playable(pos).then_some(pos)
+}
+
+/// Finds the center of each band of letter pixels in a projection profile.
+/// profile: The number of letter pixels in each row or column.
+/// pitch: The expected distance between tiles, in pixels.
+fn ink_centers(profile: &[u32], pitch: f64) -> Vec<f64> {
+    // Each band is the (weighted sum, total, last position) of its pixels.
+    let mut bands: Vec<(f64, f64, usize)> = Vec::new();
+    for (position, ink) in profile.iter().enumerate().filter(|(_, ink)| **ink >= MIN_INK) {
+        let ink = *ink as f64;
+        match bands.last_mut() {
+            Some(band) if (position - band.2) as f64 <= pitch * INK_JOIN => {
+                band.0 += position as f64 * ink;
+                band.1 += ink;
+                band.2 = position;
+            },
+            _ => bands.push((position as f64 * ink, ink, position))
+        }
+    }
+
+    bands.iter().map(|(sum, total, _)| sum / total).collect()
+}
+
+/// Fits evenly spaced tiles through the centers of their letters.
+/// Returns the center of the first tile and the pitch,
+/// or None if there isn't a center for each tile, or the pitch is too far off.
+/// centers: The centers of the letters along an axis.
+/// count: The number of tiles along the axis.
+/// pitch: The expected distance between tiles, in pixels.
+fn fit_grid(centers: &[f64], count: usize, pitch: f64) -> Option<(f64, f64)> {
+    if centers.len() != count {
+        return None;
+    }
+    if count == 1 {
+        return Some((centers[0], pitch));
+    }
+
+    // Fit a line through the centers, by their index.
+    let n = count as f64;
+    let mean_index = (n - 1.0) / 2.0;
+    let mean_center = centers.iter().sum::<f64>() / n;
+    let spread: f64 = (0..count).map(|i| (i as f64 - mean_index).powi(2)).sum();
+    let fitted = centers.iter().enumerate()
+        .map(|(i, center)| (i as f64 - mean_index) * (center - mean_center))
+        .sum::<f64>() / spread;
+
+    if (fitted - pitch).abs() > pitch * PITCH_TOLERANCE {
+        return None;
     }
 
-    // Find all valid words.
-    solver::find_all_words(&board)
+    Some((mean_center - fitted * mean_index, fitted))
 }
 
 /// Crops an image to find the row and column specified.
 /// image: The source image to crop.
+/// grid: Where the tiles are in the image.
 /// row: The row to crop.
 /// column: The column to crop.
-pub fn crop_image(mut image: DynamicImage, row: u32, column: u32) -> RgbImage {
+pub fn crop_image(mut image: DynamicImage, grid: &TileGrid, row: u32, column: u32) -> RgbImage {
     // Check if the image is the correct dimensions.
     let (width, height) = (image.width(), image.height());
     if width != BOARD_SIZE || height != BOARD_SIZE {
@@ -60,18 +556,22 @@ pub fn crop_image(mut image: DynamicImage, row: u32, column: u32) -> RgbImage {
         return image.to_rgb8();
     }
 
-    // Crop the image using the dimensions above.
-    let x = (column * TILE_SIZE) + BOARD_PADDING + (column * TILE_GAP);
-    let y = (row * TILE_SIZE) + BOARD_PADDING + (row * TILE_GAP);
+    // Crop the image using the detected grid.
+    let (x, y, width, height) = grid.tile_rect(row, column);
+    let tile = image.crop(x, y, width, height);
 
-    image
-        .crop(x, y, TILE_SIZE, TILE_SIZE)
-        .to_rgb8()
+    // Other sizes are scaled, since letters are compared at the 4x4 size.
+    if (width, height) != (TILE_SIZE, TILE_SIZE) {
+        return tile.resize_exact(TILE_SIZE, TILE_SIZE, FilterType::Triangle).to_rgb8();
+    }
+
+    tile.to_rgb8()
 }
 
 /// Filters an image turning all non-black pixels into white.
 /// This uses the RGB limit defined in the `ERROR` constant.
 /// image: The RGB image to filter.
+#[tracing::instrument(skip_all)]
 pub fn filter_image(image: &RgbaImage) -> RgbImage {
     let mut filtered_pixels = vec![0; (image.width() * image.height() * 3) as usize];
 
@@ -90,24 +590,133 @@ pub fn filter_image(image: &RgbaImage) -> RgbImage {
         .expect("Failed to create filtered image")
 }
 
-/// Converts a grid (x, y) pair into mouse (x, y) coordinates.
-/// grid_pos: The grid position.
-/// x: The grid x-coordinate.
-/// y: The grid y-coordinate.
-pub fn grid_to_mouse(grid_pos: (i32, i32), x: i32, y: i32) -> (i32, i32) {
-    // Using the current grid position,
-    // find the delta we need to get to the tile specified.
-    // Getting the absolute position of a tile can be done with:
-    // (35 + (x * 30), 160 + (y * 20))
+#[cfg(test)]
+mod test {
+    use image::{Rgb, RgbImage, RgbaImage};
+    use crate::board::{tile_at, BoardFile, GridPos, Layout, RecognizedBoard, TileGrid, BOARD_PADDING, BOARD_SIZE, BOARD_TOP};
+
+    #[test]
+    pub fn board_file_round_trip() {
+        let tiles = vec![
+            vec!["a".to_string(), "b".to_string()],
+            vec!["qu".to_string(), "d".to_string()]
+        ];
+
+        let mut board = BoardFile::new(tiles, Some(&RgbaImage::new(2, 2)));
+        board.bonuses.push((GridPos::new(1, 0), "2x".to_string()));
+
+        let path = std::env::temp_dir().join("word-terminator-test.board");
+        board.save(&path).unwrap();
+        let loaded = BoardFile::load(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(loaded, board);
+        assert_eq!((loaded.rows, loaded.cols), (2, 2));
+        assert!(loaded.source_hash.is_some());
+    }
+
+    #[test]
+    pub fn indexes_tiles() {
+        for pos in [GridPos::new(0, 0), GridPos::new(3, 1), GridPos::new(7, 7)] {
+            assert_eq!(GridPos::from_index(pos.index()), pos);
+        }
+        assert_eq!(GridPos::new(1, 2).index(), 10);
+    }
+
+    #[test]
+    pub fn finds_tiles_under_pixels() {
+        let first = (BOARD_TOP.0 + BOARD_PADDING, BOARD_TOP.1 + BOARD_PADDING);
+        let layout = Layout::default();
 
-    // Use these to configure the offset.
-    const OFFSET_X: i32 = 30;
-    const OFFSET_Y: i32 = 33;
-    
-    let (gx, gy) = grid_pos;
+        assert_eq!(tile_at(layout, first), Some(GridPos::new(0, 0)));
+        assert_eq!(tile_at(layout, (first.0 + 95 * 3 + 82, first.1 + 95)), Some(GridPos::new(1, 3)));
+        assert_eq!(tile_at(layout, (first.0 + 83, first.1)), None);
+        assert_eq!(tile_at(layout, (first.0 - 1, first.1)), None);
+        assert_eq!(tile_at(layout, (first.0 + 95 * 4, first.1)), None);
+    }
+
+    #[test]
+    pub fn scales_larger_layouts() {
+        let layout = Layout::default();
+        assert_eq!((layout.pitch(), layout.tile_size()), (95.0, 83));
+        assert_eq!(layout.tile_origin(2, 3), (BOARD_PADDING + 285, BOARD_PADDING + 190));
+
+        // The last tile of a 5x5 board still ends inside the board's padding.
+        let layout = Layout::new(5, 5);
+        let (x, y) = layout.tile_origin(4, 4);
+        assert!(x + layout.tile_size() <= BOARD_SIZE - BOARD_PADDING);
+        assert!(y + layout.tile_size() > BOARD_SIZE - BOARD_PADDING - 2);
+        assert_eq!(tile_at(layout, (BOARD_TOP.0 + x, BOARD_TOP.1 + y)), Some(GridPos::new(4, 4)));
+
+        assert_eq!(Layout::new(0, 12), Layout::new(1, 8));
+    }
 
-    let current = (START_POS.0 + (gx * OFFSET_X), START_POS.1 + (gy * OFFSET_Y));
-    let target = (START_POS.0 + (x * OFFSET_X), START_POS.1 + (y * OFFSET_Y));
+    #[test]
+    pub fn detects_tile_grids() {
+        // Draw a letter on each tile of a board scaled up by 5%, with a dot above the first.
+        let (first, pitch) = (66.0, 99.75);
+        let mut image = RgbImage::from_pixel(BOARD_SIZE, BOARD_SIZE, Rgb([255, 255, 255]));
+        for (row, column) in (0..4).flat_map(|row| (0..4).map(move |column| (row, column))) {
+            let center = (first + column as f64 * pitch, first + row as f64 * pitch);
+            for (dx, dy) in (-12..12).flat_map(|dx| (-18..18).map(move |dy| (dx, dy))) {
+                image.put_pixel((center.0 as i32 + dx) as u32, (center.1 as i32 + dy) as u32, Rgb([0, 0, 0]));
+            }
+        }
+        for (dx, dy) in (-3..3).flat_map(|dx| (-28..-23).map(move |dy| (dx, dy))) {
+            image.put_pixel((first as i32 + dx) as u32, (first as i32 + dy) as u32, Rgb([0, 0, 0]));
+        }
+
+        let grid = TileGrid::detect(&image, Layout::default()).unwrap();
+        assert!((grid.pitch.0 - pitch).abs() < 0.5 && (grid.pitch.1 - pitch).abs() < 0.5);
+        assert!((grid.first.0 - first).abs() < 1.0 && (grid.first.1 - first).abs() < 2.0);
+        assert_eq!(grid.tile_rect(1, 2).2, 87);
+
+        // The constants are used without a letter in every row and column.
+        let blank = RgbImage::from_pixel(BOARD_SIZE, BOARD_SIZE, Rgb([255, 255, 255]));
+        assert_eq!(TileGrid::detect(&blank, Layout::default()), None);
+        assert_eq!(TileGrid::find(&blank, Layout::default()).tile_rect(2, 3),
+            (BOARD_PADDING + 285, BOARD_PADDING + 190, 83, 83));
+    }
 
-    (target.0 - current.0, target.1 - current.1)
-}
\ No newline at end of file
+    #[test]
+    pub fn reads_board_holes() {
+        let holes = "0,0 0,4 4, 0 2,2 5,5 corner";
+        let layout = Layout::new(5, 5).with_holes(holes);
+        assert_eq!(layout.invalid_holes(holes), vec!["4,", "0", "5,5", "corner"]);
+
+        assert_eq!(layout.holes.count_ones(), 3);
+        assert!(!layout.is_playable(GridPos::new(2, 2)));
+        assert!(layout.is_playable(GridPos::new(4, 0)));
+        assert!(!layout.is_playable(GridPos::new(0, 5)));
+
+        let (x, y) = layout.tile_origin(0, 0);
+        assert_eq!(tile_at(layout, (BOARD_TOP.0 + x, BOARD_TOP.1 + y)), None);
+    }
+
+    #[test]
+    pub fn lists_hypotheses() {
+        let tile = |candidates: &[(&str, f64)]| candidates.iter()
+            .map(|(c, score)| (c.to_string(), *score))
+            .collect::<Vec<_>>();
+
+        let board = RecognizedBoard {
+            tiles: vec![vec!["i".to_string(), "a".to_string(), "x".to_string()]],
+            confidence: vec![vec![0.90, 0.99, 0.95]],
+            candidates: vec![vec![
+                tile(&[("i", 0.90), ("l", 0.89)]),
+                tile(&[("a", 0.99), ("o", 0.50)]),
+                // This tile was corrected by the user.
+                tile(&[("k", 0.95), ("y", 0.94)])
+            ]]
+        };
+
+        let hypotheses = board.hypotheses(0.05, 8);
+        assert_eq!(hypotheses.len(), 2);
+        assert_eq!(hypotheses[0], board.tiles);
+        assert_eq!(hypotheses[1][0], vec!["l", "a", "x"]);
+
+        assert_eq!(board.hypotheses(1.0, 8).len(), 4);
+        assert_eq!(board.hypotheses(1.0, 3)[2][0], vec!["i", "o", "x"]);
+        assert_eq!(board.hypotheses(0.0, 8).len(), 1);
+    }
+}
@@ -0,0 +1,302 @@
+use std::fs;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use image::RgbaImage;
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+use tokio::time::sleep;
+use crate::board::START_POS;
+use crate::capture;
+use crate::config::Config;
+use crate::limits;
+use crate::mouse::Mouse;
+use crate::planner::CoordinateMapper;
+
+/// The move sizes to measure, in mouse units.
+pub const STEPS: [i32; 4] = [20, 40, 60, 90];
+/// How long to wait for the mirror to show the pointer after moving.
+pub const SETTLE_TIME: Duration = Duration::from_millis(300);
+/// How much a pixel's channel must change for the pixel to count as changed.
+pub const CHANGE_THRESHOLD: u8 = 32;
+/// The fewest changed pixels which can be the pointer.
+pub const MIN_CHANGED: usize = 8;
+
+/// The results of the last calibration, persisted between runs.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct Calibration {
+    /// When the calibration was measured, in seconds since the UNIX epoch.
+    pub time: u64,
+    /// The size of the captured frames which were measured, before they were scaled.
+    pub frame: (u32, u32),
+    /// The measured pointer gain along each axis.
+    pub gain: (f64, f64),
+    /// The measured pointer curve, shared by both axes.
+    pub curve: f64,
+    /// The root mean square error of the fit, in mouse units.
+    pub residual: f64,
+    /// The root mean square error of the fit, in pixels.
+    /// This is about how far a tile's center is missed by.
+    pub tile_error: f64
+}
+
+/// A reason the last calibration may no longer be accurate.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum CalibrationIssue {
+    /// The tile-center error, in pixels, is over the allowed error.
+    Inaccurate(f64),
+    /// The frames were this size when calibrated, which differs from the current size.
+    Resized((u32, u32))
+}
+
+impl Calibration {
+    /// Loads the calibration from a file.
+    /// Returns None if the file is missing or invalid.
+    /// path: The path to the calibration file.
+    pub fn load<P: AsRef<Path>>(path: P) -> Option<Self> {
+        fs::read_to_string(path)
+            .ok()
+            .and_then(|contents| serde_json::from_str(&contents).ok())
+    }
+
+    /// Saves the calibration to a file.
+    /// path: The path to the calibration file.
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> anyhow::Result<()> {
+        fs::write(path, serde_json::to_string_pretty(self)?)?;
+        Ok(())
+    }
+
+    /// Finds the reasons the calibration may no longer be accurate.
+    /// frame: The size of the frames being captured now, before they're scaled. (None if it's unknown)
+    /// max_error: The most tile-center error allowed, in pixels.
+    pub fn issues(&self, frame: Option<(u32, u32)>, max_error: f64) -> Vec<CalibrationIssue> {
+        let mut issues = Vec::new();
+        if max_error > 0.0 && self.tile_error > max_error {
+            issues.push(CalibrationIssue::Inaccurate(self.tile_error));
+        }
+        if frame.is_some_and(|frame| frame != self.frame) {
+            issues.push(CalibrationIssue::Resized(self.frame));
+        }
+
+        issues
+    }
+}
+
+/// Warns if the last calibration may no longer be accurate.
+/// Nothing is checked if there's no calibration file, or it's never been written.
+/// config: The application configuration.
+pub fn check_calibration(config: &Config) {
+    if config.calibration_file.is_empty() {
+        return;
+    }
+    let Some(calibration) = Calibration::load(&config.calibration_file) else {
+        return;
+    };
+
+    // Frames are scaled to the configured size, so the window is measured instead.
+    let frame = capture::capture_size(config);
+    for issue in calibration.issues(frame, config.calibration_error) {
+        match issue {
+            CalibrationIssue::Inaccurate(error) => warn!(
+                "The last calibration missed tile centers by {:.1} pixels; re-run the calibrate command.", error),
+            CalibrationIssue::Resized((width, height)) => warn!(
+                "The capture resolution changed from {}x{} since the last calibration; re-run the calibrate command.",
+                width, height)
+        }
+    }
+}
+
+/// Measures how far the mirror actually moves the pointer, compared to how far it's asked to.
+/// The pointer is found by comparing frames before and after each move.
+/// Logs the correction to set in the configuration, and saves the results to the calibration file.
+/// config: The application configuration.
+pub async fn calibrate(config: &Config) -> anyhow::Result<()> {
+    let mut mouse = Mouse::open(config).await?;
+    let frames = Arc::new(Mutex::new(capture::open(config)?));
+
+    // The tile spacing relates pixels to mouse units.
+    let mapper = CoordinateMapper::new(config.layout());
+    let pitch = mapper.layout.pitch();
+    let scale = [pitch / mapper.offset.0 as f64, pitch / mapper.offset.1 as f64];
+
+    let mut fits = Vec::new();
+    let mut frame = (0, 0);
+    let (mut squared, mut count) = ((0.0, 0.0), 0);
+    for (axis, &scale) in scale.iter().enumerate() {
+        let mut samples = Vec::new();
+        for step in STEPS {
+            mouse.move_absolute(START_POS, true).await?;
+            sleep(SETTLE_TIME).await;
+            let before = capture::next_frame(&frames).await?;
+
+            let (dx, dy) = if axis == 0 { (step, 0) } else { (0, step) };
+            mouse.move_relative(dx, dy).await?;
+            sleep(SETTLE_TIME).await;
+            let after = capture::next_frame(&frames).await?;
+            frame = after.dimensions();
+
+            match displacement(&before, &after, axis) {
+                Some(pixels) => {
+                    let actual = pixels / scale;
+                    info!("Requested {} units, moved {:.1} units.", step, actual);
+                    samples.push((step as f64, actual));
+                },
+                None => warn!("Couldn't find the pointer after moving {} units.", step)
+            }
+        }
+
+        let (gain, curve) = fit(&samples)
+            .ok_or_else(|| anyhow::anyhow!("Not enough moves were measured to calibrate."))?;
+        fits.push((gain, curve));
+
+        // Keep the error in both units, since pixels are what tiles are missed by.
+        let error = residual(&samples, gain, curve);
+        squared.0 += error.powi(2) * samples.len() as f64;
+        squared.1 += (error * scale).powi(2) * samples.len() as f64;
+        count += samples.len();
+    }
+
+    mouse.normalize().await;
+
+    // Window frames are scaled to the configured size, so measure the window itself.
+    let frame = capture::capture_size(config).unwrap_or(frame);
+
+    // The curve is shared by both axes.
+    let curve = (fits[0].1 + fits[1].1) / 2.0;
+    info!("Set pointer_gain_x to {:.3}, pointer_gain_y to {:.3}, and pointer_curve to {:.3}.",
+        fits[0].0, fits[1].0, curve);
+
+    let calibration = Calibration {
+        time: limits::now(),
+        frame,
+        gain: (fits[0].0, fits[1].0),
+        curve,
+        residual: (squared.0 / count as f64).sqrt(),
+        tile_error: (squared.1 / count as f64).sqrt()
+    };
+    info!("The moves were off by {:.2} units ({:.1} pixels) on average.", calibration.residual, calibration.tile_error);
+    if calibration.tile_error > config.calibration_error && config.calibration_error > 0.0 {
+        warn!("The pointer is too inconsistent to hit tile centers; try a mirror without pointer acceleration.");
+    }
+
+    if !config.calibration_file.is_empty() {
+        calibration.save(&config.calibration_file)?;
+    }
+
+    Ok(())
+}
+
+/// Fits the (gain, curve) of pointer moves, where `actual = gain * requested ^ curve`.
+/// Returns None if there aren't at least two distinct moves.
+/// samples: The (requested, actual) distance of each move.
+pub fn fit(samples: &[(f64, f64)]) -> Option<(f64, f64)> {
+    // The model is a line on a log-log scale.
+    let points: Vec<(f64, f64)> = samples.iter()
+        .filter(|(requested, actual)| *requested > 0.0 && *actual > 0.0)
+        .map(|(requested, actual)| (requested.ln(), actual.ln()))
+        .collect();
+    if points.len() < 2 {
+        return None;
+    }
+
+    let count = points.len() as f64;
+    let mean_x = points.iter().map(|(x, _)| x).sum::<f64>() / count;
+    let mean_y = points.iter().map(|(_, y)| y).sum::<f64>() / count;
+    let spread: f64 = points.iter().map(|(x, _)| (x - mean_x).powi(2)).sum();
+    if spread < f64::EPSILON {
+        return None;
+    }
+
+    let curve = points.iter().map(|(x, y)| (x - mean_x) * (y - mean_y)).sum::<f64>() / spread;
+    let gain = (mean_y - curve * mean_x).exp();
+
+    Some((gain, curve))
+}
+
+/// Measures how far moves land from where a fit expects them to.
+/// Returns the root mean square error, in mouse units.
+/// samples: The (requested, actual) distance of each move.
+/// (gain, curve): The fit of the moves.
+pub fn residual(samples: &[(f64, f64)], gain: f64, curve: f64) -> f64 {
+    if samples.is_empty() {
+        return 0.0;
+    }
+
+    let squared: f64 = samples.iter()
+        .map(|(requested, actual)| (actual - gain * requested.powf(curve)).powi(2))
+        .sum();
+    (squared / samples.len() as f64).sqrt()
+}
+
+/// Measures how far the pointer moved between two frames, in pixels.
+/// The changed pixels are the pointer at its old and new positions,
+/// which are split in half along the axis of the move.
+/// Returns None if the pointer can't be found.
+/// before: The frame before the move.
+/// after: The frame after the move.
+/// axis: The axis of the move. (0 for x, 1 for y)
+pub fn displacement(before: &RgbaImage, after: &RgbaImage, axis: usize) -> Option<f64> {
+    if before.dimensions() != after.dimensions() {
+        return None;
+    }
+
+    let changed: Vec<f64> = before.enumerate_pixels()
+        .filter(|(x, y, pixel)| {
+            let other = after.get_pixel(*x, *y);
+            pixel.0.iter().zip(other.0).any(|(a, b)| a.abs_diff(b) > CHANGE_THRESHOLD)
+        })
+        .map(|(x, y, _)| if axis == 0 { x as f64 } else { y as f64 })
+        .collect();
+    if changed.len() < MIN_CHANGED {
+        return None;
+    }
+
+    let middle = changed.iter().sum::<f64>() / changed.len() as f64;
+    let (near, far): (Vec<f64>, Vec<f64>) = changed.iter().partition(|position| **position < middle);
+    if near.is_empty() || far.is_empty() {
+        return None;
+    }
+
+    let mean = |positions: &[f64]| positions.iter().sum::<f64>() / positions.len() as f64;
+    Some(mean(&far) - mean(&near))
+}
+
+#[cfg(test)]
+mod test {
+    use image::{Rgba, RgbaImage};
+    use crate::calibrate::{displacement, fit, residual, Calibration, CalibrationIssue};
+
+    #[test]
+    pub fn measures_pointer_moves() {
+        let samples: Vec<(f64, f64)> = [20.0, 40.0, 60.0, 90.0].iter()
+            .map(|requested: &f64| (*requested, 1.5 * requested.powf(1.1)))
+            .collect();
+        let (gain, curve) = fit(&samples).unwrap();
+        assert!((gain - 1.5).abs() < 1e-6 && (curve - 1.1).abs() < 1e-6);
+        assert_eq!(fit(&samples[..1]), None);
+
+        // Draw the pointer, then move it 60 pixels right.
+        let pointer = |x: u32| {
+            let mut frame = RgbaImage::from_pixel(200, 100, Rgba([255, 255, 255, 255]));
+            for (dx, dy) in (0..6).flat_map(|dx| (0..6).map(move |dy| (dx, dy))) {
+                frame.put_pixel(x + dx, 40 + dy, Rgba([80, 80, 80, 255]));
+            }
+            frame
+        };
+        assert_eq!(displacement(&pointer(50), &pointer(110), 0), Some(60.0));
+        assert_eq!(displacement(&pointer(50), &pointer(50), 0), None);
+    }
+
+    #[test]
+    pub fn tracks_calibration_error() {
+        let samples = [(20.0, 21.0), (40.0, 39.0)];
+        assert!((residual(&samples, 1.0, 1.0) - 1.0).abs() < 1e-9);
+        assert_eq!(residual(&[], 1.0, 1.0), 0.0);
+
+        let calibration = Calibration { frame: (540, 960), tile_error: 6.0, ..Default::default() };
+        assert_eq!(calibration.issues(Some((540, 960)), 8.0), vec![]);
+        assert_eq!(calibration.issues(Some((540, 960)), 4.0), vec![CalibrationIssue::Inaccurate(6.0)]);
+        assert_eq!(calibration.issues(Some((720, 1280)), 0.0), vec![CalibrationIssue::Resized((540, 960))]);
+        assert_eq!(calibration.issues(None, 0.0), vec![]);
+    }
+}
@@ -0,0 +1,432 @@
+use std::io::{ErrorKind, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use image::imageops::FilterType;
+use image::RgbaImage;
+use log::{info, warn};
+use crate::board::{BOARD_SIZE, BOARD_TOP};
+use crate::config::Config;
+use crate::preprocess::Preprocess;
+use crate::{preprocess, screen};
+
+/// The byte sent to the companion app to request a frame.
+pub const FRAME_REQUEST: u8 = b'F';
+/// The largest frame accepted from the companion app. (32 MiB)
+pub const MAX_FRAME_SIZE: u32 = 32 << 20;
+/// How far a frame's aspect ratio may differ from the layout's before the board is stretched. (1 in 50)
+pub const ASPECT_TOLERANCE: u64 = 50;
+
+/// Where frames are captured from.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum CaptureBackend {
+    /// A mirrored device's desktop window.
+    Window,
+    /// A companion app on the device, over the network.
+    Network,
+    /// A screenshot on disk, for replaying rounds without a device.
+    File
+}
+
+impl CaptureBackend {
+    /// Parses a capture backend from the configuration.
+    /// Unknown values are treated as `Window`.
+    /// value: The configured value. ("window", "network", or "file")
+    pub fn parse(value: &str) -> Self {
+        match value.to_lowercase().as_str() {
+            "network" => CaptureBackend::Network,
+            "file" => CaptureBackend::File,
+            _ => CaptureBackend::Window
+        }
+    }
+}
+
+/// A source of captured frames.
+pub trait FrameSource: Send {
+    /// Captures the next frame, as opaque RGBA.
+    fn next_frame(&mut self) -> anyhow::Result<RgbaImage>;
+}
+
+/// A frame source which can be captured from on a blocking thread.
+pub type SharedSource = Arc<Mutex<Box<dyn FrameSource>>>;
+
+/// Captures the next frame on a blocking thread.
+/// Sources wait on the window or the companion app, which would stall the async runtime.
+/// source: The source to capture from.
+pub async fn next_frame(source: &SharedSource) -> anyhow::Result<RgbaImage> {
+    let source = source.clone();
+    tokio::task::spawn_blocking(move || source.lock().unwrap().next_frame()).await?
+}
+
+/// Opens the configured frame source.
+/// The configured preprocessing steps are applied to every frame.
+/// config: The application configuration.
+pub fn open(config: &Config) -> anyhow::Result<Box<dyn FrameSource>> {
+    let source: Box<dyn FrameSource> = match CaptureBackend::parse(&config.capture_backend) {
+        CaptureBackend::Window => Box::new(WindowSource { config: config.clone() }),
+        CaptureBackend::Network => Box::new(NetworkSource::bind(config)?),
+        CaptureBackend::File => Box::new(FileSource { path: config.capture_file.clone() })
+    };
+
+    let steps = Preprocess::parse_all(&config.preprocess)?;
+    if steps.is_empty() {
+        return Ok(source);
+    }
+
+    let size = (config.screen_width as u32, config.screen_height as u32);
+    Ok(Box::new(PreprocessedSource { source, steps, size }))
+}
+
+/// Finds the size of frames as they're captured, before they're scaled.
+/// Only a window can be measured without capturing a frame, so other backends return None.
+/// config: The application configuration.
+pub fn capture_size(config: &Config) -> Option<(u32, u32)> {
+    if CaptureBackend::parse(&config.capture_backend) != CaptureBackend::Window {
+        return None;
+    }
+
+    let handle = unsafe { screen::get_window(&config.device_name) };
+    let (width, height) = unsafe { screen::window_size(handle, config.client_area) }?;
+    Some((width.max(0) as u32, height.max(0) as u32))
+}
+
+/// Applies preprocessing steps to the frames of another source.
+pub struct PreprocessedSource {
+    source: Box<dyn FrameSource>,
+    steps: Vec<Preprocess>,
+
+    // The canonical size of a frame.
+    size: (u32, u32)
+}
+
+impl FrameSource for PreprocessedSource {
+    fn next_frame(&mut self) -> anyhow::Result<RgbaImage> {
+        let frame = self.source.next_frame()?;
+        Ok(preprocess::apply_all(frame, &self.steps, self.size))
+    }
+}
+
+/// Captures frames from the device's desktop window.
+/// When the window isn't managed, frames are scaled to the configured size.
+pub struct WindowSource {
+    config: Config
+}
+
+impl FrameSource for WindowSource {
+    fn next_frame(&mut self) -> anyhow::Result<RgbaImage> {
+        let frame = capture_frame(&self.config)?;
+        if self.config.manage_window {
+            return Ok(frame);
+        }
+
+        let size = (self.config.screen_width as u32, self.config.screen_height as u32);
+        Ok(scale_frame(frame, size))
+    }
+}
+
+/// Reads frames from a screenshot on disk.
+/// The file is read again for every frame, so it can be swapped out.
+pub struct FileSource {
+    path: String
+}
+
+impl FrameSource for FileSource {
+    fn next_frame(&mut self) -> anyhow::Result<RgbaImage> {
+        Ok(image::open(&self.path)?.to_rgba8())
+    }
+}
+
+/// Receives frames from a companion app running on the device.
+/// The app connects over TCP. For each request byte it receives, it replies
+/// with the frame's length (4 bytes, big-endian) followed by a JPEG of the screen.
+pub struct NetworkSource {
+    listener: TcpListener,
+    stream: Option<TcpStream>,
+
+    // How long a request or frame may take, or None to wait forever.
+    timeout: Option<Duration>,
+    // Frames are scaled to the configured screen size.
+    size: (u32, u32)
+}
+
+impl NetworkSource {
+    /// Listens for the companion app.
+    /// config: The application configuration.
+    pub fn bind(config: &Config) -> anyhow::Result<Self> {
+        let listener = TcpListener::bind((config.capture_address.as_str(), config.capture_port))?;
+        let timeout = (config.capture_timeout > 0).then(|| Duration::from_secs(config.capture_timeout));
+        let size = (config.screen_width as u32, config.screen_height as u32);
+
+        Ok(NetworkSource { listener, stream: None, timeout, size })
+    }
+
+    /// Requests and reads a frame from the companion app.
+    /// stream: The connection to the app.
+    #[tracing::instrument(skip_all)]
+    fn read_frame(stream: &mut TcpStream) -> anyhow::Result<RgbaImage> {
+        stream.write_all(&[FRAME_REQUEST])?;
+
+        let mut length = [0u8; 4];
+        stream.read_exact(&mut length)?;
+        let length = u32::from_be_bytes(length);
+        if length > MAX_FRAME_SIZE {
+            anyhow::bail!("The companion app sent a {} byte frame.", length);
+        }
+
+        let mut data = vec![0u8; length as usize];
+        stream.read_exact(&mut data)?;
+
+        Ok(image::load_from_memory(&data)?.to_rgba8())
+    }
+}
+
+impl FrameSource for NetworkSource {
+    fn next_frame(&mut self) -> anyhow::Result<RgbaImage> {
+        loop {
+            // Wait for the app to connect.
+            let stream = match &mut self.stream {
+                Some(stream) => stream,
+                None => {
+                    info!("Waiting for the companion app on {}...", self.listener.local_addr()?);
+                    let (stream, address) = self.listener.accept()?;
+                    info!("The companion app connected from {}.", address);
+
+                    stream.set_read_timeout(self.timeout)?;
+                    stream.set_write_timeout(self.timeout)?;
+                    self.stream.insert(stream)
+                }
+            };
+
+            // Drop the connection on failure, so the app can reconnect.
+            match Self::read_frame(stream) {
+                // The device's resolution differs from the mirrored window.
+                Ok(frame) => return Ok(scale_frame(frame, self.size)),
+                Err(error) => {
+                    self.stream = None;
+
+                    // A stalled app is waited for again, rather than ending the game.
+                    if !is_timeout(&error) {
+                        return Err(error);
+                    }
+                    warn!("The companion app didn't send a frame in time. Waiting for it to reconnect.");
+                }
+            }
+        }
+    }
+}
+
+/// Checks if an error is a socket timing out.
+/// Windows reports a timed out read as `TimedOut`, and other platforms as `WouldBlock`.
+/// error: The error to check.
+fn is_timeout(error: &anyhow::Error) -> bool {
+    error.downcast_ref::<std::io::Error>()
+        .is_some_and(|error| matches!(error.kind(), ErrorKind::TimedOut | ErrorKind::WouldBlock))
+}
+
+/// Scales a frame to the canonical size board coordinates are measured in.
+/// Warns if the frame's aspect ratio differs, since the board would be stretched.
+/// frame: The captured frame.
+/// size: The canonical (width, height) of a frame.
+pub fn scale_frame(frame: RgbaImage, (width, height): (u32, u32)) -> RgbaImage {
+    let (frame_width, frame_height) = frame.dimensions();
+    if (frame_width, frame_height) == (width, height) {
+        return frame;
+    }
+
+    if !aspect_matches((frame_width, frame_height), (width, height)) {
+        warn!("The captured frame is {}x{}, which doesn't match the {}x{} layout.",
+            frame_width, frame_height, width, height);
+    }
+
+    image::imageops::resize(&frame, width, height, FilterType::Triangle)
+}
+
+/// Checks if a size has the same aspect ratio as another, within the tolerance.
+/// size: The (width, height) to check.
+/// expected: The (width, height) with the expected ratio.
+pub fn aspect_matches((width, height): (u32, u32), expected: (u32, u32)) -> bool {
+    // Compare the ratios by cross-multiplying.
+    let (actual, expected) = (width as u64 * expected.1 as u64, height as u64 * expected.0 as u64);
+    actual.abs_diff(expected) * ASPECT_TOLERANCE <= expected
+}
+
+/// The channel order of a captured frame.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum PixelFormat {
+    /// Detect the channel order from the frame.
+    Auto,
+    /// Red, green, blue, alpha.
+    Rgba,
+    /// Blue, green, red, alpha.
+    Bgra
+}
+
+impl PixelFormat {
+    /// Parses a pixel format from the configuration.
+    /// Unknown values are treated as `Auto`.
+    /// value: The configured value. ("auto", "rgba", or "bgra")
+    pub fn parse(value: &str) -> Self {
+        match value.to_lowercase().as_str() {
+            "rgba" => PixelFormat::Rgba,
+            "bgra" => PixelFormat::Bgra,
+            _ => PixelFormat::Auto
+        }
+    }
+}
+
+/// Takes a screenshot of the device's window.
+/// The frame is normalized, and cropped to the client area if configured.
+/// config: The application configuration.
+#[tracing::instrument(skip_all)]
+pub fn capture_frame(config: &Config) -> anyhow::Result<RgbaImage> {
+    // Keep the desktop cursor from covering the tiles.
+    let parked = config.park_cursor
+        .then(|| unsafe { screen::park_cursor(screen::get_window(&config.device_name)) })
+        .flatten();
+
+    let frame = screen::take_screenshot(&config.device_name);
+    if let Some(cursor) = parked {
+        unsafe { screen::restore_cursor(cursor) };
+    }
+
+    let frame = frame?;
+    let frame = normalize_frame(frame, PixelFormat::parse(&config.pixel_format));
+
+    // Remove the window's title bar and borders.
+    if !config.client_area {
+        return Ok(frame);
+    }
+
+    Ok(unsafe {
+        let handle = screen::get_window(&config.device_name);
+        screen::crop_to_client(handle, frame)
+    })
+}
+
+/// Normalizes a captured frame into opaque RGBA.
+/// This undoes premultiplied alpha and swaps BGRA frames into RGBA.
+/// image: The captured frame.
+/// format: The channel order of the frame.
+pub fn normalize_frame(mut image: RgbaImage, format: PixelFormat) -> RgbaImage {
+    // Premultiplied pixels never have a color channel above their alpha.
+    let premultiplied = image.pixels().any(|pixel| pixel.0[3] < 255) &&
+        image.pixels().all(|pixel| {
+            let [r, g, b, a] = pixel.0;
+            r <= a && g <= a && b <= a
+        });
+
+    let swap = match format {
+        PixelFormat::Auto => is_bgra(&image),
+        PixelFormat::Rgba => false,
+        PixelFormat::Bgra => true
+    };
+
+    for pixel in image.pixels_mut() {
+        let [mut r, g, mut b, a] = pixel.0;
+        if swap {
+            (r, b) = (b, r);
+        }
+
+        pixel.0 = if !premultiplied {
+            [r, g, b, 255]
+        } else if a == 0 {
+            // Fully transparent pixels have no color, so treat them as background.
+            [255, 255, 255, 255]
+        } else {
+            [unpremultiply(r, a), unpremultiply(g, a), unpremultiply(b, a), 255]
+        };
+    }
+
+    image
+}
+
+/// Guesses if a frame is in BGRA order.
+/// The board's tiles are a warm beige, so red should outweigh blue.
+/// image: The captured frame.
+fn is_bgra(image: &RgbaImage) -> bool {
+    // Only sample the board when the frame is large enough to contain it.
+    let (x, y, width, height) = if image.width() >= BOARD_TOP.0 + BOARD_SIZE &&
+        image.height() >= BOARD_TOP.1 + BOARD_SIZE {
+        (BOARD_TOP.0, BOARD_TOP.1, BOARD_SIZE, BOARD_SIZE)
+    } else {
+        (0, 0, image.width(), image.height())
+    };
+
+    let (mut red, mut blue) = (0u64, 0u64);
+    for py in y..y + height {
+        for px in x..x + width {
+            let [r, _, b, _] = image.get_pixel(px, py).0;
+            red += r as u64;
+            blue += b as u64;
+        }
+    }
+
+    blue > red
+}
+
+/// Converts a premultiplied color channel back into a straight one.
+/// channel: The premultiplied channel.
+/// alpha: The pixel's alpha.
+fn unpremultiply(channel: u8, alpha: u8) -> u8 {
+    ((channel as u32 * 255 + alpha as u32 / 2) / alpha as u32).min(255) as u8
+}
+
+#[cfg(test)]
+mod test {
+    use image::{Rgba, RgbaImage};
+    use crate::capture::{aspect_matches, normalize_frame, scale_frame, PixelFormat};
+
+    /// The color of a board tile.
+    const TILE: [u8; 4] = [231, 203, 145, 255];
+
+    /// Creates a small frame of tiles with a black letter in the corner.
+    fn frame(order: PixelFormat) -> RgbaImage {
+        let mut image = RgbaImage::from_pixel(8, 8, Rgba(TILE));
+        image.put_pixel(0, 0, Rgba([0, 0, 0, 255]));
+
+        if order == PixelFormat::Bgra {
+            for pixel in image.pixels_mut() {
+                pixel.0.swap(0, 2);
+            }
+        }
+
+        image
+    }
+
+    #[test]
+    pub fn normalizes_channel_order() {
+        let expected = frame(PixelFormat::Rgba);
+
+        for order in [PixelFormat::Rgba, PixelFormat::Bgra] {
+            assert_eq!(normalize_frame(frame(order), PixelFormat::Auto), expected);
+            assert_eq!(normalize_frame(frame(order), order), expected);
+        }
+    }
+
+    #[test]
+    pub fn removes_premultiplied_alpha() {
+        let mut image = RgbaImage::from_pixel(2, 1, Rgba([116, 102, 73, 128]));
+        image.put_pixel(1, 0, Rgba([0, 0, 0, 0]));
+
+        let image = normalize_frame(image, PixelFormat::Rgba);
+
+        assert_eq!(image.get_pixel(0, 0).0, [231, 203, 145, 255]);
+        assert_eq!(image.get_pixel(1, 0).0, [255, 255, 255, 255]);
+    }
+
+    #[test]
+    pub fn scales_frames() {
+        let image = RgbaImage::from_pixel(40, 80, Rgba([0, 0, 0, 255]));
+
+        assert_eq!(scale_frame(image.clone(), (40, 80)), image);
+        assert_eq!(scale_frame(image, (20, 40)).dimensions(), (20, 40));
+    }
+
+    #[test]
+    pub fn checks_aspect_ratios() {
+        assert!(aspect_matches((1046, 2270), (523, 1135)));
+        assert!(aspect_matches((530, 1135), (523, 1135)));
+        assert!(!aspect_matches((600, 1135), (523, 1135)));
+        assert!(!aspect_matches((1135, 523), (523, 1135)));
+    }
+}
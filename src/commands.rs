@@ -0,0 +1,473 @@
+use std::fs;
+use std::path::Path;
+use std::thread::sleep;
+use std::time::{Duration, Instant};
+use anyhow::anyhow;
+use image::{DynamicImage, RgbImage, RgbaImage};
+use log::{info, warn};
+use crate::{audit, board, calibrate, capture, compiled, editor, export, limits, mock, photo, recording, screen, solver, update};
+use crate::accuracy::RecognitionStats;
+use crate::board::{BoardFile, Layout, TileGrid};
+use crate::compiled::CompiledDictionary;
+use crate::config::Config;
+use crate::dictionary::DictionaryBackend;
+use crate::letters::{CompareMetric, Letters};
+use crate::mouse::Mouse;
+use crate::planner::CoordinateMapper;
+use crate::recording::{TraceRecording, DIFF_TOLERANCE};
+use crate::solver::{SolveOptions, Solver};
+
+/// Vowel-heavy boards, where most prefixes stay alive and solving is slowest.
+const BENCH_BOARDS: [[&str; 4]; 3] = [
+    ["aeio", "uaei", "oeau", "ieao"],
+    ["eate", "rase", "tera", "seat"],
+    ["oeia", "ltne", "aiso", "enrt"]
+];
+
+/// Where the screen layout is exported by default.
+const LAYOUT_OUTPUT: &str = "layout.json";
+
+/// Where the mock mouse server saves the cursor path by default.
+const MOCK_OUTPUT: &str = "mock.png";
+
+/// A sample board image, with the correct letters for each row.
+pub type Sample = (RgbImage, Vec<Vec<char>>);
+
+/// Runs a command given on the command line.
+/// config: The application configuration.
+/// args: The command line arguments, excluding the program name.
+pub async fn run(config: &Config, args: &[String]) -> anyhow::Result<()> {
+    let args: Vec<&str> = args.iter().map(String::as_str).collect();
+
+    match args.as_slice() {
+        ["letters", "evaluate"] => evaluate_letters(config),
+        ["letters", "accuracy"] => report_accuracy(config),
+        ["samples", "add"] => add_sample(config),
+        ["solve", path] => solve_board(config, path),
+        ["photo", path] => solve_photo(config, image::open(path)?.to_rgba8()),
+        ["clipboard"] => solve_photo(config, photo::read_clipboard()?),
+        ["rank", board, words] => rank_words(config, board, words),
+        ["bench", "solver"] => bench_solver(config),
+        ["dictionary", "compile"] | ["--compile-dictionary"] => compile_dictionary(config),
+        ["dictionary" | "dict", "audit"] => audit_dictionary(config, None),
+        ["dictionary" | "dict", "audit", output] => audit_dictionary(config, Some(output)),
+        ["where"] => log_cursor(config),
+        ["layout", "export"] => export_layout(config, LAYOUT_OUTPUT),
+        ["layout", "export", path] => export_layout(config, path),
+        ["calibrate"] => calibrate::calibrate(config).await,
+        ["mouse", "click", x, y] => click_at(config, (x.parse()?, y.parse()?), false).await,
+        ["mouse", "right-click", x, y] => click_at(config, (x.parse()?, y.parse()?), true).await,
+        ["mouse", "scroll", notches, x, y] => scroll_at(config, (x.parse()?, y.parse()?), notches.parse()?).await,
+        ["trace", "diff", left, right] => diff_traces(left, right),
+        ["mock-server"] => mock::serve(config, MOCK_OUTPUT).await,
+        ["mock-server", output] => mock::serve(config, output).await,
+        ["update"] => update::update(&config.update_channel),
+        _ => Err(anyhow!("Unknown command: {}", args.join(" ")))
+    }
+}
+
+/// Clicks at a position, for navigating outside of the game.
+/// config: The application configuration.
+/// position: The mouse coordinates to click at. (as logged by the `where` command)
+/// right: Should the right button be clicked?
+async fn click_at(config: &Config, position: (i32, i32), right: bool) -> anyhow::Result<()> {
+    let mut mouse = mouse_at(config, position).await?;
+    if right {
+        mouse.right_click().await?;
+    } else {
+        mouse.click().await?;
+    }
+
+    mouse.normalize().await;
+    Ok(())
+}
+
+/// Scrolls at a position, for navigating outside of the game.
+/// config: The application configuration.
+/// position: The mouse coordinates to scroll at. (as logged by the `where` command)
+/// notches: The notches to scroll. Positive values scroll down, and negative values scroll up.
+async fn scroll_at(config: &Config, position: (i32, i32), notches: i32) -> anyhow::Result<()> {
+    let mut mouse = mouse_at(config, position).await?;
+    mouse.scroll(notches).await?;
+
+    mouse.normalize().await;
+    Ok(())
+}
+
+/// Opens the mouse, then moves it to a position.
+/// config: The application configuration.
+/// position: The mouse coordinates to move to.
+async fn mouse_at(config: &Config, position: (i32, i32)) -> anyhow::Result<Mouse> {
+    let mut mouse = Mouse::open(config).await?;
+    mouse.move_absolute(position, true).await?;
+    tokio::time::sleep(Duration::from_millis(50)).await;
+
+    Ok(mouse)
+}
+
+/// Logs the frame pixel, board tile, and mouse coordinates under the cursor.
+/// This helps measure button positions and board offsets on new devices.
+/// Runs until the program is stopped.
+/// config: The application configuration.
+fn log_cursor(config: &Config) -> anyhow::Result<()> {
+    let mapper = CoordinateMapper::new(config.layout());
+    let handle = unsafe { screen::get_window(&config.device_name) };
+    info!("Move the cursor over the window to measure it. Press Ctrl+C to stop.");
+
+    let mut last = None;
+    loop {
+        sleep(Duration::from_millis(100));
+
+        let Some(((x, y), (width, height))) = (unsafe {
+            screen::cursor_position(handle, config.client_area)
+        }) else {
+            continue;
+        };
+        if x < 0 || y < 0 || x >= width || y >= height || last == Some((x, y)) {
+            continue;
+        }
+        last = Some((x, y));
+
+        // Scale the cursor into the layout frames are measured in.
+        let pixel = (
+            (x as i64 * config.screen_width as i64 / width.max(1) as i64) as u32,
+            (y as i64 * config.screen_height as i64 / height.max(1) as i64) as u32
+        );
+        let tile = board::tile_at(config.layout(), pixel)
+            .map_or("none".to_string(), |pos| format!("({}, {})", pos.row, pos.col));
+
+        info!("Pixel {:?}, tile {}, mouse {:?}", pixel, tile, mapper.pixel_position(pixel));
+    }
+}
+
+/// Exports where every tile and button is in captured frames.
+/// config: The application configuration.
+/// path: The path to the JSON file.
+fn export_layout(config: &Config, path: &str) -> anyhow::Result<()> {
+    let layout = config.screen_layout();
+    export::write_json(path, &layout)?;
+
+    info!("Exported {} tiles and {} buttons to {}.", layout.tiles.len(), layout.buttons.len(), path);
+    Ok(())
+}
+
+/// Reports how accurately each metric recognizes the sample boards.
+/// config: The application configuration.
+fn evaluate_letters(config: &Config) -> anyhow::Result<()> {
+    let samples = read_samples(&config.samples)?;
+    info!("Loaded {} sample boards.", samples.len());
+
+    for metric in CompareMetric::ALL {
+        // Compare every letter, so the metrics are judged equally.
+        let letters = Letters::new(&config.font, 1.0, metric);
+
+        let (mut correct, mut total) = (0, 0);
+        for (image, expected) in &samples {
+            let layout = Layout::new(expected.len(), expected.first().map_or(0, Vec::len));
+            let grid = TileGrid::find(image, layout);
+            let image = DynamicImage::ImageRgb8(image.clone());
+            for (row, line) in expected.iter().enumerate() {
+                for (column, letter) in line.iter().enumerate() {
+                    let tile = board::crop_image(image.clone(), &grid, row as u32, column as u32);
+                    if letters.compare(&tile).0 == *letter {
+                        correct += 1;
+                    }
+                    total += 1;
+                }
+            }
+        }
+
+        let accuracy = 100.0 * correct as f64 / total.max(1) as f64;
+        info!("{:?}: {}/{} tiles correct ({:.1}%)", metric, correct, total, accuracy);
+    }
+
+    Ok(())
+}
+
+/// Reports how accurately each letter has been recognized during play.
+/// config: The application configuration.
+fn report_accuracy(config: &Config) -> anyhow::Result<()> {
+    let stats = RecognitionStats::load(&config.recognition_stats);
+    for (letter, (total, accuracy)) in stats.overall() {
+        info!("'{}': {:.1}% of {} tiles correct", letter, accuracy * 100.0, total);
+    }
+
+    let degraded = stats.degraded(limits::now(), config.accuracy_warning);
+    if degraded.is_empty() {
+        info!("No letters are being recognized worse than before.");
+    }
+    for letter in degraded {
+        warn!("'{}' dropped from {:.1}% to {:.1}% recently. Consider regenerating its template.",
+            letter.letter, letter.previous * 100.0, letter.recent * 100.0);
+    }
+
+    Ok(())
+}
+
+/// Compiles the word list with the configured backend, so later launches can skip parsing and building it.
+/// config: The application configuration.
+fn compile_dictionary(config: &Config) -> anyhow::Result<()> {
+    let words = crate::read_dictionary(&config.dictionary, config.strip_diacritics)
+        .ok_or_else(|| anyhow!("The dictionary {} doesn't exist.", config.dictionary))?;
+
+    let path = compiled::compiled_path(&config.dictionary);
+    let count = words.len();
+    let backend = DictionaryBackend::parse(&config.dictionary_backend);
+    CompiledDictionary::new(&config.dictionary, config.strip_diacritics, backend, words)?.save(&path)?;
+
+    info!("Compiled {} words to {} for the {:?} backend.", count, path.display(), backend);
+    Ok(())
+}
+
+/// Reports the dictionary entries which can never be played.
+/// config: The application configuration.
+/// output: Where to write a copy of the dictionary without them, if anywhere.
+fn audit_dictionary(config: &Config, output: Option<&str>) -> anyhow::Result<()> {
+    let contents = fs::read_to_string(&config.dictionary)
+        .map_err(|error| anyhow!("Couldn't read the dictionary {}: {}", config.dictionary, error))?;
+
+    let layout = config.layout();
+    let tiles = layout.rows * layout.cols - layout.holes.count_ones() as usize;
+    let report = audit::audit(&contents, tiles, config.strip_diacritics);
+
+    info!("Read {} entries from {}.", report.entries, config.dictionary);
+    for (problem, words) in [
+        (format!("longer than {} tiles", tiles), &report.too_long),
+        ("with letters outside of the tiles".to_string(), &report.outside_alphabet),
+        ("duplicated once normalized".to_string(), &report.duplicates)
+    ] {
+        let examples: Vec<&str> = words.iter().take(5).map(String::as_str).collect();
+        info!("{} entries {}: {}", words.len(), problem, examples.join(", "));
+    }
+    info!("The unplayable entries waste {} trie nodes. ({:.1} KiB in an arena trie)",
+        report.wasted_nodes, report.wasted_bytes() as f64 / 1024.0);
+
+    if let Some(output) = output {
+        fs::write(output, report.kept.join("\n") + "\n")?;
+        info!("Wrote {} playable entries to {}.", report.kept.len(), output);
+    }
+
+    Ok(())
+}
+
+/// Captures the current board and adds it to the samples.
+/// The recognized letters are shown for correction before saving.
+/// config: The application configuration.
+fn add_sample(config: &Config) -> anyhow::Result<()> {
+    let letters = Letters::new(&config.font, config.letter_threshold,
+                               CompareMetric::parse(&config.letter_metric));
+
+    // Capture and recognize the board.
+    let frame = capture::open(config)?.next_frame()?;
+    let image = board::board_image(&frame);
+    let mut tiles: Vec<Vec<String>> = screen::process_board(&image, &letters, config.layout())
+        .iter()
+        .map(|line| line.iter().map(|(c, _)| c.to_string()).collect())
+        .collect();
+
+    // Let the user correct the letters.
+    editor::edit_board(&mut tiles)?;
+
+    // Find the next free sample number.
+    fs::create_dir_all(&config.samples)?;
+    let index = (1..)
+        .find(|i| !Path::new(&format!("{}/{}.png", config.samples, i)).exists())
+        .unwrap();
+
+    // Save the board image and its letters.
+    let letters: Vec<String> = tiles.iter().map(|row| row.concat()).collect();
+    image.save(format!("{}/{}.png", config.samples, index))?;
+    fs::write(format!("{}/{}.txt", config.samples, index), letters.join("\n"))?;
+    BoardFile::new(tiles, Some(&frame)).save(format!("{}/{}.board", config.samples, index))?;
+
+    info!("Saved sample {}.", index);
+    Ok(())
+}
+
+/// Solves a saved board and prints the words found.
+/// config: The application configuration.
+/// path: The path to the `.board` file.
+fn solve_board(config: &Config, path: &str) -> anyhow::Result<()> {
+    crate::load_dictionary(&config.dictionary, config.strip_diacritics,
+                           DictionaryBackend::parse(&config.dictionary_backend));
+
+    let board = BoardFile::load(path)?;
+    let words = solver::find_all_words(&board.tiles, &[]);
+
+    info!("Found {} words.", words.len());
+    for word in words {
+        info!("{:<16} {:>5} pts {}", word.word, word.score, word.assumptions());
+    }
+
+    Ok(())
+}
+
+/// Finds a board in a screenshot or photo, then solves it.
+/// config: The application configuration.
+/// image: The image containing the board.
+fn solve_photo(config: &Config, image: RgbaImage) -> anyhow::Result<()> {
+    let corners = photo::find_corners(&image)
+        .ok_or_else(|| anyhow!("Couldn't find a board in the image."))?;
+    info!("Found the board's corners at {:?}.", corners);
+
+    // Straighten the board, in case it was photographed at an angle.
+    let size = board::BOARD_SIZE - 2 * board::BOARD_PADDING;
+    let tiles = photo::rectify(&image, corners, size)
+        .ok_or_else(|| anyhow!("The board's corners don't form a quadrilateral."))?;
+
+    // Read the board as if it had been captured.
+    let frame = photo::to_frame(&tiles, (0, 0, size, size));
+    let letters = Letters::new(&config.font, config.letter_threshold,
+                               CompareMetric::parse(&config.letter_metric));
+    let layout = config.layout();
+    let board = board::read_board(&frame, &letters, layout, 1);
+
+    crate::load_dictionary(&config.dictionary, config.strip_diacritics,
+                           DictionaryBackend::parse(&config.dictionary_backend));
+    let options = SolveOptions { excluded: layout.holes, ..SolveOptions::default() };
+    let words = solver::find_words(&board.tiles, &options);
+
+    info!("Recognized board:\n{}", board::format_board(&board));
+    info!("Found {} words.", words.len());
+    for word in words {
+        info!("{:<16} {:>5} pts {}", word.word, word.score, word.assumptions());
+    }
+
+    Ok(())
+}
+
+/// Checks and scores a list of words against a saved board.
+/// config: The application configuration.
+/// board: The path to the `.board` file.
+/// words: The path to a file with one word per line.
+fn rank_words(config: &Config, board: &str, words: &str) -> anyhow::Result<()> {
+    crate::load_dictionary(&config.dictionary, config.strip_diacritics,
+                           DictionaryBackend::parse(&config.dictionary_backend));
+
+    let board = BoardFile::load(board)?;
+    let words: Vec<String> = fs::read_to_string(words)?
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .map(String::from)
+        .collect();
+
+    let (valid, rejected) = solver::rank_words(&board.tiles, &words);
+    for word in &valid {
+        info!("{:<16} {:>5} pts {}", word.word, word.score, word.assumptions());
+    }
+    for (word, rejection) in &rejected {
+        info!("{:<16} {:?}", word, rejection);
+    }
+
+    let score: u32 = valid.iter().map(|word| word.score).sum();
+    info!("{} of {} words are valid, for {} points.", valid.len(), words.len(), score);
+
+    Ok(())
+}
+
+/// Compares two recordings of the same board, logging the points where their traces diverge.
+/// Traces are compared in order, so a change of plan shows up as a different word.
+/// left: The path to the first recording.
+/// right: The path to the second recording.
+fn diff_traces(left: &str, right: &str) -> anyhow::Result<()> {
+    let (left, right) = (TraceRecording::load(left)?, TraceRecording::load(right)?);
+    if left.board != right.board {
+        warn!("The recordings are of different boards, so their traces won't line up.");
+    }
+
+    let point = |point: Option<(i32, i32)>| point.map_or("-".to_string(), |(x, y)| format!("({}, {})", x, y));
+    let mut divergent = 0;
+    for index in 0..left.traces.len().max(right.traces.len()) {
+        match (left.traces.get(index), right.traces.get(index)) {
+            (Some(a), Some(b)) if a.word != b.word => {
+                divergent += 1;
+                warn!("! {:>3}. {:<16} became {}", index + 1, a.word, b.word);
+            },
+            (Some(a), Some(b)) => {
+                let diffs = recording::diff_points(a, b, DIFF_TOLERANCE);
+                if diffs.is_empty() {
+                    info!("  {:>3}. {:<16} {:>5}ms {:>5}ms", index + 1, a.word, a.duration, b.duration);
+                    continue;
+                }
+
+                divergent += 1;
+                warn!("! {:>3}. {:<16} {:>5}ms {:>5}ms  {} of {} points diverge",
+                    index + 1, a.word, a.duration, b.duration, diffs.len(), a.points.len().max(b.points.len()));
+                for diff in diffs {
+                    info!("        point {:>3}: {} -> {}", diff.index, point(diff.left), point(diff.right));
+                }
+            },
+            (Some(a), None) => {
+                divergent += 1;
+                warn!("! {:>3}. {:<16} is only in the first recording", index + 1, a.word);
+            },
+            (None, Some(b)) => {
+                divergent += 1;
+                warn!("! {:>3}. {:<16} is only in the second recording", index + 1, b.word);
+            },
+            (None, None) => unreachable!()
+        }
+    }
+
+    info!("{} of {} traces diverge.", divergent, left.traces.len().max(right.traces.len()));
+
+    Ok(())
+}
+
+/// Times the solver with each dictionary backend, on boards which are slow to solve.
+/// config: The application configuration.
+fn bench_solver(config: &Config) -> anyhow::Result<()> {
+    const RUNS: u32 = 20;
+
+    let words = crate::read_dictionary(&config.dictionary, config.strip_diacritics)
+        .ok_or_else(|| anyhow!("The dictionary {} doesn't exist.", config.dictionary))?;
+
+    for backend in DictionaryBackend::ALL {
+        // Each backend gets its own solver, so the loaded dictionary is left alone.
+        let start = Instant::now();
+        let solver = Solver::new(backend.build(&words));
+        info!("{:?}: built in {:.2?}", backend, start.elapsed());
+
+        for rows in BENCH_BOARDS {
+            let board: Vec<Vec<String>> = rows.iter()
+                .map(|row| row.chars().map(|c| c.to_string()).collect())
+                .collect();
+
+            let start = Instant::now();
+            let mut found = 0;
+            for _ in 0..RUNS {
+                found = solver.find_all_words(&board, &[]).len();
+            }
+
+            info!("{:?}: {}: {} words in {:.2?} per solve",
+                backend, rows.join("/"), found, start.elapsed() / RUNS);
+        }
+    }
+
+    Ok(())
+}
+
+/// Reads the sample boards and their correct letters.
+/// Samples are numbered from 1, as `<n>.png` with the letters in `<n>.txt`.
+/// path: The path to the samples directory.
+pub fn read_samples(path: &str) -> anyhow::Result<Vec<Sample>> {
+    let mut samples = Vec::new();
+
+    for i in 1.. {
+        let image_path = format!("{}/{}.png", path, i);
+        if !Path::new(&image_path).exists() {
+            break;
+        }
+
+        let image = image::open(&image_path)?.to_rgb8();
+        let letters = fs::read_to_string(format!("{}/{}.txt", path, i))?
+            .lines()
+            .map(|line| line.trim().chars().collect::<Vec<char>>())
+            .filter(|line| !line.is_empty())
+            .collect();
+
+        samples.push((image, letters));
+    }
+
+    Ok(samples)
+}
@@ -0,0 +1,169 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::UNIX_EPOCH;
+use anyhow::anyhow;
+use serde::{Deserialize, Serialize};
+use crate::arena::ArenaTrie;
+use crate::dawg::Dawg;
+use crate::dictionary::{Dictionary, DictionaryBackend};
+use crate::hash;
+use crate::trie::TrieNode;
+
+/// The start of every compiled dictionary, including the format version.
+pub const MAGIC: &[u8; 8] = b"WTDICT\x00\x02";
+
+/// A dictionary's words, already built with the backend they'll be searched in.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum CompiledWords {
+    /// The words with their frequencies, in sorted order.
+    /// The HashMap trie is rebuilt from these, since its maps can't be stored flat.
+    HashMap(Vec<(String, u32)>),
+    /// A built arena trie.
+    Arena(ArenaTrie),
+    /// A built word graph.
+    Dawg(Dawg)
+}
+
+/// A dictionary which has already been normalized, sorted, and built.
+/// The file is the magic, a checksum of the body, then the body in bincode.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct CompiledDictionary {
+    /// The size of the word list this was compiled from, in bytes.
+    pub source_size: u64,
+    /// When the word list was last modified, as a UNIX timestamp.
+    pub source_modified: u64,
+    /// Were diacritics removed from the words?
+    pub strip_diacritics: bool,
+    /// The built dictionary.
+    pub words: CompiledWords
+}
+
+impl CompiledDictionary {
+    /// Compiles a word list with a backend.
+    /// source: The path to the word list.
+    /// strip_diacritics: Were diacritics removed from the words?
+    /// backend: The data structure to store the dictionary in.
+    /// words: The normalized words with their frequencies, in sorted order.
+    pub fn new<P: AsRef<Path>>(
+        source: P, strip_diacritics: bool,
+        backend: DictionaryBackend, words: Vec<(String, u32)>
+    ) -> anyhow::Result<Self> {
+        let (source_size, source_modified) = source_metadata(source)?;
+        let words = match backend {
+            DictionaryBackend::HashMap => CompiledWords::HashMap(words),
+            DictionaryBackend::Arena => CompiledWords::Arena(ArenaTrie::from_sorted(&words)),
+            DictionaryBackend::Dawg => CompiledWords::Dawg(Dawg::from_sorted(&words))
+        };
+
+        Ok(CompiledDictionary { source_size, source_modified, strip_diacritics, words })
+    }
+
+    /// Returns the backend this was compiled with.
+    pub fn backend(&self) -> DictionaryBackend {
+        match self.words {
+            CompiledWords::HashMap(_) => DictionaryBackend::HashMap,
+            CompiledWords::Arena(_) => DictionaryBackend::Arena,
+            CompiledWords::Dawg(_) => DictionaryBackend::Dawg
+        }
+    }
+
+    /// Checks if this was compiled from the current word list, with the same options.
+    /// source: The path to the word list.
+    /// strip_diacritics: Should diacritics be removed from the words?
+    /// backend: The data structure the dictionary should be stored in.
+    pub fn is_current<P: AsRef<Path>>(&self, source: P, strip_diacritics: bool, backend: DictionaryBackend) -> bool {
+        self.strip_diacritics == strip_diacritics && self.backend() == backend &&
+            source_metadata(source).is_ok_and(|metadata| metadata == (self.source_size, self.source_modified))
+    }
+
+    /// Converts this into a searchable dictionary.
+    pub fn into_dictionary(self) -> Arc<dyn Dictionary> {
+        match self.words {
+            CompiledWords::HashMap(words) => Arc::new(TrieNode::from_sorted_parallel(&words)),
+            CompiledWords::Arena(trie) => Arc::new(trie),
+            CompiledWords::Dawg(dawg) => Arc::new(dawg)
+        }
+    }
+
+    /// Loads a compiled dictionary.
+    /// Fails if the file isn't a compiled dictionary, or it's corrupted.
+    /// path: The path to the compiled dictionary.
+    pub fn load<P: AsRef<Path>>(path: P) -> anyhow::Result<Self> {
+        let contents = fs::read(path)?;
+        if contents.len() < MAGIC.len() + 8 || !contents.starts_with(MAGIC) {
+            return Err(anyhow!("The file isn't a compiled dictionary."));
+        }
+
+        let (checksum, body) = contents[MAGIC.len()..].split_at(8);
+        if u64::from_le_bytes(checksum.try_into()?) != hash::fnv(body) {
+            return Err(anyhow!("The compiled dictionary is corrupted."));
+        }
+
+        Ok(bincode::deserialize(body)?)
+    }
+
+    /// Saves this compiled dictionary.
+    /// path: The path to save to.
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> anyhow::Result<()> {
+        let body = bincode::serialize(self)?;
+
+        let mut contents = Vec::with_capacity(MAGIC.len() + 8 + body.len());
+        contents.extend_from_slice(MAGIC);
+        contents.extend_from_slice(&hash::fnv(&body).to_le_bytes());
+        contents.extend_from_slice(&body);
+
+        fs::write(path, contents)?;
+        Ok(())
+    }
+}
+
+/// Returns where a word list's compiled form is stored. (e.g. "words.dict")
+/// source: The path to the word list.
+pub fn compiled_path<P: AsRef<Path>>(source: P) -> PathBuf {
+    source.as_ref().with_extension("dict")
+}
+
+/// Reads the (size, modified time) of a word list.
+/// source: The path to the word list.
+fn source_metadata<P: AsRef<Path>>(source: P) -> anyhow::Result<(u64, u64)> {
+    let metadata = fs::metadata(source)?;
+    let modified = metadata.modified()?.duration_since(UNIX_EPOCH)?.as_secs();
+
+    Ok((metadata.len(), modified))
+}
+
+#[cfg(test)]
+mod test {
+    use crate::arena::ArenaTrie;
+    use crate::compiled::{CompiledDictionary, CompiledWords};
+    use crate::dictionary::DictionaryBackend;
+
+    #[test]
+    pub fn compiled_round_trip() {
+        let words = vec![("cafe".to_string(), 5), ("cat".to_string(), 0)];
+        let dictionary = CompiledDictionary {
+            source_size: 12,
+            source_modified: 34,
+            strip_diacritics: true,
+            words: CompiledWords::Arena(ArenaTrie::from_sorted(&words))
+        };
+
+        // Include the process ID, so parallel test runs don't share the file.
+        let path = std::env::temp_dir()
+            .join(format!("word-terminator-test-{}.dict", std::process::id()));
+        dictionary.save(&path).unwrap();
+        let loaded = CompiledDictionary::load(&path).unwrap();
+        assert_eq!(loaded, dictionary);
+        assert_eq!(loaded.backend(), DictionaryBackend::Arena);
+        assert_eq!(loaded.into_dictionary().iter().collect::<Vec<_>>(), words);
+
+        // Flip a byte in the body, which the checksum should catch.
+        let mut contents = std::fs::read(&path).unwrap();
+        *contents.last_mut().unwrap() ^= 1;
+        std::fs::write(&path, contents).unwrap();
+        assert!(CompiledDictionary::load(&path).is_err());
+
+        std::fs::remove_file(&path).unwrap();
+    }
+}
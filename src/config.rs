@@ -2,10 +2,17 @@ use std::fs::File;
 use std::io::Write;
 use std::path::Path;
 use std::sync::RwLock;
+use std::time::Duration;
 use config::{ConfigurationBuilder, DefaultConfigurationBuilder};
 use config::ext::{ConfigurationBinder, JsonConfigurationExtensions};
 use lazy_static::lazy_static;
+use log::warn;
 use serde::{Deserialize, Serialize};
+use crate::paths;
+use crate::board::Layout;
+use crate::export::ScreenLayout;
+use crate::mouse::SwipeSpeed;
+use crate::planner::{PathStyle, PointerCorrection, TimingProfile, MAX_PATH_POINTS};
 
 lazy_static! {
     static ref CONFIG: RwLock<Config> = RwLock::new(Config::default());
@@ -15,6 +22,7 @@ macro_rules! define {
     ($name:ident { $($field:tt)* }) => {
         #[derive(Clone, Debug, Serialize, Deserialize)]
         #[serde(rename_all(serialize = "camelCase", deserialize = "PascalCase"))]
+        #[serde(default)]
         pub struct $name {
             $($field)*
         }
@@ -33,7 +41,7 @@ pub fn init_config() -> anyhow::Result<Config> {
         let mut file = File::create("config.json")?;
         file.write_all(serde_json::to_string_pretty(&config)?.as_bytes())?;
 
-        return Ok(config);
+        return Ok(config.resolve_paths());
     }
 
     // Parse the configuration.
@@ -42,6 +50,8 @@ pub fn init_config() -> anyhow::Result<Config> {
         .build()
         .unwrap()
         .reify();
+    let config = config.resolve_paths();
+    config.check();
 
     // Copy the config to the global variable.
     if let Ok(mut write) = CONFIG.write() {
@@ -52,6 +62,10 @@ pub fn init_config() -> anyhow::Result<Config> {
 }
 
 define!(Config {
+    // The directory runtime files are stored in. Leave empty for the platform default.
+    // Relative paths below which don't exist in the working directory are placed here.
+    pub data_dir: String,
+
     // The name of the device/window.
     pub device_name: String,
 
@@ -59,33 +73,434 @@ define!(Config {
     pub screen_width: i32,
     pub screen_height: i32,
 
+    // Is the window size of the client area? (excluding the title bar and borders)
+    // When enabled, board coordinates are relative to the client area.
+    pub client_area: bool,
+
+    // Where frames are captured from. ("window", "network", or "file")
+    pub capture_backend: String,
+    // The screenshot to read frames from with the file backend.
+    pub capture_file: String,
+    // The address and port to listen for the companion app on.
+    // Only local connections are accepted by default; use "0.0.0.0" to accept the device over the network.
+    pub capture_address: String,
+    pub capture_port: u16,
+    // How long to wait for the companion app to take a request or send a frame, in seconds. (0 to wait forever)
+    // The connection is dropped when this runs out, and the app is waited for again.
+    pub capture_timeout: u64,
+    // Steps applied to each frame after capture, separated by semicolons.
+    // ("crop <left> <top> <right> <bottom>", "corners <radius>", or "scale")
+    pub preprocess: String,
+    // The channel order of captured frames. ("auto", "rgba", or "bgra")
+    pub pixel_format: String,
+    // Should the desktop cursor be moved off the window while capturing?
+    pub park_cursor: bool,
+    // Should physical mouse input be blocked while each word is traced?
+    // Only input injected by software gets through, so this suits the sendinput backend,
+    // or a mouse server plugged into the device instead of this PC.
+    pub block_input: bool,
+
+    // Should the window be moved and resized to the layout above?
+    // When disabled, the window is left alone and frames are scaled to the layout.
+    pub manage_window: bool,
+    // How far the mirror moves the pointer for each requested unit, and how much
+    // faster longer moves are. Some mirroring apps accelerate the pointer, which
+    // makes moves land short or long. Measure these with the calibrate command.
+    pub pointer_gain_x: f64,
+    pub pointer_gain_y: f64,
+    pub pointer_curve: f64,
+    // The path to the file the last calibration's results are saved to.
+    // A warning is shown at startup if it's no longer accurate. Leave empty to disable.
+    pub calibration_file: String,
+    // Warn when the last calibration missed tile centers by more than this, in pixels. (0 to disable)
+    pub calibration_error: f64,
+    // The points added along each move between tiles, so swipes curve like a finger's.
+    // Set to 0 for straight moves. (at most 8, and long words get fewer to fit in one swipe)
+    pub path_points: usize,
+    // The most a curved move bows away from a straight line, as a fraction of its length.
+    pub path_curve: f64,
+    // The most each tile's point is moved off the tile's center, in mouse units. (0 to disable)
+    pub swipe_jitter: f64,
+    // How long the server waits on each tile, in milliseconds. (0 for the server's default)
+    // Longer waits are more reliable on slow mirrors, but take longer.
+    pub swipe_dwell: i32,
+    // The most mouse units the server moves at once. Smaller steps swipe slower. (0 for the default)
+    pub swipe_step: i32,
+    // Should tracing pause while another window is in front of the mirror?
+    pub pause_on_focus_loss: bool,
     // Set the window's position.
     pub window_x: i32,
     pub window_y: i32,
 
     // The path to the dictionary file.
     pub dictionary: String,
+    // Should diacritics be removed from dictionary words? (e.g. 'é' -> 'e')
+    pub strip_diacritics: bool,
+    // The data structure to store the dictionary in. ("arena", "dawg", or "hashmap")
+    pub dictionary_backend: String,
+    // Write a dictionary's path into this file to switch to it between rounds.
+    // The file is removed once the switch starts. (empty to disable)
+    pub dictionary_switch: String,
 
     // The path to the letters folder.
     pub font: String,
+    // The directory containing the sample boards.
+    pub samples: String,
+    // A letter match scoring above this is accepted without checking the rest.
+    // Set to 1 to always compare every letter.
+    pub letter_threshold: f64,
+    // The metric used to compare tiles to letters. ("hybrid", "rms", "ssim", or "histogram")
+    pub letter_metric: String,
+    // Should the recognized board be shown for corrections before solving?
+    pub edit_board: bool,
+    // Should the recognized board and best words be logged?
+    pub log_board: bool,
+    // The path to the file tracking how accurately each letter is recognized.
+    // Corrections made in the board editor are counted. Leave empty to disable.
+    pub recognition_stats: String,
+    // Warn when a letter's recent accuracy drops by this much. (0 to 1)
+    pub accuracy_warning: f64,
+    // The number of days of recognition statistics to keep. 0 keeps them all.
+    pub stats_retention_days: u64,
     
     // The server configuration.
     pub server_address: String,
-    pub server_port: u16
+    pub server_port: u16,
+    // The server's WebSocket URL, used instead of the address when set. (e.g. "wss://example.com/mouse")
+    pub server_url: String,
+    // The secret the server expects before any instruction, over TCP or WebSocket. (empty if the server has none)
+    pub server_token: String,
+    // Should the connection to the server be encrypted?
+    pub server_tls: bool,
+    // The certificate to trust for the server, as PEM. (empty to trust the usual authorities)
+    pub server_ca: String,
+    // The name on the server's certificate. (empty to use the server address)
+    pub server_tls_name: String,
+    // Should the delays between mouse actions grow by the mouse's measured round-trip time?
+    // This keeps a slow link from starting a swipe before the last move has landed.
+    pub compensate_latency: bool,
+    // Where mouse input is sent. ("server", "sendinput" for a device mirrored to this PC,
+    // "adb" for an Android device, or "dryrun" to only record it, the same as --dry-run)
+    pub mouse_backend: String,
+    // The path to write each instruction of a dry run to, one JSON object per line. Leave empty to only log them.
+    pub dry_run_file: String,
+    // The path to the adb executable, and the serial of the device. (empty for the only device)
+    pub adb_path: String,
+    pub adb_serial: String,
+
+    // The maximum rounds to play automatically. 0 disables the limit.
+    // Once reached, the bot only shows hints.
+    pub max_rounds_per_hour: u32,
+    pub max_rounds_per_day: u32,
+    // The path to the file tracking recently played rounds.
+    pub history_file: String,
+
+    // The length of a round, in seconds.
+    pub round_time: u64,
+    // The longest capture, recognition, and solving may take before tracing starts.
+    // Words found by then are traced. (in milliseconds, 0 for no limit)
+    pub max_recognition_time: u64,
+
+    // A preset for the settings below. ("casual", "competitive", or "max")
+    // Leave empty to use the settings as they are. (overridden by --difficulty)
+    pub difficulty: String,
+    // The most words to play each round. (0 for no limit)
+    pub max_words: usize,
+    // Stop playing once the round's words are worth this many points. (0 for no target)
+    pub target_score: u32,
+    // How fast to play, as a fraction of full speed. Lower values wait longer around each word.
+    pub trace_speed: f64,
+    // An extra pause before each word, in milliseconds.
+    pub think_time: u64,
+    // Skip words worth fewer points than this. (0 plays every word)
+    pub min_score: u32,
+    // Only play 3-4 letter words with nearly straight paths.
+    // This is useful for quick games, and for testing calibration.
+    pub easy_words: bool,
+
+    // Should the planned words be shown before tracing?
+    pub preview_plan: bool,
+    // Should the plan be confirmed before tracing? (requires preview_plan)
+    pub confirm_plan: bool,
+
+    // The game being played. ("word-hunt" or "anagrams")
+    pub game_mode: String,
+    // The number of letters in the Anagrams rack.
+    pub rack_size: usize,
+    // The number of rows and columns on the board. (e.g. 5 and 5 for larger boards)
+    pub board_rows: usize,
+    pub board_cols: usize,
+    // The tiles missing from shaped boards, as (row, col) pairs. (e.g. "0,0 0,4 4,0 4,4")
+    pub board_holes: String,
+    // Tiles recognized below this score are considered uncertain. (0 to 1)
+    pub low_confidence: f64,
+    // Letters scoring within this margin of a tile's best letter are solved
+    // as alternatives. Set to 0 to only use the best letter.
+    pub ambiguity_margin: f64,
+    // The number of letters to consider for each tile.
+    pub letter_candidates: usize,
+    // The number of likeliest board readings to solve.
+    pub beam_width: usize,
+
+    // Sounds to play on key events. Leave empty to disable a sound.
+    pub sound_round_start: String,
+    pub sound_low_confidence: String,
+    pub sound_round_complete: String,
+    pub sound_error: String,
+
+    // A directory to save each run in, with its config, log, frames, stats, and traces.
+    // Every run gets a timestamped folder with a manifest.json. Leave empty to disable.
+    pub runs_dir: String,
+    // The path to append the progress of each round to, one JSON event per line.
+    // (boardRecognized, planReady, wordStarted, wordCompleted, roundFinished, error) Leave empty to disable.
+    pub events_file: String,
+
+    // Paths to export each round's words to. Leave empty to disable.
+    pub export_csv: String,
+    pub export_anki: String,
+    // The path to a file of word definitions for Anki decks. (word<TAB>definition)
+    pub definitions: String,
+
+    // Should the result be sent once the round ends?
+    pub auto_send: bool,
+    // An image of the send button, found on screen by template matching.
+    pub send_button: String,
+    // How long to wait for the send button to appear or disappear, in seconds.
+    pub send_timeout: u64,
+    // Should the bot wait for the opponent, then play the next round?
+    pub wait_for_turn: bool,
+    // An image of the game bubble shown when it's our turn.
+    pub turn_button: String,
+    // The notches to scroll the chat down while waiting, so the newest bubble is shown.
+    // Requires a mouse which can scroll. (0 to disable)
+    pub scroll_chat: i32,
+    // The similarity needed to match a button's image. (0 to 1)
+    pub template_threshold: f64,
+    // Should words played in earlier rounds of the match be skipped?
+    pub unique_words: bool,
+    // An image only shown when a new match starts, which resets the played words.
+    // Leave empty to remember the played words for the whole session.
+    pub match_button: String,
+
+    // Should newer releases be checked for on startup?
+    pub check_updates: bool,
+    // The release channel to update from. ("stable" or "prerelease")
+    pub update_channel: String,
+
+    // Should words which are path prefixes of other words be traced
+    // as part of the longer word? Only enable this for variants which
+    // score every prefix on release.
+    pub merge_prefixes: bool,
+
+    // The number of threads running async tasks, like the mouse link. Set to 0 for one per core.
+    pub worker_threads: usize,
+    // The most threads used for blocking work, like recognizing the board. Set to 0 for tokio's default.
+    pub blocking_threads: usize
 });
 
 impl Default for Config {
     fn default() -> Self {
         Config {
+            data_dir: String::new(),
             device_name: "iPhone".to_string(),
             screen_width: 523,
             screen_height: 1135,
+            client_area: false,
+            capture_backend: "window".to_string(),
+            capture_file: "frame.png".to_string(),
+            capture_address: "127.0.0.1".to_string(),
+            capture_port: 5001,
+            capture_timeout: 5,
+            preprocess: String::new(),
+            pixel_format: "auto".to_string(),
+            park_cursor: false,
+            block_input: false,
+            manage_window: true,
+            pointer_gain_x: 1.0,
+            pointer_gain_y: 1.0,
+            pointer_curve: 1.0,
+            calibration_file: "calibration.json".to_string(),
+            calibration_error: 4.0,
+            path_points: 0,
+            path_curve: 0.15,
+            swipe_jitter: 0.0,
+            swipe_dwell: 0,
+            swipe_step: 0,
+            pause_on_focus_loss: true,
             window_x: 0,
             window_y: 0,
             dictionary: "words.txt".to_string(),
+            strip_diacritics: false,
+            dictionary_backend: "arena".to_string(),
+            dictionary_switch: String::new(),
             font: "images".to_string(),
+            samples: "samples".to_string(),
+            letter_threshold: 0.98,
+            letter_metric: "hybrid".to_string(),
+            edit_board: false,
+            log_board: true,
+            recognition_stats: "stats.json".to_string(),
+            accuracy_warning: 0.1,
+            stats_retention_days: 90,
             server_address: "127.0.0.1".to_string(),
-            server_port: 5000
+            server_port: 5000,
+            server_url: String::new(),
+            server_token: String::new(),
+            server_tls: false,
+            server_ca: String::new(),
+            server_tls_name: String::new(),
+            compensate_latency: true,
+            mouse_backend: "server".to_string(),
+            dry_run_file: "dry-run.jsonl".to_string(),
+            adb_path: "adb".to_string(),
+            adb_serial: String::new(),
+            max_rounds_per_hour: 0,
+            max_rounds_per_day: 0,
+            history_file: "history.json".to_string(),
+            round_time: 80,
+            max_recognition_time: 0,
+            difficulty: String::new(),
+            max_words: 0,
+            min_score: 0,
+            easy_words: false,
+            target_score: 0,
+            trace_speed: 1.0,
+            think_time: 0,
+            preview_plan: false,
+            confirm_plan: false,
+            game_mode: "word-hunt".to_string(),
+            rack_size: 6,
+            board_rows: 4,
+            board_cols: 4,
+            board_holes: String::new(),
+            low_confidence: 0.75,
+            ambiguity_margin: 0.02,
+            letter_candidates: 3,
+            beam_width: 8,
+            sound_round_start: String::new(),
+            sound_low_confidence: String::new(),
+            sound_round_complete: String::new(),
+            sound_error: String::new(),
+            runs_dir: String::new(),
+            events_file: String::new(),
+            export_csv: String::new(),
+            export_anki: String::new(),
+            definitions: String::new(),
+            auto_send: false,
+            send_button: "send.png".to_string(),
+            send_timeout: 10,
+            wait_for_turn: false,
+            turn_button: "turn.png".to_string(),
+            scroll_chat: 0,
+            template_threshold: 0.9,
+            unique_words: false,
+            match_button: String::new(),
+            check_updates: false,
+            update_channel: "stable".to_string(),
+            merge_prefixes: false,
+            worker_threads: 0,
+            blocking_threads: 0
+        }
+    }
+}
+
+impl Config {
+    /// Resolves the paths to runtime files against the data directory.
+    pub fn resolve_paths(mut self) -> Self {
+        let data_dir = paths::data_dir(&self.data_dir);
+        let _ = std::fs::create_dir_all(&data_dir);
+
+        for path in [
+            &mut self.dictionary, &mut self.dictionary_switch, &mut self.capture_file, &mut self.font, &mut self.samples,
+            &mut self.history_file, &mut self.recognition_stats, &mut self.definitions, &mut self.send_button,
+            &mut self.turn_button, &mut self.match_button,
+            &mut self.export_csv, &mut self.export_anki, &mut self.runs_dir, &mut self.events_file, &mut self.dry_run_file, &mut self.server_ca, &mut self.calibration_file,
+            &mut self.sound_round_start, &mut self.sound_low_confidence,
+            &mut self.sound_round_complete, &mut self.sound_error
+        ] {
+            *path = paths::resolve(&data_dir, path);
+        }
+
+        self
+    }
+
+    /// Warns about settings which are ignored because they're invalid.
+    /// This is done once when the configuration loads, rather than each time they're read.
+    pub fn check(&self) {
+        let layout = Layout::new(self.board_rows, self.board_cols);
+        for hole in layout.invalid_holes(&self.board_holes) {
+            warn!("Ignoring the invalid board hole '{}'.", hole);
+        }
+    }
+
+    /// Returns the layout of the board being played.
+    pub fn layout(&self) -> Layout {
+        Layout::new(self.board_rows, self.board_cols).with_holes(&self.board_holes)
+    }
+
+    /// Returns where the tiles and buttons are in captured frames.
+    pub fn screen_layout(&self) -> ScreenLayout {
+        ScreenLayout::new((self.screen_width.max(0) as u32, self.screen_height.max(0) as u32), self.layout())
+    }
+
+    /// Returns the shape of the moves between tiles.
+    pub fn path_style(&self) -> PathStyle {
+        PathStyle {
+            points: self.path_points.min(MAX_PATH_POINTS),
+            curve: self.path_curve.max(0.0),
+            jitter: self.swipe_jitter.max(0.0)
+        }
+    }
+
+    /// Returns how the server moves through each word.
+    pub fn swipe_speed(&self) -> SwipeSpeed {
+        SwipeSpeed {
+            dwell: self.swipe_dwell.max(0),
+            step: self.swipe_step.clamp(0, 127)
+        }
+    }
+
+    /// Returns the timing of each word, slowed by the trace speed and think time.
+    pub fn timing(&self) -> TimingProfile {
+        let timing = TimingProfile::default();
+        let speed = self.trace_speed.clamp(0.05, 1.0);
+
+        // The server waits on each point, then presses or moves on. (10 ms)
+        let swipe = self.swipe_speed();
+        let point_time = match swipe.dwell {
+            0 => timing.point_time,
+            dwell => Duration::from_millis(dwell as u64 + 10)
+        };
+
+        TimingProfile {
+            start_delay: timing.start_delay.div_f64(speed) + Duration::from_millis(self.think_time),
+            word_delay: timing.word_delay.div_f64(speed),
+            point_time,
+            step_size: if swipe.step > 0 { swipe.step as u32 } else { timing.step_size },
+            ..timing
+        }
+    }
+
+    /// Checks if play is slowed down, by the trace speed or think time.
+    pub fn is_paced(&self) -> bool {
+        self.trace_speed < 1.0 || self.think_time > 0
+    }
+
+    /// Returns the correction for the mirror's pointer acceleration.
+    /// Values which aren't positive can't be corrected for, so 1 is used instead.
+    pub fn pointer_correction(&self) -> PointerCorrection {
+        let positive = |name: &str, value: f64| {
+            if value.is_finite() && value > 0.0 {
+                value
+            } else {
+                warn!("{} must be positive, but is {}. Using 1 instead.", name, value);
+                1.0
+            }
+        };
+
+        PointerCorrection {
+            gain: (positive("pointer_gain_x", self.pointer_gain_x), positive("pointer_gain_y", self.pointer_gain_y)),
+            curve: positive("pointer_curve", self.pointer_curve)
         }
     }
 }
@@ -0,0 +1,228 @@
+use std::collections::HashMap;
+use serde::{Deserialize, Serialize};
+use crate::dictionary::{Cursor, Dictionary, Entry};
+use crate::trie::{fold, split_groups};
+
+/// A node in a word graph.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+struct DawgNode {
+    /// The index of this node's first edge.
+    first_edge: u32,
+    /// The number of edges leaving this node.
+    edge_count: u32,
+    /// Does a word end at this node?
+    is_end_of_word: bool,
+    /// The number of words ending at or below this node.
+    words: u32
+}
+
+/// An edge between two nodes of a word graph.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+struct DawgEdge {
+    /// The character of the edge.
+    ch: char,
+    /// The node the edge leads to.
+    target: u32,
+    /// The number of words below the parent which sort before this edge.
+    rank: u32
+}
+
+/// The shape of a node, used to find identical subtrees.
+type Signature = (bool, Vec<(char, u32)>);
+
+/// A directed acyclic word graph.
+/// This is a trie where identical subtrees are stored once, so words
+/// sharing a suffix (e.g. "-ing", "-ed") share their nodes.
+/// Since nodes are shared, frequencies are stored by each word's rank
+/// in sorted order, which is counted while walking the graph.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct Dawg {
+    /// Every node. The root is stored last.
+    nodes: Vec<DawgNode>,
+    /// Every edge. Each node's edges are contiguous and sorted.
+    edges: Vec<DawgEdge>,
+    /// The frequency of each word, in sorted order.
+    frequencies: Vec<u32>
+}
+
+impl Dawg {
+    /// Builds a word graph from sorted, deduplicated words.
+    /// The words are expected to already be normalized.
+    /// words: The words to insert with their frequencies, in sorted order.
+    pub fn from_sorted<S: AsRef<str>>(words: &[(S, u32)]) -> Self {
+        let mut dawg = Dawg {
+            frequencies: words.iter().map(|(_, frequency)| *frequency).collect(),
+            ..Dawg::default()
+        };
+
+        let mut registry = HashMap::new();
+        dawg.build(words, 0, &mut registry);
+
+        dawg.nodes.shrink_to_fit();
+        dawg.edges.shrink_to_fit();
+        dawg
+    }
+
+    /// Adds the node for words sharing the first `depth` bytes.
+    /// An identical node is reused if one was already added.
+    /// Returns the index of the node.
+    /// words: The sorted words under this node.
+    /// depth: The byte offset of the node's character.
+    /// registry: The nodes added so far, by their shape.
+    fn build<S: AsRef<str>>(
+        &mut self,
+        words: &[(S, u32)],
+        depth: usize,
+        registry: &mut HashMap<Signature, u32>
+    ) -> u32 {
+        let (frequency, groups) = split_groups(words, depth);
+
+        // Add the children first, so identical nodes can be found.
+        let children: Vec<(char, u32)> = groups.into_iter()
+            .map(|(ch, group)| (ch, self.build(group, depth + ch.len_utf8(), registry)))
+            .collect();
+
+        let signature = (frequency.is_some(), children);
+        if let Some(index) = registry.get(&signature) {
+            return *index;
+        }
+
+        // Count the words which sort before each edge.
+        let first_edge = self.edges.len() as u32;
+        let mut rank = frequency.is_some() as u32;
+        for (ch, target) in &signature.1 {
+            self.edges.push(DawgEdge { ch: *ch, target: *target, rank });
+            rank += self.nodes[*target as usize].words;
+        }
+
+        let index = self.nodes.len() as u32;
+        self.nodes.push(DawgNode {
+            first_edge,
+            edge_count: signature.1.len() as u32,
+            is_end_of_word: signature.0,
+            words: rank
+        });
+        registry.insert(signature, index);
+
+        index
+    }
+
+    /// Returns the edges leaving a node.
+    /// node: The index of the node.
+    fn edges(&self, node: u32) -> &[DawgEdge] {
+        let node = &self.nodes[node as usize];
+        let start = node.first_edge as usize;
+        &self.edges[start..start + node.edge_count as usize]
+    }
+
+    // Only used to check that nodes are shared.
+    #[cfg(test)]
+    pub fn node_count(&self) -> usize {
+        self.nodes.len()
+    }
+}
+
+/// Finds the node index and rank of a cursor.
+/// cursor: A cursor from a word graph.
+fn node_rank(cursor: Cursor<'_>) -> (u32, u32) {
+    let Cursor::Node(node, rank) = cursor else {
+        unreachable!("The cursor isn't from a word graph.");
+    };
+
+    (node, rank)
+}
+
+impl Dictionary for Dawg {
+    fn root(&self) -> Option<Cursor<'_>> {
+        (!self.nodes.is_empty()).then(|| Cursor::Node(self.nodes.len() as u32 - 1, 0))
+    }
+
+    fn step<'a>(&'a self, cursor: Cursor<'a>, letters: &str) -> Option<Cursor<'a>> {
+        // Count the words which sort before the prefix along the way.
+        let (mut node, mut rank) = node_rank(cursor);
+        for ch in fold(letters) {
+            let edges = self.edges(node);
+            let edge = edges[edges.binary_search_by_key(&ch, |edge| edge.ch).ok()?];
+            node = edge.target;
+            rank += edge.rank;
+        }
+
+        Some(Cursor::Node(node, rank))
+    }
+
+    fn entry(&self, cursor: Cursor<'_>) -> Entry {
+        let (node, rank) = node_rank(cursor);
+        let is_word = self.nodes[node as usize].is_end_of_word;
+        let frequency = if is_word { self.frequencies[rank as usize] } else { 0 };
+        Entry { is_word, frequency }
+    }
+
+    fn letters(&self, cursor: Cursor<'_>) -> Vec<char> {
+        self.edges(node_rank(cursor).0).iter().map(|edge| edge.ch).collect()
+    }
+
+    fn iter(&self) -> Box<dyn Iterator<Item = (String, u32)> + '_> {
+        if self.nodes.is_empty() {
+            return Box::new(std::iter::empty());
+        }
+
+        // Walk the nodes depth-first, so words come out in sorted order.
+        let mut frequencies = self.frequencies.iter();
+        let mut stack = vec![(String::new(), self.nodes.len() as u32 - 1)];
+        Box::new(std::iter::from_fn(move || {
+            while let Some((prefix, index)) = stack.pop() {
+                for edge in self.edges(index).iter().rev() {
+                    let mut word = prefix.clone();
+                    word.push(edge.ch);
+                    stack.push((word, edge.target));
+                }
+
+                if self.nodes[index as usize].is_end_of_word {
+                    return Some((prefix, *frequencies.next()?));
+                }
+            }
+
+            None
+        }))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::arena::ArenaTrie;
+    use crate::dawg::Dawg;
+    use crate::dictionary::Dictionary;
+
+    #[test]
+    pub fn matches_arena_trie() {
+        let mut words: Vec<(&str, u32)> = ["car", "card", "cards", "cart", "carts", "dart", "darts", "do", "ça"]
+            .iter()
+            .enumerate()
+            .map(|(i, word)| (*word, i as u32))
+            .collect();
+        words.sort_unstable();
+
+        let dawg = Dawg::from_sorted(&words);
+        let arena = ArenaTrie::from_sorted(&words);
+
+        for prefix in ["", "c", "car", "cards", "CARTS", "dar", "dart", "darts", "dogs", "ç", "ça"] {
+            assert_eq!(Dictionary::lookup(&dawg, prefix), Dictionary::lookup(&arena, prefix), "{}", prefix);
+            assert_eq!(dawg.children(prefix), arena.children(prefix), "{}", prefix);
+        }
+        assert_eq!(arena.children("car"), vec!['d', 't']);
+
+        // Walking a prefix in steps finds the same entry as looking it up.
+        let dictionaries: [&dyn Dictionary; 2] = [&dawg, &arena];
+        for dictionary in dictionaries {
+            let car = dictionary.root().and_then(|root| dictionary.step(root, "car")).unwrap();
+            let cards = dictionary.step(car, "d").and_then(|card| dictionary.step(card, "s")).unwrap();
+            assert_eq!(Some(dictionary.entry(cards)), dictionary.lookup("cards"));
+            assert!(dictionary.step(car, "x").is_none());
+        }
+        assert!(dawg.iter().eq(arena.iter()));
+
+        // The "-art" and "-arts" endings are shared.
+        assert!(dawg.node_count() < 16);
+        assert_eq!(Dawg::default().lookup("a"), None);
+    }
+}
@@ -0,0 +1,117 @@
+use std::sync::Arc;
+use crate::arena::ArenaTrie;
+use crate::dawg::Dawg;
+use crate::trie::TrieNode;
+
+/// What a dictionary knows about a prefix.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct Entry {
+    /// Is the prefix a whole word?
+    pub is_word: bool,
+    /// How common the word is. This is 0 when no frequency was provided.
+    pub frequency: u32
+}
+
+/// A prefix walked in a dictionary, which can be extended a letter at a time.
+/// Cursors are only valid in the dictionary which made them.
+#[derive(Clone, Copy)]
+pub enum Cursor<'a> {
+    /// A node of a HashMap trie.
+    Trie(&'a TrieNode),
+    /// A node's index, and the number of words which sort before it. (only counted by DAWGs)
+    Node(u32, u32)
+}
+
+/// A set of words which can be searched by prefix.
+/// Words are looked up case-insensitively.
+pub trait Dictionary: Send + Sync {
+    /// Returns the cursor for the empty prefix.
+    /// Returns None if the dictionary has no words.
+    fn root(&self) -> Option<Cursor<'_>>;
+
+    /// Extends a cursor's prefix by some letters.
+    /// Returns None if no word starts with the longer prefix.
+    /// cursor: The prefix walked so far.
+    /// letters: The letters to add.
+    fn step<'a>(&'a self, cursor: Cursor<'a>, letters: &str) -> Option<Cursor<'a>>;
+
+    /// Finds the entry for a cursor's prefix.
+    /// cursor: The prefix walked so far.
+    fn entry(&self, cursor: Cursor<'_>) -> Entry;
+
+    /// Returns the letters which can follow a cursor's prefix, in sorted order.
+    /// cursor: The prefix walked so far.
+    fn letters(&self, cursor: Cursor<'_>) -> Vec<char>;
+
+    /// Returns every word with its frequency, in sorted order.
+    fn iter(&self) -> Box<dyn Iterator<Item = (String, u32)> + '_>;
+
+    /// Finds the entry for a prefix.
+    /// Returns None if no word starts with the prefix.
+    /// prefix: The prefix to look up.
+    fn lookup(&self, prefix: &str) -> Option<Entry> {
+        let cursor = self.step(self.root()?, prefix)?;
+        Some(self.entry(cursor))
+    }
+
+    /// Returns the letters which can follow a prefix, in sorted order.
+    /// This is empty if no word starts with the prefix.
+    /// prefix: The prefix to look up.
+    fn children(&self, prefix: &str) -> Vec<char> {
+        self.root()
+            .and_then(|root| self.step(root, prefix))
+            .map(|cursor| self.letters(cursor))
+            .unwrap_or_default()
+    }
+
+    /// Checks if any word starts with a prefix.
+    /// prefix: The prefix to check.
+    fn has_prefix(&self, prefix: &str) -> bool {
+        self.lookup(prefix).is_some()
+    }
+
+    /// Checks if a word is in the dictionary.
+    /// word: The word to check.
+    fn is_word(&self, word: &str) -> bool {
+        self.lookup(word).is_some_and(|entry| entry.is_word)
+    }
+}
+
+/// The data structure a dictionary is stored in.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum DictionaryBackend {
+    /// A trie with a map of children per node.
+    HashMap,
+    /// A trie stored in flat vectors.
+    Arena,
+    /// A word graph sharing common suffixes.
+    Dawg
+}
+
+impl DictionaryBackend {
+    /// Every supported backend.
+    pub const ALL: [DictionaryBackend; 3] = [
+        DictionaryBackend::HashMap, DictionaryBackend::Arena, DictionaryBackend::Dawg
+    ];
+
+    /// Parses a backend from the configuration.
+    /// Unknown values are treated as `Arena`.
+    /// value: The configured value. ("hashmap", "arena", or "dawg")
+    pub fn parse(value: &str) -> Self {
+        match value.to_lowercase().as_str() {
+            "hashmap" => DictionaryBackend::HashMap,
+            "dawg" => DictionaryBackend::Dawg,
+            _ => DictionaryBackend::Arena
+        }
+    }
+
+    /// Builds a dictionary with this backend.
+    /// words: The normalized words with their frequencies, in sorted order.
+    pub fn build(&self, words: &[(String, u32)]) -> Arc<dyn Dictionary> {
+        match self {
+            DictionaryBackend::HashMap => Arc::new(TrieNode::from_sorted_parallel(words)),
+            DictionaryBackend::Arena => Arc::new(ArenaTrie::from_sorted(words)),
+            DictionaryBackend::Dawg => Arc::new(Dawg::from_sorted(words))
+        }
+    }
+}
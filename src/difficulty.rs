@@ -0,0 +1,93 @@
+use log::warn;
+use crate::config::Config;
+
+/// A named set of strategy settings, so the bot's strength can be picked in one place.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Difficulty {
+    /// Use the strategy settings from the configuration.
+    Custom,
+    /// Plays slowly and stops early, like a casual player.
+    Casual,
+    /// Plays like a strong player, without sweeping the board.
+    Competitive,
+    /// Plays every word as fast as possible.
+    Max
+}
+
+impl Difficulty {
+    /// Parses a difficulty from the configuration or command line.
+    /// Unknown values are treated as `Custom`, with a warning.
+    /// value: The difficulty's name. ("casual", "competitive", or "max")
+    pub fn parse(value: &str) -> Self {
+        match value.to_lowercase().as_str() {
+            "casual" => Difficulty::Casual,
+            "competitive" => Difficulty::Competitive,
+            "max" => Difficulty::Max,
+            "" | "custom" => Difficulty::Custom,
+            _ => {
+                warn!("Unknown difficulty '{}', using the configured settings instead.", value);
+                Difficulty::Custom
+            }
+        }
+    }
+
+    /// Overwrites the strategy settings with this difficulty's.
+    /// A custom difficulty leaves the configuration as it is.
+    /// config: The configuration to change.
+    pub fn apply(self, config: &mut Config) {
+        let (max_words, target_score, trace_speed, think_time) = match self {
+            Difficulty::Custom => return,
+            Difficulty::Casual => (20, 8000, 0.4, 800),
+            Difficulty::Competitive => (60, 30000, 0.75, 200),
+            Difficulty::Max => (0, 0, 1.0, 0)
+        };
+
+        config.max_words = max_words;
+        config.target_score = target_score;
+        config.trace_speed = trace_speed;
+        config.think_time = think_time;
+    }
+}
+
+/// Removes a `--difficulty <name>` option from the command line arguments.
+/// Returns the difficulty's name, if the option was given.
+/// args: The command line arguments, excluding the program name.
+pub fn take_option(args: &mut Vec<String>) -> Option<String> {
+    let index = args.iter().position(|arg| arg == "--difficulty")?;
+    let name = args.get(index + 1).cloned();
+    args.drain(index..(index + 2).min(args.len()));
+
+    name
+}
+
+#[cfg(test)]
+mod test {
+    use crate::config::Config;
+    use crate::difficulty::{take_option, Difficulty};
+
+    #[test]
+    pub fn applies_presets() {
+        assert_eq!(Difficulty::parse("Casual"), Difficulty::Casual);
+        assert_eq!(Difficulty::parse(""), Difficulty::Custom);
+
+        let mut config = Config { max_words: 5, ..Config::default() };
+        Difficulty::Custom.apply(&mut config);
+        assert_eq!(config.max_words, 5);
+
+        assert!(!config.is_paced());
+
+        Difficulty::Casual.apply(&mut config);
+        assert!(config.is_paced());
+
+        Difficulty::Max.apply(&mut config);
+        assert_eq!((config.max_words, config.target_score, config.think_time), (0, 0, 0));
+        assert!(!config.is_paced());
+
+        let mut args: Vec<String> = ["--difficulty", "casual", "solve", "a.board"].iter()
+            .map(|arg| arg.to_string())
+            .collect();
+        assert_eq!(take_option(&mut args), Some("casual".to_string()));
+        assert_eq!(args, vec!["solve", "a.board"]);
+        assert_eq!(take_option(&mut args), None);
+    }
+}
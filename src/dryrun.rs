@@ -0,0 +1,114 @@
+use std::fs::File;
+use std::io::Write;
+use std::time::Instant;
+use async_trait::async_trait;
+use log::{info, warn};
+use serde::Serialize;
+use crate::config::Config;
+use crate::mock::SimulatedMouse;
+use crate::mouse::{Ack, Capabilities, Instruction, MouseBackend, Opcode, Status};
+
+/// An instruction which would have been sent, if this weren't a dry run.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct DryRunRecord {
+    /// The milliseconds since the backend was opened.
+    pub time: u64,
+    /// The instruction's sequence number.
+    pub sequence: u32,
+    /// The instruction's opcode.
+    pub opcode: String,
+    /// The instruction's (x, y) position, if it has one.
+    pub position: Option<(i32, i32)>,
+    /// The points of a group move.
+    pub group: Option<Vec<(i32, i32)>>,
+    /// The cursor's position afterwards, since the last normalize.
+    pub cursor: (i32, i32)
+}
+
+/// Performs instructions on a simulated mouse, without moving anything.
+/// This tests recognition and word choice with any capture backend.
+/// Every instruction is logged, and written to the dry run file as a line of JSON.
+pub struct DryRunBackend {
+    /// The file instructions are written to, if any.
+    file: Option<File>,
+    /// When the backend was opened.
+    started: Instant,
+
+    // The mouse which is moved instead.
+    mouse: SimulatedMouse
+}
+
+impl DryRunBackend {
+    /// Creates a backend which writes to the configured file.
+    /// config: The application configuration.
+    pub fn new(config: &Config) -> Self {
+        let file = match config.dry_run_file.as_str() {
+            "" => None,
+            path => File::create(path)
+                .inspect_err(|error| warn!("Failed to create the dry run file: {}", error))
+                .ok()
+        };
+        info!("This is a dry run, the mouse won't be moved.");
+
+        DryRunBackend { file, started: Instant::now(), mouse: SimulatedMouse::default() }
+    }
+
+    /// Performs an instruction on the simulated mouse.
+    /// Returns the record of the instruction.
+    /// instruction: The instruction to perform.
+    /// sequence: The instruction's sequence number.
+    pub fn record(&mut self, instruction: &Instruction, sequence: u32) -> DryRunRecord {
+        let position = instruction.position.unwrap_or_default();
+        let group = instruction.group.as_deref().unwrap_or_default();
+        self.mouse.apply(instruction.opcode.as_byte(), position, group);
+
+        DryRunRecord {
+            time: self.started.elapsed().as_millis() as u64,
+            sequence,
+            opcode: format!("{:?}", instruction.opcode),
+            position: instruction.position,
+            group: instruction.group.clone(),
+            cursor: self.mouse.cursor
+        }
+    }
+}
+
+#[async_trait]
+impl MouseBackend for DryRunBackend {
+    async fn transmit(&mut self, instruction: &Instruction, sequence: u32) -> std::io::Result<Ack> {
+        let record = self.record(instruction, sequence);
+        info!("Dry run #{} {} {:?} -> cursor at {:?}", record.sequence, record.opcode, record.position, record.cursor);
+
+        if let Some(file) = &mut self.file {
+            writeln!(file, "{}", serde_json::to_string(&record)?)?;
+        }
+
+        // Every opcode is simulated.
+        let position = if instruction.opcode == Opcode::Hello { Capabilities::all().as_position() } else { record.cursor };
+        Ok(Ack { status: Status::Ok, sequence: Some(sequence), position: Some(position) })
+    }
+
+    async fn reconnect(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::config::Config;
+    use crate::dryrun::DryRunBackend;
+    use crate::mouse::{Instruction, SwipeSpeed};
+
+    #[test]
+    pub fn records_instructions() {
+        let config = Config { dry_run_file: String::new(), ..Config::default() };
+        let mut backend = DryRunBackend::new(&config);
+
+        let record = backend.record(&Instruction::absolute(35, 165), 0);
+        assert_eq!((record.opcode.as_str(), record.position, record.cursor), ("NormalMove", Some((35, 165)), (35, 165)));
+
+        let record = backend.record(&Instruction::group(&[(0, 0), (30, 0), (0, 33)], SwipeSpeed::default()), 1);
+        assert_eq!((record.sequence, record.cursor), (1, (65, 198)));
+        assert_eq!(record.group.map(|group| group.len()), Some(3));
+    }
+}
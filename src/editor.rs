@@ -0,0 +1,111 @@
+use std::io::{stdout, Stdout, Write};
+use crossterm::{cursor, queue, terminal};
+use crossterm::event::{self, Event, KeyCode, KeyEventKind};
+use crossterm::style::{Print, Stylize};
+use crate::board::GridPos;
+use crate::solver::WILDCARD;
+
+/// The instructions shown above the board.
+const HELP: &str = "Correct the board: arrows move, letters replace, enter solves.";
+
+/// Shows the board in the terminal and lets the user correct tiles.
+/// Arrow keys select a tile, and typing a letter replaces it. ('u' over 'q' makes "qu")
+/// Typing '?' makes the tile a wildcard.
+/// Pressing enter (or escape) accepts the board.
+/// board: The board to edit in place.
+pub fn edit_board(board: &mut [Vec<String>]) -> anyhow::Result<()> {
+    let mut stdout = stdout();
+    let mut selected = GridPos::default();
+
+    terminal::enable_raw_mode()?;
+    queue!(stdout, cursor::Hide)?;
+
+    let result = edit_loop(&mut stdout, board, &mut selected);
+
+    // Always restore the terminal, even if editing failed.
+    terminal::disable_raw_mode()?;
+    queue!(stdout, cursor::Show, Print("\r\n"))?;
+    stdout.flush()?;
+
+    result
+}
+
+/// Runs the editor until the board is accepted.
+/// stdout: The terminal to draw to.
+/// board: The board to edit.
+/// selected: The currently selected tile.
+fn edit_loop(
+    stdout: &mut Stdout,
+    board: &mut [Vec<String>],
+    selected: &mut GridPos
+) -> anyhow::Result<()> {
+    let rows = board.len();
+    let cols = board[0].len();
+
+    loop {
+        draw(stdout, board, selected)?;
+
+        // Only handle key presses. (Windows also reports releases)
+        let Event::Key(key) = event::read()? else {
+            continue;
+        };
+        if key.kind != KeyEventKind::Press {
+            continue;
+        }
+
+        match key.code {
+            KeyCode::Up if selected.row > 0 => selected.row -= 1,
+            KeyCode::Down if selected.row + 1 < rows => selected.row += 1,
+            KeyCode::Left if selected.col > 0 => selected.col -= 1,
+            KeyCode::Right if selected.col + 1 < cols => selected.col += 1,
+            KeyCode::Char(c) if c.is_alphabetic() => {
+                let tile = &mut board[selected.row][selected.col];
+
+                // Typing 'u' over a 'q' makes a "Qu" tile.
+                *tile = if tile == "q" && c.eq_ignore_ascii_case(&'u') {
+                    "qu".to_string()
+                } else {
+                    c.to_lowercase().to_string()
+                };
+            },
+            KeyCode::Char('?') => board[selected.row][selected.col] = WILDCARD.to_string(),
+            KeyCode::Enter | KeyCode::Esc => return Ok(()),
+            _ => {}
+        }
+
+        // Move back up to redraw over the previous board.
+        queue!(stdout, cursor::MoveUp(rows as u16 + 1))?;
+    }
+}
+
+/// Draws the board with the selected tile highlighted.
+/// stdout: The terminal to draw to.
+/// board: The board to draw.
+/// selected: The currently selected tile.
+fn draw(
+    stdout: &mut Stdout,
+    board: &[Vec<String>],
+    selected: &GridPos
+) -> anyhow::Result<()> {
+    queue!(
+        stdout,
+        cursor::MoveToColumn(0),
+        terminal::Clear(terminal::ClearType::FromCursorDown),
+        Print(HELP), Print("\r\n")
+    )?;
+
+    for (row, tiles) in board.iter().enumerate() {
+        for (col, tile) in tiles.iter().enumerate() {
+            let tile = format!(" {:<2}", tile.to_uppercase());
+            if *selected == GridPos::new(row, col) {
+                queue!(stdout, Print(tile.reverse()))?;
+            } else {
+                queue!(stdout, Print(tile))?;
+            }
+        }
+        queue!(stdout, Print("\r\n"))?;
+    }
+
+    stdout.flush()?;
+    Ok(())
+}
@@ -0,0 +1,79 @@
+use std::fs::OpenOptions;
+use std::io::Write;
+use log::warn;
+use serde::Serialize;
+use tokio::sync::broadcast::error::RecvError;
+use tokio::sync::broadcast::Receiver;
+use tokio::task::JoinHandle;
+
+/// The events kept for subscribers which fall behind.
+pub const EVENT_CAPACITY: usize = 256;
+
+/// The progress of a round, as it's played.
+/// Events are sent to every subscriber of the game, in order.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+#[serde(tag = "event", rename_all = "camelCase")]
+pub enum GameEvent {
+    /// The board was read. (a single row for the Anagrams rack)
+    BoardRecognized { tiles: Vec<Vec<String>> },
+    /// The words to play were chosen, worth the score in total.
+    PlanReady { words: usize, score: u32 },
+    /// A word is about to be played.
+    WordStarted { index: usize, word: String },
+    /// A word was played. Late words were played after the round ended.
+    WordCompleted { index: usize, word: String, score: u32, late: bool },
+    /// The round's words were all played.
+    RoundFinished { played: usize, score: u32 },
+    /// The round stopped because of an error.
+    Error { message: String }
+}
+
+/// Appends each event to a file, as a line of JSON, until the game is dropped.
+/// Returns the task writing the events, which finishes once every event is written.
+/// events: The game's events.
+/// path: The path to the events file.
+pub fn write_events(mut events: Receiver<GameEvent>, path: String) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        let mut file = match OpenOptions::new().create(true).append(true).open(&path) {
+            Ok(file) => file,
+            Err(error) => {
+                warn!("Failed to open the events file {}: {}", path, error);
+                return;
+            }
+        };
+
+        loop {
+            let event = match events.recv().await {
+                Ok(event) => event,
+                Err(RecvError::Lagged(skipped)) => {
+                    warn!("Skipped {} game events which weren't written in time.", skipped);
+                    continue;
+                },
+                Err(RecvError::Closed) => break
+            };
+
+            let written = serde_json::to_string(&event)
+                .map_err(std::io::Error::from)
+                .and_then(|line| writeln!(file, "{}", line));
+            if let Err(error) = written {
+                warn!("Failed to write to the events file: {}", error);
+            }
+        }
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use serde_json::json;
+    use crate::events::GameEvent;
+
+    #[test]
+    pub fn serializes_events() {
+        let event = GameEvent::WordCompleted { index: 2, word: "cats".to_string(), score: 400, late: false };
+        assert_eq!(serde_json::to_value(&event).unwrap(),
+            json!({ "event": "wordCompleted", "index": 2, "word": "cats", "score": 400, "late": false }));
+
+        let event = GameEvent::RoundFinished { played: 12, score: 5200 };
+        assert_eq!(serde_json::to_value(&event).unwrap()["event"], "roundFinished");
+    }
+}
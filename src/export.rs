@@ -0,0 +1,191 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+use serde::Serialize;
+use crate::board::{GridPos, Layout, BOARD_TOP};
+use crate::game::{key_position, KEYBOARD_ROWS, KEY_OFFSET, RETURN_KEY, START_BUTTON, START_BUTTON_SIZE};
+use crate::planner::CoordinateMapper;
+
+/// A word from a round, to be exported.
+#[derive(Clone, Debug)]
+pub struct ExportedWord {
+    /// The word itself.
+    pub word: String,
+    /// The points awarded for the word.
+    pub score: u32,
+    /// Was the word traced before the round ended?
+    pub played: bool
+}
+
+/// A rectangle in a captured frame, in pixels.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Serialize)]
+pub struct Rect {
+    /// The left edge.
+    pub x: i32,
+    /// The top edge.
+    pub y: i32,
+    /// The width.
+    pub width: u32,
+    /// The height.
+    pub height: u32
+}
+
+/// Where a board tile is on the screen.
+#[derive(Clone, Debug, Serialize)]
+pub struct TileRect {
+    /// The tile's row.
+    pub row: usize,
+    /// The tile's column.
+    pub col: usize,
+    /// The tile's rectangle.
+    pub rect: Rect
+}
+
+/// Where a button the bot taps is on the screen.
+#[derive(Clone, Debug, Serialize)]
+pub struct ButtonRect {
+    /// The button's name. ("start", "return", or a keyboard letter)
+    pub name: String,
+    /// The button's rectangle.
+    pub rect: Rect
+}
+
+/// The screen-space rectangles of everything the bot reads or taps.
+/// Overlays can draw these over captures without repeating the layout math.
+#[derive(Clone, Debug, Serialize)]
+pub struct ScreenLayout {
+    /// The size of a captured frame, which the rectangles are measured in.
+    pub frame: (u32, u32),
+    /// The playable tiles, in row order.
+    pub tiles: Vec<TileRect>,
+    /// The buttons, including the on-screen keyboard.
+    pub buttons: Vec<ButtonRect>
+}
+
+impl ScreenLayout {
+    /// Computes the rectangles for a board layout.
+    /// frame: The size of a captured frame.
+    /// layout: The board layout.
+    pub fn new(frame: (u32, u32), layout: Layout) -> Self {
+        let tile_size = layout.tile_size();
+        let tiles = (0..layout.rows)
+            .flat_map(|row| (0..layout.cols).map(move |col| GridPos::new(row, col)))
+            .filter(|pos| layout.is_playable(*pos))
+            .map(|pos| {
+                let (x, y) = layout.tile_origin(pos.row as u32, pos.col as u32);
+                TileRect {
+                    row: pos.row,
+                    col: pos.col,
+                    rect: Rect { x: (BOARD_TOP.0 + x) as i32, y: (BOARD_TOP.1 + y) as i32, width: tile_size, height: tile_size }
+                }
+            })
+            .collect();
+
+        // Buttons are tapped in mouse units, so they're mapped back into the frame.
+        let mapper = CoordinateMapper::new(layout);
+        let button = |name: &str, center: (i32, i32), size: (i32, i32)| {
+            let (left, top) = mapper.frame_pixel((center.0 - size.0 / 2, center.1 - size.1 / 2));
+            let (right, bottom) = mapper.frame_pixel((center.0 + size.0 / 2, center.1 + size.1 / 2));
+            ButtonRect {
+                name: name.to_string(),
+                rect: Rect { x: left, y: top, width: (right - left) as u32, height: (bottom - top) as u32 }
+            }
+        };
+
+        let mut buttons = vec![button("start", START_BUTTON, START_BUTTON_SIZE)];
+        for key in KEYBOARD_ROWS.concat().chars() {
+            if let Some(center) = key_position(key) {
+                buttons.push(button(&key.to_string(), center, KEY_OFFSET));
+            }
+        }
+        buttons.push(button("return", RETURN_KEY, KEY_OFFSET));
+
+        ScreenLayout { frame, tiles, buttons }
+    }
+}
+
+/// Writes a value to a JSON file.
+/// path: The path to the JSON file.
+/// value: The value to write.
+pub fn write_json<P: AsRef<Path>, T: Serialize>(path: P, value: &T) -> anyhow::Result<()> {
+    fs::write(path, serde_json::to_string_pretty(value)?)?;
+    Ok(())
+}
+
+/// Reads word definitions from a file.
+/// Each line is a word, a tab, and its definition.
+/// A missing or empty path results in no definitions.
+/// path: The path to the definitions file.
+pub fn read_definitions(path: &str) -> HashMap<String, String> {
+    if path.is_empty() {
+        return HashMap::new();
+    }
+
+    fs::read_to_string(path)
+        .unwrap_or_default()
+        .lines()
+        .filter_map(|line| line.split_once('\t'))
+        .map(|(word, definition)| (word.trim().to_lowercase(), definition.trim().to_string()))
+        .collect()
+}
+
+/// Writes a round's words to a CSV file.
+/// path: The path to the CSV file.
+/// words: The words to export.
+pub fn write_csv<P: AsRef<Path>>(path: P, words: &[ExportedWord]) -> anyhow::Result<()> {
+    let mut contents = String::from("word,score,played\n");
+    for word in words {
+        contents.push_str(&format!("{},{},{}\n", word.word, word.score, word.played));
+    }
+
+    fs::write(path, contents)?;
+    Ok(())
+}
+
+/// Writes a round's words as an Anki-importable deck.
+/// The deck is tab-separated, with the word on the front of each card.
+/// path: The path to the deck file.
+/// words: The words to export.
+/// definitions: The known word definitions.
+pub fn write_anki<P: AsRef<Path>>(
+    path: P,
+    words: &[ExportedWord],
+    definitions: &HashMap<String, String>
+) -> anyhow::Result<()> {
+    // These headers tell Anki how to read the file.
+    let mut contents = String::from("#separator:tab\n#html:false\n#columns:Word\tDefinition\tScore\n");
+    for word in words {
+        let definition = definitions.get(&word.word)
+            .map(|definition| definition.replace(['\t', '\n'], " "))
+            .unwrap_or_default();
+
+        contents.push_str(&format!("{}\t{}\t{}\n", word.word, definition, word.score));
+    }
+
+    fs::write(path, contents)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use crate::board::Layout;
+    use crate::export::ScreenLayout;
+    use crate::game::START_BUTTON;
+    use crate::planner::CoordinateMapper;
+
+    #[test]
+    pub fn lays_out_tiles_and_buttons() {
+        let layout = Layout::new(5, 5).with_holes("0,0 4,4");
+        let screen = ScreenLayout::new((523, 1135), layout);
+
+        assert_eq!(screen.tiles.len(), 23);
+        assert_eq!((screen.tiles[0].row, screen.tiles[0].col), (0, 1));
+        assert_eq!(screen.buttons.len(), 28);
+
+        // The start button's rectangle covers where it's tapped.
+        let start = screen.buttons[0].rect;
+        let (x, y) = CoordinateMapper::new(layout).frame_pixel(START_BUTTON);
+        assert!(x > start.x && x < start.x + start.width as i32);
+        assert!(y > start.y && y < start.y + start.height as i32);
+    }
+}
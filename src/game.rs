@@ -1,113 +1,952 @@
-use std::time::Duration;
-use log::info;
-use tokio::sync::Mutex;
+use std::collections::HashSet;
+use std::io::Write;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::time::{Duration, Instant};
+use image::RgbaImage;
+use log::{info, warn};
+use tokio::sync::{broadcast, Mutex};
 use tokio::time::sleep;
-use crate::{board, screen};
+use crate::{board, capture, editor, export, limits, planner, screen, solver, sound, template, LETTERS};
+use crate::accuracy::RecognitionStats;
+use crate::artifacts::RunDirectory;
+use crate::blocker::InputBlocker;
+use crate::board::RecognizedBoard;
+use crate::capture::{CaptureBackend, SharedSource};
 use crate::config::Config;
-use crate::mouse::Mouse;
-use crate::solver::Word;
+use crate::events::{GameEvent, EVENT_CAPACITY};
+use crate::export::ExportedWord;
+use crate::limits::PlayHistory;
+use crate::mouse::{Mouse, Reconnected};
+use crate::planner::{CoordinateMapper, TimingProfile, Trace};
+use crate::recording::TraceRecording;
+use crate::solver::{SolveOptions, Word};
 
 /// This is the X, Y mouse coordinates of the start game button.
 pub const START_BUTTON: (i32, i32) = (70, 245);
+/// The size of the start button, in mouse units.
+pub const START_BUTTON_SIZE: (i32, i32) = (60, 20);
+
+/// This is the mouse coordinates of the middle of the chat, outside of a game.
+pub const CHAT_POSITION: (i32, i32) = (130, 200);
+
+/// The shortest and longest times between checks for the opponent's turn.
+pub const TURN_POLL_MIN: Duration = Duration::from_secs(2);
+pub const TURN_POLL_MAX: Duration = Duration::from_secs(60);
+
+/// The rows of the on-screen keyboard, from the top.
+pub const KEYBOARD_ROWS: [&str; 3] = ["qwertyuiop", "asdfghjkl", "zxcvbnm"];
+/// How far each row of keys is indented from the left, in mouse units.
+pub const KEYBOARD_INDENTS: [i32; 3] = [0, 11, 34];
+/// This is the mouse coordinates of the "q" key.
+pub const KEYBOARD_ORIGIN: (i32, i32) = (12, 395);
+/// The mouse distance between adjacent keys, and between rows.
+pub const KEY_OFFSET: (i32, i32) = (23, 27);
+/// This is the mouse coordinates of the return key.
+pub const RETURN_KEY: (i32, i32) = (215, 476);
+/// This is the mouse coordinates of the backspace key.
+pub const BACKSPACE_KEY: (i32, i32) = (215, 449);
+/// The delay after pressing a key.
+pub const KEY_DELAY: Duration = Duration::from_millis(30);
+
+/// How often to check if the mirror is back in front, while paused.
+pub const FOCUS_POLL: Duration = Duration::from_millis(100);
+/// The times a word is retried after the mouse server reconnects.
+pub const RESUME_ATTEMPTS: u32 = 2;
+
+/// The Game Pigeon game being played.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum GameMode {
+    /// Trace words through adjacent tiles on a board.
+    WordHunt,
+    /// Type words made from a rack of letters.
+    Anagrams
+}
+
+impl GameMode {
+    /// Parses a game mode from the configuration.
+    /// Unknown values are treated as `WordHunt`.
+    /// value: The configured value. ("word-hunt" or "anagrams")
+    pub fn parse(value: &str) -> Self {
+        match value.to_lowercase().as_str() {
+            "anagrams" => GameMode::Anagrams,
+            _ => GameMode::WordHunt
+        }
+    }
+}
+
+/// Finds the mouse coordinates of a key on the on-screen keyboard.
+/// Returns None if the keyboard doesn't have the key.
+/// key: The lowercase letter.
+pub fn key_position(key: char) -> Option<(i32, i32)> {
+    KEYBOARD_ROWS.iter().enumerate().find_map(|(row, keys)| {
+        let col = keys.find(key)?;
+        Some((
+            KEYBOARD_ORIGIN.0 + KEYBOARD_INDENTS[row] + col as i32 * KEY_OFFSET.0,
+            KEYBOARD_ORIGIN.1 + row as i32 * KEY_OFFSET.1
+        ))
+    })
+}
+
+/// The words played in the current match.
+/// Some variants ignore or penalize a word played in an earlier round.
+#[derive(Clone, Debug, Default)]
+pub struct MatchWords {
+    /// The words played so far.
+    words: HashSet<String>
+}
+
+impl MatchWords {
+    /// Forgets the played words, for a new match.
+    pub fn reset(&mut self) {
+        self.words.clear();
+    }
+
+    /// Remembers words as played.
+    /// words: The words played this round.
+    pub fn record<'a>(&mut self, words: impl IntoIterator<Item = &'a str>) {
+        self.words.extend(words.into_iter().map(String::from));
+    }
+
+    /// Removes the words already played in this match.
+    /// words: The words found this round.
+    pub fn filter(&self, mut words: Vec<Word>) -> Vec<Word> {
+        words.retain(|word| !self.words.contains(&word.word));
+        words
+    }
+}
 
 pub struct Game {
-    device: String,
-    mouse: Mutex<Mouse>
+    config: Config,
+    mouse: Mutex<Mouse>,
+    frames: SharedSource,
+    mapper: CoordinateMapper,
+    timing: TimingProfile,
+    played: std::sync::Mutex<MatchWords>,
+    run: Option<std::sync::Mutex<RunDirectory>>,
+    blocker: InputBlocker,
+    events: broadcast::Sender<GameEvent>,
+
+    // The number of times the window had to be resized.
+    corrections: AtomicU32
 }
 
 impl Game {
     /// Creates a new game instance.
     /// config: The application configuration.
     pub async fn new(config: &Config) -> anyhow::Result<Self> {
-        // Connect to the mouse server.
-        let mut mouse = Mouse::new(
-            config.server_address.clone(),
-            config.server_port
-        ).await?;
+        // Connect to the mouse.
+        let mut mouse = Mouse::open(config).await?;
 
         // Normalize the mouse connection.
         mouse.normalize().await;
 
+        // Save this run's files for later, if enabled.
+        let mut run = RunDirectory::create(config)?;
+        if let Some(run) = &mut run {
+            info!("Saving this run to {}.", run.path().display());
+            if let Err(error) = run.save_json("layout.json", "layout", &config.screen_layout()) {
+                warn!("Failed to save to the run directory: {}", error);
+            }
+        }
+
         Ok(Game {
-            device: config.device_name.clone(),
-            mouse: Mutex::new(mouse)
+            config: config.clone(),
+            mouse: Mutex::new(mouse),
+            frames: Arc::new(std::sync::Mutex::new(capture::open(config)?)),
+            mapper: CoordinateMapper {
+                correction: config.pointer_correction(),
+                path: config.path_style(),
+                ..CoordinateMapper::new(config.layout())
+            },
+            timing: config.timing(),
+            played: std::sync::Mutex::new(MatchWords::default()),
+            run: run.map(std::sync::Mutex::new),
+            blocker: InputBlocker::new(config),
+            events: broadcast::channel(EVENT_CAPACITY).0,
+            corrections: AtomicU32::new(0)
         })
     }
 
+    /// Subscribes to the progress of every round from now on.
+    pub fn subscribe(&self) -> broadcast::Receiver<GameEvent> {
+        self.events.subscribe()
+    }
+
+    /// Sends an event to the subscribers.
+    /// event: The event to send.
+    fn emit(&self, event: GameEvent) {
+        // Having no subscribers isn't an error.
+        let _ = self.events.send(event);
+    }
+
     /// Starts the game instance.
+    /// Subscribers are told if the round stops because of an error.
     pub async fn start_game(&self) -> anyhow::Result<()> {
+        let result = self.play_round().await;
+        if let Err(error) = &result {
+            self.emit(GameEvent::Error { message: error.to_string() });
+        }
+
+        result
+    }
+
+    /// Plays a round.
+    async fn play_round(&self) -> anyhow::Result<()> {
+        // Make sure the window is still the configured size.
+        self.check_window();
+
+        // Check if the bot has played too many rounds recently.
+        let config = &self.config;
+        let mut history = PlayHistory::load(&config.history_file);
+        if history.is_limited(limits::now(), config.max_rounds_per_hour, config.max_rounds_per_day) {
+            warn!("The round limit has been reached, switching to hint mode.");
+            return self.show_hints().await;
+        }
+
+        history.record(limits::now());
+        history.save(&config.history_file)?;
+        self.save_artifacts(|run| {
+            run.start_round();
+            Ok(())
+        });
+
+        // Forget the words from the last match, if this is a new one.
+        if config.unique_words {
+            self.check_new_match().await?;
+        }
+
+        sound::play(&self.config.sound_round_start);
+
         // Lock the mouse object.
         let mut mouse = self.mouse.lock().await;
 
         // Start the game.
         mouse.move_absolute(START_BUTTON, true).await?;
-        sleep(Duration::from_millis(50)).await;
+        self.settle(&mouse, Duration::from_millis(50)).await;
 
-        mouse.click().await;
-        sleep(Duration::from_millis(50)).await;
+        mouse.click().await?;
+        self.settle(&mouse, Duration::from_millis(50)).await;
 
         // Move the mouse so we aren't blocking the screen.
         mouse.normalize().await;
-        sleep(Duration::from_millis(1000)).await;
+        self.settle(&mouse, Duration::from_millis(1000)).await;
 
         // Release the mouse lock.
         drop(mouse);
 
-        // Get the words on the board.
-        let words = self.get_board();
-        info!("Found {} words.", words.len());
+        // Anagrams has a rack of letters instead of a board.
+        if GameMode::parse(&config.game_mode) == GameMode::Anagrams {
+            return self.play_anagrams().await;
+        }
+
+        // Read the letters on the board.
+        let mut started = Instant::now();
+        let mut board = self.get_board().await?;
+
+        // Let the user correct any misread tiles.
+        // The time spent editing doesn't count towards recognition.
+        if self.config.edit_board {
+            let editing = Instant::now();
+            let recognized = board.tiles.clone();
+            editor::edit_board(&mut board.tiles)?;
+            started += editing.elapsed();
+
+            self.record_accuracy(&recognized, &board.tiles);
+            self.save_artifacts(|run| run.copy("stats.json", "stats", &self.config.recognition_stats));
+        }
+        self.save_artifacts(|run| run.save_json("board.json", "board", &board.tiles));
+
+        // Stop solving if recognition would eat into the round.
+        // Words with several paths are traced along the quickest.
+        let options = SolveOptions {
+            deadline: (self.config.max_recognition_time > 0)
+                .then(|| started + Duration::from_millis(self.config.max_recognition_time)),
+            excluded: self.config.layout().holes,
+            trace_model: Some((self.mapper, self.timing))
+        };
+
+        // Alert the user if any tile might be wrong.
+        let threshold = self.config.low_confidence;
+        if board.confidence.iter().flatten().any(|score| *score < threshold) {
+            warn!("Some tiles were recognized with low confidence.");
+            sound::play(&self.config.sound_low_confidence);
+        }
+
+        // Find all valid words, under the likeliest readings of ambiguous tiles.
+        let hypotheses = board.hypotheses(self.config.ambiguity_margin, self.config.beam_width);
+        let (chosen, mut words) = solver::find_merged_words(&hypotheses, &options);
+        if options.expired() {
+            warn!("Recognition took too long, tracing the {} words found so far.", words.len());
+        }
+        if chosen != 0 {
+            info!("Chose reading {} of {} for the ambiguous tiles.", chosen + 1, hypotheses.len());
+            board.tiles = hypotheses[chosen].clone();
+        }
+
+        self.emit(GameEvent::BoardRecognized { tiles: board.tiles.clone() });
+
+        let safe = words.iter().filter(|word| word.safe).count();
+        info!("Found {} words. ({} safe across {} readings)", words.len(), safe, hypotheses.len());
+
+        // Log the board and the best words.
+        if self.config.log_board {
+            let best: Vec<String> = words.iter()
+                .take(10)
+                .map(|word| format!("{} ({})", word.word, word.score))
+                .collect();
+
+            info!("Recognized board:\n{}", board::format_board(&board));
+            info!("Top words: {}", best.join(", "));
+        }
+
+        // Skip the words played in earlier rounds.
+        if self.config.unique_words {
+            words = self.played.lock().unwrap().filter(words);
+            info!("{} words weren't played earlier in the match.", words.len());
+        }
+        words = self.filter_words(words, true);
+
+        // Trace prefixes as part of their longer words.
+        let mut prefixes = Vec::new();
+        if self.config.merge_prefixes {
+            prefixes = words.clone();
+            words = solver::merge_prefixes(words);
+            info!("Merged prefixes into {} words.", words.len());
+        }
+
+        // Leave the rest of the words for weaker difficulties.
+        if self.config.max_words > 0 {
+            words.truncate(self.config.max_words);
+        }
+
+        // Plan the movement for every word.
+        let traces: Vec<Trace> = words.iter()
+            .map(|word| planner::plan_word(word, &self.mapper, &self.timing))
+            .collect();
+        self.emit(GameEvent::PlanReady { words: traces.len(), score: traces.iter().map(|trace| trace.score).sum() });
+
+        // Show the plan before tracing.
+        if self.config.preview_plan && !self.preview_plan(&traces)? {
+            info!("The plan was rejected.");
+            return Ok(());
+        }
 
         // Start the primary loop.
-        self.do_mouse_loop(words).await?;
+        self.save_artifacts(|run| run.save_json("traces.json", "traces", &traces));
+        let mut recording = TraceRecording::new(board.tiles.clone());
+        let (sent, played) = self.do_mouse_loop(&traces, &mut recording).await?;
+        self.save_artifacts(|run| run.save_compact_json("recording.json", "recording", &recording));
+
+        // Remember every word which was traced, even late, and the prefixes traced as part of them.
+        let traced = &words[..sent];
+        let folded = prefixes.iter().filter(|prefix| {
+            traced.iter().any(|word| word.characters.starts_with(&prefix.characters))
+        });
+        self.played.lock().unwrap().record(traced.iter().chain(folded).map(|word| word.word.as_str()));
+
+        // Export the words from this round.
+        self.export_words(&traces, played)?;
+
+        // Send the result to the opponent.
+        if self.config.auto_send {
+            self.send_result().await?;
+        }
+
+        Ok(())
+    }
+
+    /// Reads the Anagrams rack, then types every word it makes.
+    async fn play_anagrams(&self) -> anyhow::Result<()> {
+        let started = Instant::now();
+
+        // Read the letters in the rack, off of the async threads.
+        let frame = capture::next_frame(&self.frames).await?;
+        self.save_artifacts(|run| run.save_image("frame.png", &frame));
+        let letters = LETTERS.read().unwrap().clone();
+        let size = self.config.rack_size.clamp(1, board::MAX_SIDE);
+        let rack = tokio::task::spawn_blocking(move || board::read_rack(&frame, &letters, size)).await?;
+
+        // Alert the user if any letter might be wrong.
+        if rack.iter().any(|(_, score)| *score < self.config.low_confidence) {
+            warn!("Some letters were recognized with low confidence.");
+            sound::play(&self.config.sound_low_confidence);
+        }
+
+        let rack: Vec<String> = rack.into_iter().map(|(letter, _)| letter).collect();
+        info!("Recognized rack: {}", rack.concat().to_uppercase());
+        self.emit(GameEvent::BoardRecognized { tiles: vec![rack.clone()] });
+        self.save_artifacts(|run| run.save_json("rack.json", "board", &rack));
+
+        // Find every word in the rack.
+        let options = SolveOptions {
+            deadline: (self.config.max_recognition_time > 0)
+                .then(|| started + Duration::from_millis(self.config.max_recognition_time)),
+            ..SolveOptions::default()
+        };
+        let mut words = solver::find_anagrams(&rack, &options);
+        info!("Found {} words.", words.len());
+
+        // Skip the words played in earlier rounds.
+        if self.config.unique_words {
+            words = self.played.lock().unwrap().filter(words);
+        }
+        words = self.filter_words(words, false);
+        if self.config.max_words > 0 {
+            words.truncate(self.config.max_words);
+        }
+        self.emit(GameEvent::PlanReady { words: words.len(), score: words.iter().map(|word| word.score).sum() });
+
+        let typed = self.do_keyboard_loop(&words).await?;
+        self.played.lock().unwrap().record(typed.iter().map(|&index| words[index].word.as_str()));
+        info!("Typed {} of {} words.", typed.len(), words.len());
+
+        Ok(())
+    }
+
+    /// Types words on the on-screen keyboard, submitting each with the return key.
+    /// Returns the indices of the words typed before the round ended.
+    /// words: The words to type.
+    async fn do_keyboard_loop(&self, words: &[Word]) -> anyhow::Result<Vec<usize>> {
+        // Lock the mouse.
+        let mut mouse = self.mouse.lock().await;
+
+        let round_end = Instant::now() + Duration::from_secs(self.config.round_time);
+        let mut typed = Vec::new();
+        let mut score = 0;
+
+        for (index, word) in words.iter().enumerate() {
+            if Instant::now() > round_end || self.reached_target(score) {
+                break;
+            }
+
+            // Skip words with letters the keyboard doesn't have.
+            let Some(keys) = word.word.chars().map(key_position).collect::<Option<Vec<_>>>() else {
+                warn!("Can't type the word: {}", word.word);
+                continue;
+            };
+
+            info!("Typing word: {}", word.word);
+            self.emit(GameEvent::WordStarted { index, word: word.word.clone() });
+            // Pause before the word, if the difficulty slows play down.
+            if self.config.is_paced() {
+                self.settle(&mouse, self.timing.start_delay).await;
+            }
+
+            // Retype the word if the mouse server reconnects partway through.
+            let mut attempt = 0;
+            loop {
+                match self.type_keys(&mut mouse, &keys).await {
+                    Err(error) if error.is::<Reconnected>() && attempt < RESUME_ATTEMPTS => {
+                        attempt += 1;
+                        warn!("Retyping word: {}", word.word);
+
+                        // Erase whatever was typed, without submitting a shorter word.
+                        self.tap_keys(&mut mouse, &vec![BACKSPACE_KEY; keys.len()]).await?;
+                    }
+                    result => break result?
+                }
+            }
+
+            typed.push(index);
+            score += word.score;
+            self.emit(GameEvent::WordCompleted { index, word: word.word.clone(), score: word.score, late: false });
+        }
+
+        info!("Done!");
+        self.emit(GameEvent::RoundFinished { played: typed.len(), score });
+        sound::play_and_wait(&self.config.sound_round_complete);
+
+        Ok(typed)
+    }
+
+    /// Taps keys on the on-screen keyboard, followed by the return key.
+    /// mouse: The locked mouse.
+    /// keys: The positions of the keys to tap.
+    async fn type_keys(&self, mouse: &mut Mouse, keys: &[(i32, i32)]) -> anyhow::Result<()> {
+        self.tap_keys(mouse, keys).await?;
+        self.tap_keys(mouse, &[RETURN_KEY]).await
+    }
+
+    /// Taps keys on the on-screen keyboard.
+    /// mouse: The locked mouse.
+    /// keys: The positions of the keys to tap.
+    async fn tap_keys(&self, mouse: &mut Mouse, keys: &[(i32, i32)]) -> anyhow::Result<()> {
+        for &key in keys {
+            self.wait_for_focus(mouse).await;
+            mouse.move_absolute(key, true).await?;
+            mouse.click().await?;
+            self.settle(mouse, KEY_DELAY).await;
+        }
 
         Ok(())
     }
 
+    /// Waits for the round to end, then taps the send button.
+    /// The button is found by matching the configured template.
+    async fn send_result(&self) -> anyhow::Result<()> {
+        let config = &self.config;
+        let template = image::open(&config.send_button)?.to_rgba8();
+
+        // The button appears once the round ends.
+        let deadline = Instant::now() + Duration::from_secs(config.round_time + config.send_timeout);
+        let Some(position) = self.wait_for_template(&template, true, deadline).await? else {
+            warn!("The send button never appeared.");
+            return Ok(());
+        };
+
+        self.tap_template(position, &template).await?;
+
+        // Confirm the button went away.
+        let deadline = Instant::now() + Duration::from_secs(config.send_timeout);
+        if self.wait_for_template(&template, false, deadline).await?.is_some() {
+            warn!("The result might not have been sent.");
+        } else {
+            info!("Sent the result.");
+        }
+
+        Ok(())
+    }
+
+    /// Forgets the played words if the screen shows a new match.
+    /// This is recognized by the configured match image.
+    async fn check_new_match(&self) -> anyhow::Result<()> {
+        if self.config.match_button.is_empty() {
+            return Ok(());
+        }
+
+        let template = image::open(&self.config.match_button)?.to_rgba8();
+        if self.find_template(&template).await?.is_some() {
+            info!("A new match started, forgetting the played words.");
+            self.played.lock().unwrap().reset();
+        }
+
+        Ok(())
+    }
+
+    /// Waits for the opponent to play, then opens the new game.
+    /// The screen is checked less often the longer the wait goes on.
+    pub async fn wait_for_turn(&self) -> anyhow::Result<()> {
+        let template = image::open(&self.config.turn_button)?.to_rgba8();
+
+        let mut delay = TURN_POLL_MIN;
+        loop {
+            if let Some(position) = self.find_template(&template).await? {
+                info!("It's our turn again.");
+                self.tap_template(position, &template).await?;
+
+                // Give the game time to open.
+                sleep(Duration::from_millis(1000)).await;
+                return Ok(());
+            }
+
+            info!("Waiting {:?} for the opponent to play...", delay);
+            sleep(delay).await;
+            delay = (delay * 2).min(TURN_POLL_MAX);
+
+            // The bubble is added to the bottom of the chat, which might be out of view.
+            if self.config.scroll_chat > 0 {
+                self.scroll_chat().await?;
+            }
+        }
+    }
+
+    /// Scrolls the chat down by the configured notches.
+    async fn scroll_chat(&self) -> anyhow::Result<()> {
+        let mut mouse = self.mouse.lock().await;
+        mouse.move_absolute(CHAT_POSITION, true).await?;
+        mouse.scroll(self.config.scroll_chat).await?;
+        mouse.normalize().await;
+
+        Ok(())
+    }
+
+    /// Taps the center of a template on the screen.
+    /// position: The top-left pixel of the template.
+    /// template: The matched image.
+    async fn tap_template(&self, position: (u32, u32), template: &RgbaImage) -> anyhow::Result<()> {
+        let (width, height) = template.dimensions();
+        let center = self.mapper.pixel_position((position.0 + width / 2, position.1 + height / 2));
+
+        let mut mouse = self.mouse.lock().await;
+        mouse.move_absolute(center, true).await?;
+        self.settle(&mouse, Duration::from_millis(50)).await;
+        mouse.click().await?;
+        mouse.normalize().await;
+
+        Ok(())
+    }
+
+    /// Finds a template on the screen.
+    /// Returns its top-left pixel, or None if it isn't visible.
+    /// template: The image to look for.
+    async fn find_template(&self, template: &RgbaImage) -> anyhow::Result<Option<(u32, u32)>> {
+        let frame = capture::next_frame(&self.frames).await?;
+
+        // Search off of the async threads, so the mouse link stays responsive.
+        let template = template.clone();
+        let found = tokio::task::spawn_blocking(move || template::find_template(&frame, &template)).await?;
+
+        Ok(found
+            .filter(|(_, similarity)| *similarity >= self.config.template_threshold)
+            .map(|(position, _)| position))
+    }
+
+    /// Polls the screen until a template appears or disappears.
+    /// Returns where the template was last seen, or None if it isn't visible.
+    /// template: The image to look for.
+    /// visible: Wait for the template to appear? (false to disappear)
+    /// deadline: When to stop waiting.
+    async fn wait_for_template(
+        &self,
+        template: &RgbaImage,
+        visible: bool,
+        deadline: Instant
+    ) -> anyhow::Result<Option<(u32, u32)>> {
+        loop {
+            let found = self.find_template(template).await?;
+            if found.is_some() == visible || Instant::now() >= deadline {
+                return Ok(found);
+            }
+
+            sleep(Duration::from_millis(500)).await;
+        }
+    }
+
+    /// Records how accurately the board was recognized.
+    /// Warns about letters which are being recognized worse than before.
+    /// Failing to save the statistics is logged, without stopping the round.
+    /// recognized: The tiles as they were recognized.
+    /// corrected: The tiles after the user's corrections.
+    fn record_accuracy(&self, recognized: &[Vec<String>], corrected: &[Vec<String>]) {
+        let config = &self.config;
+        if config.recognition_stats.is_empty() {
+            return;
+        }
+
+        let mut stats = RecognitionStats::load(&config.recognition_stats);
+        stats.record(limits::now(), recognized, corrected);
+        stats.retain_days(limits::now(), config.stats_retention_days);
+        if let Err(error) = stats.save(&config.recognition_stats) {
+            warn!("Failed to save the recognition statistics: {}", error);
+        }
+
+        for letter in stats.degraded(limits::now(), config.accuracy_warning) {
+            warn!("'{}' is recognized {:.0}% of the time, down from {:.0}%. Consider regenerating its template.",
+                letter.letter, letter.recent * 100.0, letter.previous * 100.0);
+        }
+    }
+
+    /// Exports the round's words to the configured files.
+    /// traces: The planned traces, in order.
+    /// played: The number of traces finished before the round ended.
+    fn export_words(&self, traces: &[Trace], played: usize) -> anyhow::Result<()> {
+        let config = &self.config;
+        if config.export_csv.is_empty() && config.export_anki.is_empty() {
+            return Ok(());
+        }
+
+        let words: Vec<ExportedWord> = traces.iter()
+            .enumerate()
+            .map(|(index, trace)| ExportedWord {
+                word: trace.word.clone(),
+                score: trace.score,
+                played: index < played
+            })
+            .collect();
+
+        if !config.export_csv.is_empty() {
+            export::write_csv(&config.export_csv, &words)?;
+        }
+        if !config.export_anki.is_empty() {
+            let definitions = export::read_definitions(&config.definitions);
+            export::write_anki(&config.export_anki, &words, &definitions)?;
+        }
+
+        info!("Exported {} words.", words.len());
+        Ok(())
+    }
+
+    /// Shows the words on the board without tracing them.
+    /// The user is expected to start the round and play it themselves.
+    async fn show_hints(&self) -> anyhow::Result<()> {
+        print!("Start the round, then press enter to read the board. ");
+        std::io::stdout().flush()?;
+        std::io::stdin().read_line(&mut String::new())?;
+
+        // Read and solve the board.
+        let board = self.get_board().await?;
+        let options = SolveOptions { excluded: self.config.layout().holes, ..SolveOptions::default() };
+        let words = solver::find_words(&board.tiles, &options);
+
+        info!("Recognized board:\n{}", board::format_board(&board));
+        for word in words {
+            info!("{:<16} {:>5} pts {}", word.word, word.score, word.assumptions());
+        }
+
+        Ok(())
+    }
+
+    /// Restores the window's size and position if they changed.
+    /// Windows which aren't managed are left alone.
+    /// Warns if the window has been corrected more than once.
+    fn check_window(&self) {
+        let config = &self.config;
+        if !config.manage_window ||
+            CaptureBackend::parse(&config.capture_backend) != CaptureBackend::Window {
+            return;
+        }
+
+        let corrected = unsafe {
+            let handle = screen::get_window(&config.device_name);
+            screen::enforce_size(handle,
+                                 config.screen_width, config.screen_height,
+                                 (config.window_x, config.window_y),
+                                 config.client_area)
+        };
+
+        if corrected && self.corrections.fetch_add(1, Ordering::Relaxed) > 0 {
+            warn!("The window keeps changing size; board coordinates may be unreliable.");
+        }
+    }
+
+    /// Checks if the mirror is in front, so mouse input reaches it.
+    /// Always true unless pausing on focus loss is enabled for a mirrored window.
+    fn has_focus(&self) -> bool {
+        let config = &self.config;
+        if !config.pause_on_focus_loss ||
+            CaptureBackend::parse(&config.capture_backend) != CaptureBackend::Window {
+            return true;
+        }
+
+        unsafe { screen::is_foreground(screen::get_window(&config.device_name)) }
+    }
+
+    /// Waits while another window is in front of the mirror.
+    /// The mouse button is released first, so nothing is dragged in the other window.
+    /// mouse: The locked mouse.
+    async fn wait_for_focus(&self, mouse: &mut Mouse) {
+        if self.has_focus() {
+            return;
+        }
+
+        warn!("Another window is in front of the mirror, pausing.");
+        if let Err(error) = mouse.button(Some(false)).await {
+            warn!("Failed to release the mouse button: {}", error);
+        }
+
+        while !self.has_focus() {
+            sleep(FOCUS_POLL).await;
+        }
+        info!("The mirror is back in front, resuming.");
+    }
+
+    /// Logs the planned words with an estimated timeline.
+    /// Returns false if confirmation is required and the user declined.
+    /// traces: The planned traces, in order.
+    fn preview_plan(&self, traces: &[Trace]) -> anyhow::Result<bool> {
+        let budget = Duration::from_secs(self.config.round_time);
+
+        let mut elapsed = Duration::ZERO;
+        let mut score = 0;
+
+        info!("Planned {} words:", traces.len());
+        for (index, trace) in traces.iter().enumerate() {
+            let duration = planner::estimate_duration(trace, &self.timing);
+            elapsed += duration;
+
+            // Only words finished within the round count towards the score.
+            let marker = if elapsed <= budget {
+                score += trace.score;
+                ' '
+            } else {
+                '!'
+            };
+
+            info!("{marker} {:>3}. {:<16} {:>5} pts  {:>6.2}s  {:>6.2}s / {}s",
+                index + 1, trace.word, trace.score,
+                duration.as_secs_f32(), elapsed.as_secs_f32(), budget.as_secs());
+        }
+        info!("Expected score: {} ({:.2}s of {}s)",
+            score, elapsed.as_secs_f32(), budget.as_secs());
+
+        if !self.config.confirm_plan {
+            return Ok(true);
+        }
+
+        // Wait for the user to confirm the plan.
+        print!("Start tracing? [Y/n] ");
+        std::io::stdout().flush()?;
+
+        let mut answer = String::new();
+        std::io::stdin().read_line(&mut answer)?;
+
+        Ok(!answer.trim().eq_ignore_ascii_case("n"))
+    }
+
     /// This is the primary loop used for solving the game.
-    /// words: The words on the board to select.
-    async fn do_mouse_loop(&self, words: Vec<Word>) -> anyhow::Result<()> {
+    /// Returns the number of traces sent, and how many of those finished before the round ended.
+    /// traces: The planned traces to perform.
+    /// recording: Records the traces as they're performed.
+    async fn do_mouse_loop(&self, traces: &[Trace], recording: &mut TraceRecording) -> anyhow::Result<(usize, usize)> {
         // Lock the mouse.
         let mut mouse = self.mouse.lock().await;
 
+        let round_end = Instant::now() + Duration::from_secs(self.config.round_time);
+        let mut played = 0;
+        let mut score = 0;
+
         // Iterate over every word.
-        for word in words {
-            info!("Trying to solve word: {}", word.word);
+        let mut sent = 0;
+        for (index, trace) in traces.iter().enumerate() {
+            if self.reached_target(score) {
+                info!("Reached the target of {} points.", self.config.target_score);
+                break;
+            }
 
-            mouse.move_absolute(board::START_POS, true).await?;
-            sleep(Duration::from_millis(50)).await;
+            info!("Trying to solve word: {}", trace.word);
+            self.emit(GameEvent::WordStarted { index, word: trace.word.clone() });
 
-            let mut grid_pos = (0, 0);
-            let mut points: Vec<(i32, i32)> = Vec::new();
+            // Retrace the word if the mouse server reconnects partway through.
+            let started = Instant::now();
+            let mut attempt = 0;
+            loop {
+                match self.do_trace(&mut mouse, trace).await {
+                    Err(error) if error.is::<Reconnected>() && attempt < RESUME_ATTEMPTS => {
+                        attempt += 1;
+                        warn!("Retracing word: {}", trace.word);
+                    }
+                    result => break result?
+                }
+            }
+            recording.record(trace, started);
+            sent += 1;
 
-            // Calculate the points to move to.
-            for (x, y) in &word.characters {
-                let delta = board::grid_to_mouse(
-                    (grid_pos.0 as i32, grid_pos.1 as i32),
-                    (*x) as i32, (*y) as i32
-                );
+            self.settle(&mouse, trace.end_delay).await;
 
-                points.push(delta);
-                grid_pos = (*x, *y);
+            let late = Instant::now() > round_end;
+            if !late {
+                played += 1;
+                score += trace.score;
             }
+            self.emit(GameEvent::WordCompleted { index, word: trace.word.clone(), score: trace.score, late });
+        }
+
+        info!("Done! The mouse's round trips averaged {:.1}ms.", mouse.latency().as_secs_f64() * 1000.0);
+        self.emit(GameEvent::RoundFinished { played, score });
+        sound::play_and_wait(&self.config.sound_round_complete);
 
-            // Move the mouse.
-            mouse.move_group(points).await?;
+        Ok((sent, played))
+    }
 
-            sleep(Duration::from_millis(100)).await;
+    /// Drops the words under the minimum score, and the hard words in easy mode.
+    /// words: The words to filter.
+    /// paths: Are the words traced? If not, easy words are only limited by length.
+    fn filter_words(&self, mut words: Vec<Word>, paths: bool) -> Vec<Word> {
+        let config = &self.config;
+        if config.min_score == 0 && !config.easy_words {
+            return words;
         }
 
-        info!("Done!");
+        let (shortest, longest) = solver::EASY_LENGTH;
+        words.retain(|word| word.score >= config.min_score && (!config.easy_words ||
+            if paths { word.is_easy() } else { (shortest..=longest).contains(&word.len()) }));
+        info!("{} words are left after filtering.", words.len());
+
+        words
+    }
+
+    /// Moves to the origin, then traces a word.
+    /// mouse: The locked mouse.
+    /// trace: The trace to perform.
+    async fn do_trace(&self, mouse: &mut Mouse, trace: &Trace) -> anyhow::Result<()> {
+        mouse.move_absolute(self.mapper.origin, true).await?;
+        self.settle(mouse, trace.start_delay).await;
+
+        // Don't drag through another window.
+        self.wait_for_focus(mouse).await;
+
+        // Move the mouse, keeping the user's mouse from pulling it off the path.
+        // Input is let through again when the trace ends or fails.
+        let _blocked = self.blocker.block();
+        mouse.move_group(trace.points.clone(), self.config.swipe_speed()).await?;
 
         Ok(())
     }
 
+    /// Waits after a mouse action, for longer over a slow link to the mouse.
+    /// The delays are tuned for a local link, so the measured round trip is added to them.
+    /// mouse: The locked mouse.
+    /// delay: The delay over a local link.
+    async fn settle(&self, mouse: &Mouse, delay: Duration) {
+        let latency = if self.config.compensate_latency { mouse.latency() } else { Duration::ZERO };
+        sleep(delay + latency).await;
+    }
+
+    /// Saves files to the run directory, if runs are saved.
+    /// Failing to save is logged, without stopping the round.
+    /// save: Saves the files.
+    fn save_artifacts(&self, save: impl FnOnce(&mut RunDirectory) -> anyhow::Result<()>) {
+        let Some(run) = &self.run else {
+            return;
+        };
+
+        if let Err(error) = save(&mut run.lock().unwrap()) {
+            warn!("Failed to save to the run directory: {}", error);
+        }
+    }
+
+    /// Checks if the round's words are worth the target score.
+    /// score: The points played so far.
+    fn reached_target(&self, score: u32) -> bool {
+        self.config.target_score > 0 && score >= self.config.target_score
+    }
+
     /// Takes a picture of the device.
-    /// Returns a vector of words found on the board.
+    /// Returns the letters on the board.
     /// This method assumes the game board is open.
-    fn get_board(&self) -> Vec<Word> {
+    async fn get_board(&self) -> anyhow::Result<RecognizedBoard> {
         // Take a screenshot of the board.
-        let board = screen::take_screenshot(&self.device).unwrap();
-        // Perform OCR on the board and find all words.
-        board::words_in_image(&board)
+        let board = capture::next_frame(&self.frames).await?;
+        self.save_artifacts(|run| run.save_image("frame.png", &board));
+
+        // Perform OCR on the board, off of the async threads.
+        let letters = LETTERS.read().unwrap().clone();
+        let (layout, count) = (self.config.layout(), self.config.letter_candidates);
+        let recognized = tokio::task::spawn_blocking(move || {
+            board::read_board(&board, &letters, layout, count)
+        }).await?;
+
+        Ok(recognized)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::game::{key_position, GameMode, MatchWords, KEYBOARD_ORIGIN, KEY_OFFSET};
+    use crate::solver::word;
+
+    #[test]
+    pub fn maps_keys_to_mouse() {
+        assert_eq!(key_position('q'), Some(KEYBOARD_ORIGIN));
+        assert_eq!(key_position('e'), Some((KEYBOARD_ORIGIN.0 + 2 * KEY_OFFSET.0, KEYBOARD_ORIGIN.1)));
+        assert_eq!(key_position('a').unwrap().1, KEYBOARD_ORIGIN.1 + KEY_OFFSET.1);
+        assert!(key_position('z').unwrap().0 > key_position('a').unwrap().0);
+        assert_eq!(key_position('é'), None);
+
+        assert_eq!(GameMode::parse("Anagrams"), GameMode::Anagrams);
+        assert_eq!(GameMode::parse(""), GameMode::WordHunt);
+    }
+
+    #[test]
+    pub fn skips_played_words() {
+        let row = [(0, 0), (0, 1), (0, 2), (0, 3)];
+        let mut played = MatchWords::default();
+        played.record(["cat", "dog"]);
+        let words = played.filter(vec![word("cat", &row), word("cats", &row), word("dog", &row)]);
+        assert_eq!(words.iter().map(|word| word.word.as_str()).collect::<Vec<_>>(), vec!["cats"]);
+
+        played.reset();
+        assert_eq!(played.filter(vec![word("cat", &row)]).len(), 1);
     }
 }
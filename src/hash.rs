@@ -0,0 +1,36 @@
+/// The 64-bit FNV-1a offset basis.
+const FNV_OFFSET: u64 = 0xcbf29ce484222325;
+/// The 64-bit FNV-1a prime.
+const FNV_PRIME: u64 = 0x100000001b3;
+
+/// Hashes bytes with 64-bit FNV-1a.
+/// This is quick and stable between runs, but it isn't cryptographic.
+/// bytes: The bytes to hash.
+pub fn fnv(bytes: &[u8]) -> u64 {
+    let mut hash = FNV_OFFSET;
+    for byte in bytes {
+        hash ^= *byte as u64;
+        hash = hash.wrapping_mul(FNV_PRIME);
+    }
+
+    hash
+}
+
+/// Hashes bytes with 64-bit FNV-1a, as 16 hex digits.
+/// bytes: The bytes to hash.
+pub fn fnv_hex(bytes: &[u8]) -> String {
+    format!("{:016x}", fnv(bytes))
+}
+
+#[cfg(test)]
+mod test {
+    use crate::hash::{fnv, fnv_hex};
+
+    #[test]
+    pub fn hashes_bytes() {
+        // The published FNV-1a test vectors.
+        assert_eq!(fnv(b""), 0xcbf29ce484222325);
+        assert_eq!(fnv(b"a"), 0xaf63dc4c8601ec8c);
+        assert_eq!(fnv_hex(b"foobar"), "85944171f73967e8");
+    }
+}
@@ -1,16 +1,66 @@
 use std::collections::HashMap;
-use image::{DynamicImage, Rgb, RgbImage, RgbaImage};
+use std::path::Path;
+use image::{DynamicImage, GrayImage, Rgb, RgbImage, RgbaImage};
+use image_compare::{Algorithm, Metric};
 
 pub const WHITE: Rgb<u8> = Rgb([255, 255, 255]);
 
+/// The similarity metric used to compare tiles against letters.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum CompareMetric {
+    /// A blend of structure and color similarity.
+    Hybrid,
+    /// Root mean squared error of the RGB channels.
+    Rms,
+    /// Structural similarity of the RGB channels.
+    Ssim,
+    /// Correlation of the grayscale histograms.
+    Histogram
+}
+
+impl CompareMetric {
+    /// Every supported metric.
+    pub const ALL: [CompareMetric; 4] = [
+        CompareMetric::Hybrid, CompareMetric::Rms,
+        CompareMetric::Ssim, CompareMetric::Histogram
+    ];
+
+    /// Parses a metric from the configuration.
+    /// Unknown values are treated as `Hybrid`.
+    /// value: The configured value. ("hybrid", "rms", "ssim", or "histogram")
+    pub fn parse(value: &str) -> Self {
+        match value.to_lowercase().as_str() {
+            "rms" => CompareMetric::Rms,
+            "ssim" => CompareMetric::Ssim,
+            "histogram" => CompareMetric::Histogram,
+            _ => CompareMetric::Hybrid
+        }
+    }
+}
+
+/// English letters, ordered from most to least common.
+const FREQUENCY_ORDER: &str = "etaoinshrdlcumwfgypbvkjxqz";
+
 pub struct Letters {
-    pub letters: HashMap<char, RgbaImage>
+    pub letters: HashMap<char, RgbaImage>,
+
+    // The order to compare letters in, most common first.
+    order: Vec<char>,
+    // A match scoring above this is accepted immediately.
+    threshold: f64,
+    // Is 'q' matched against a "Qu" tile?
+    qu: bool,
+    // The metric used to compare images.
+    metric: CompareMetric
 }
 
 impl Letters {
     /// Creates a new letter set/matcher.
     /// path: The path to the letter images.
-    pub fn new(path: &String) -> Self {
+    /// threshold: A score which is accepted without checking other letters.
+    ///            Use 1 to always compare every letter.
+    /// metric: The similarity metric to compare with.
+    pub fn new(path: &String, threshold: f64, metric: CompareMetric) -> Self {
         let mut letters = HashMap::new();
 
         // There are currently no known letters for 'Q' and 'Z'.
@@ -19,37 +69,119 @@ impl Letters {
             letters.insert(c, image.to_rgba8());
         }
 
-        Self { letters }
+        // Boggle-style boards have a single "Qu" tile, which stands in for 'Q'.
+        let qu = Path::new(&format!("{}/qu.png", path)).exists();
+        if qu {
+            let image = image::open(format!("{}/qu.png", path)).unwrap();
+            letters.insert('q', image.to_rgba8());
+        }
+
+        // Compare the most common letters first.
+        let order = FREQUENCY_ORDER.chars()
+            .filter(|c| letters.contains_key(c))
+            .collect();
+
+        Self { letters, order, threshold, qu, metric }
+    }
+
+    /// Returns the lowercase text of a tile recognized as a letter.
+    /// This is "qu" for a "Qu" tile.
+    /// letter: The recognized letter.
+    pub fn tile_text(&self, letter: char) -> String {
+        if letter == 'q' && self.qu {
+            return "qu".to_string();
+        }
+
+        letter.to_lowercase().to_string()
     }
 
     /// Determines which letter is the closest match.
+    /// Returns the letter and its similarity score. (0 to 1)
+    /// image: The image to compare.
+    pub fn compare(&self, image: &RgbImage) -> (char, f64) {
+        self.candidates(image, 1)
+            .first()
+            .copied()
+            .unwrap_or((' ', 0f64))
+    }
+
+    /// Determines the closest matching letters.
+    /// Returns up to `count` letters and their scores, best first.
     /// image: The image to compare.
-    pub fn compare(&self, image: &RgbImage) -> char {
-        let mut best = (' ', 0f64);
-        let mut second_best = (' ', 0f64);
+    /// count: The number of candidates to keep.
+    #[tracing::instrument(skip_all)]
+    pub fn candidates(&self, image: &RgbImage, count: usize) -> Vec<(char, f64)> {
+        let mut best: Vec<(char, f64)> = Vec::with_capacity(count + 1);
 
         let image = DynamicImage::ImageRgb8(image.clone()).to_rgba8();
-        for (letter, letter_image) in &self.letters {
-            if let Ok(result) = image_compare::rgba_blended_hybrid_compare(
-                (&image).into(), letter_image.into(), WHITE
-            ) {
-                if result.score > best.1 {
-                    second_best = best;
-                    best = (*letter, result.score);
-                } else if result.score > second_best.1 {
-                    second_best = (*letter, result.score);
+        for letter in &self.order {
+            if let Some(score) = self.similarity(&image, &self.letters[letter]) {
+                // Keep the candidates sorted by score.
+                let index = best.partition_point(|(_, other)| *other >= score);
+                if index < count {
+                    best.insert(index, (*letter, score));
+                    best.truncate(count);
+                }
+
+                // Stop early on a near-perfect match.
+                if best.first().is_some_and(|(_, best)| *best > self.threshold) {
+                    break;
                 }
             }
         }
 
-        best.0
+        best
+    }
+
+    /// Scores how similar a tile is to a letter, using the configured metric.
+    /// Returns None if the images can't be compared.
+    /// image: The tile image.
+    /// letter: The letter image.
+    fn similarity(&self, image: &RgbaImage, letter: &RgbaImage) -> Option<f64> {
+        match self.metric {
+            CompareMetric::Hybrid => image_compare::rgba_blended_hybrid_compare(
+                image.into(), letter.into(), WHITE
+            ).ok().map(|result| result.score),
+            CompareMetric::Rms => image_compare::rgb_similarity_structure(
+                &Algorithm::RootMeanSquared, &blend(image), &blend(letter)
+            ).ok().map(|result| result.score),
+            CompareMetric::Ssim => image_compare::rgb_similarity_structure(
+                &Algorithm::MSSIMSimple, &blend(image), &blend(letter)
+            ).ok().map(|result| result.score),
+            CompareMetric::Histogram => image_compare::gray_similarity_histogram(
+                Metric::Correlation, &gray(image), &gray(letter)
+            ).ok()
+        }
     }
 }
 
+/// Blends an image onto a white background.
+/// image: The image to blend.
+fn blend(image: &RgbaImage) -> RgbImage {
+    RgbImage::from_fn(image.width(), image.height(), |x, y| {
+        let [r, g, b, a] = image.get_pixel(x, y).0;
+        let mix = |channel: u8| {
+            ((channel as u32 * a as u32 + 255 * (255 - a as u32)) / 255) as u8
+        };
+
+        Rgb([mix(r), mix(g), mix(b)])
+    })
+}
+
+/// Converts an image to grayscale, on a white background.
+/// image: The image to convert.
+fn gray(image: &RgbaImage) -> GrayImage {
+    DynamicImage::ImageRgb8(blend(image)).to_luma8()
+}
+
 impl Default for Letters {
     fn default() -> Self {
         Self {
-            letters: HashMap::new()
+            letters: HashMap::new(),
+            order: Vec::new(),
+            threshold: 1.0,
+            qu: false,
+            metric: CompareMetric::Hybrid
         }
     }
 }
@@ -0,0 +1,93 @@
+use std::fs;
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+use serde::{Deserialize, Serialize};
+
+/// The number of seconds in an hour.
+pub const HOUR: u64 = 60 * 60;
+/// The number of seconds in a day.
+pub const DAY: u64 = 24 * HOUR;
+
+/// The rounds played recently, persisted between runs.
+#[derive(Default, Debug, Serialize, Deserialize)]
+pub struct PlayHistory {
+    /// The UNIX timestamps (in seconds) of each round played.
+    rounds: Vec<u64>
+}
+
+impl PlayHistory {
+    /// Loads the play history from a file.
+    /// A missing or invalid file is treated as an empty history.
+    /// path: The path to the history file.
+    pub fn load<P: AsRef<Path>>(path: P) -> Self {
+        fs::read_to_string(path)
+            .ok()
+            .and_then(|contents| serde_json::from_str(&contents).ok())
+            .unwrap_or_default()
+    }
+
+    /// Saves the play history to a file.
+    /// path: The path to the history file.
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> anyhow::Result<()> {
+        fs::write(path, serde_json::to_string(self)?)?;
+        Ok(())
+    }
+
+    /// Counts the rounds played within a window of time.
+    /// now: The current UNIX timestamp.
+    /// window: The length of the window, in seconds.
+    pub fn count_within(&self, now: u64, window: u64) -> usize {
+        self.rounds.iter()
+            .filter(|round| now.saturating_sub(**round) < window)
+            .count()
+    }
+
+    /// Checks if another round would exceed the limits.
+    /// A limit of 0 means there is no limit.
+    /// now: The current UNIX timestamp.
+    /// per_hour: The maximum rounds per hour.
+    /// per_day: The maximum rounds per day.
+    pub fn is_limited(&self, now: u64, per_hour: u32, per_day: u32) -> bool {
+        (per_hour > 0 && self.count_within(now, HOUR) >= per_hour as usize) ||
+            (per_day > 0 && self.count_within(now, DAY) >= per_day as usize)
+    }
+
+    /// Records a round being played.
+    /// Rounds older than a day are forgotten.
+    /// now: The current UNIX timestamp.
+    pub fn record(&mut self, now: u64) {
+        self.rounds.retain(|round| now.saturating_sub(*round) < DAY);
+        self.rounds.push(now);
+    }
+}
+
+/// Returns the current UNIX timestamp, in seconds.
+pub fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |time| time.as_secs())
+}
+
+#[cfg(test)]
+mod test {
+    use crate::limits::{PlayHistory, DAY, HOUR};
+
+    #[test]
+    pub fn limits_rounds() {
+        let mut history = PlayHistory::default();
+        let now = 10 * DAY;
+
+        // Rounds are pruned against the time of the round being recorded,
+        // so the first is forgotten once a later round is more than a day after it.
+        history.record(now - DAY - 3 * HOUR);
+        history.record(now - 2 * HOUR);
+        history.record(now - 10);
+
+        assert_eq!(history.rounds.len(), 2);
+        assert_eq!(history.count_within(now, HOUR), 1);
+        assert!(history.is_limited(now, 1, 0));
+        assert!(history.is_limited(now, 0, 2));
+        assert!(!history.is_limited(now, 2, 3));
+        assert!(!history.is_limited(now, 0, 0));
+    }
+}
@@ -0,0 +1,69 @@
+use std::fs::{self, File, OpenOptions};
+use std::io::Write;
+use anyhow::anyhow;
+use fs2::FileExt;
+use crate::config::Config;
+use crate::hash;
+use crate::mouse::MouseBackendKind;
+
+/// The locks held while the bot controls a device's mouse.
+/// The locks are released when this is dropped, or the process exits.
+pub struct InstanceLock {
+    // The locked files. Closing them releases the locks.
+    _files: Vec<File>
+}
+
+impl InstanceLock {
+    /// Acquires a lock for the configured device, and one for the mouse server if it's used.
+    /// Fails if another instance is already using either.
+    /// config: The application configuration.
+    pub fn acquire(config: &Config) -> anyhow::Result<Self> {
+        let mut files = vec![lock(&format!("device {}", config.device_name))?];
+
+        // Only the server backend sends instructions to the configured endpoint.
+        if MouseBackendKind::parse(&config.mouse_backend) == MouseBackendKind::Server {
+            let endpoint = if config.server_url.is_empty() {
+                format!("{}:{}", config.server_address, config.server_port)
+            } else {
+                config.server_url.clone()
+            };
+            files.push(lock(&format!("mouse server {}", endpoint))?);
+        }
+
+        Ok(InstanceLock { _files: files })
+    }
+}
+
+/// Locks the file for a key, writing this process's ID into it.
+/// Fails if another instance already holds the lock.
+/// key: What is being locked, as shown to the user.
+fn lock(key: &str) -> anyhow::Result<File> {
+    let path = std::env::temp_dir().join(format!("word-terminator-{}.lock", hash(key)));
+
+    let mut file = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create(true)
+        .truncate(false)
+        .open(&path)?;
+
+    if file.try_lock_exclusive().is_err() {
+        // The owner writes its process ID into the lock file.
+        let owner = fs::read_to_string(&path).unwrap_or_default();
+        return Err(anyhow!(
+            "Another instance (process {}) is already controlling the {}.",
+            owner.trim(), key
+        ));
+    }
+
+    file.set_len(0)?;
+    write!(file, "{}", std::process::id())?;
+
+    Ok(file)
+}
+
+/// Hashes a lock key into a file-safe name. (64-bit FNV-1a, as hex)
+/// key: The key to hash.
+fn hash(key: &str) -> String {
+    hash::fnv_hex(key.as_bytes())
+}
@@ -1,93 +1,327 @@
 use std::path::Path;
 use std::sync::{Arc, RwLock};
 use lazy_static::lazy_static;
-use log::info;
+use log::{info, warn};
+use crate::capture::CaptureBackend;
+use crate::compiled::CompiledDictionary;
+use crate::config::Config;
+use crate::dictionary::{Dictionary, DictionaryBackend};
+use crate::difficulty::Difficulty;
 use crate::game::Game;
-use crate::letters::Letters;
+use crate::letters::{CompareMetric, Letters};
+use crate::switch::DictionarySwitch;
 use crate::trie::TrieNode;
 
 mod solver;
 mod screen;
 mod config;
 mod board;
+mod calibrate;
+mod commands;
+mod compiled;
+mod capture;
 mod trie;
+mod arena;
+mod dawg;
+mod dictionary;
+mod difficulty;
+mod dryrun;
 mod letters;
 mod game;
 mod mouse;
+mod limits;
+mod lock;
+mod mock;
+mod accuracy;
+mod adb;
+mod artifacts;
+mod audit;
+mod blocker;
+mod editor;
+mod events;
+mod export;
+mod hash;
+mod paths;
+mod photo;
+mod planner;
+mod preprocess;
+mod recording;
+mod sendinput;
+mod sound;
+mod switch;
+mod template;
+mod update;
 
 lazy_static! {
     pub static ref LETTERS: RwLock<Arc<Letters>> = RwLock::new(Arc::new(Letters::default()));
-    pub static ref DICTIONARY: RwLock<Arc<TrieNode>> = RwLock::new(Arc::new(TrieNode::new()));
+    pub static ref DICTIONARY: RwLock<Arc<dyn Dictionary>> = RwLock::new(Arc::new(TrieNode::new()));
 }
 
-#[tokio::main]
-async fn main() -> anyhow::Result<()> {
+fn main() -> anyhow::Result<()> {
     // Configure the environment for logging.
     if std::env::var("RUST_LOG").is_err() {
         std::env::set_var("RUST_LOG", "info");
     }
     // Initialize the logger.
-    pretty_env_logger::init();
+    artifacts::init_logger();
+
+    // Record the tracing spans while the program runs.
+    #[cfg(feature = "profiling")]
+    let _profiler = start_profiling();
 
     // Initialize the configuration.
     let config = config::init_config()?;
+
+    // Start the async runtime with the configured threads.
+    build_runtime(&config)?.block_on(run(config))
+}
+
+/// Builds the async runtime, sized by the configuration.
+/// config: The application configuration.
+fn build_runtime(config: &Config) -> std::io::Result<tokio::runtime::Runtime> {
+    let mut builder = tokio::runtime::Builder::new_multi_thread();
+    builder.enable_all();
+
+    if config.worker_threads > 0 {
+        builder.worker_threads(config.worker_threads);
+    }
+    if config.blocking_threads > 0 {
+        builder.max_blocking_threads(config.blocking_threads);
+    }
+
+    builder.build()
+}
+
+/// Runs a command, or plays the game if none was given.
+/// config: The application configuration.
+async fn run(mut config: Config) -> anyhow::Result<()> {
+    let mut args: Vec<String> = std::env::args().skip(1).collect();
+
+    // Apply the difficulty preset, preferring the command line's.
+    let difficulty = difficulty::take_option(&mut args).unwrap_or_else(|| config.difficulty.clone());
+    Difficulty::parse(&difficulty).apply(&mut config);
+
+    // Only record the mouse's instructions on a dry run.
+    if take_flag(&mut args, "--dry-run") {
+        config.mouse_backend = "dryrun".to_string();
+    }
+
+    // Run a command instead of the game if one was given.
+    if !args.is_empty() {
+        return commands::run(&config, &args).await;
+    }
+
+    // Let the user know if there is a newer release.
+    if config.check_updates {
+        update::notify(&config.update_channel);
+    }
+
+    // Make sure no other instance is controlling the mouse.
+    let _lock = lock::InstanceLock::acquire(&config)?;
     
-    unsafe {
-        // Fetch the window handle and set the size.
-        let handle = screen::get_window(&config.device_name);
-        screen::set_size(handle,
-                         config.screen_width, config.screen_height,
-                         (config.window_x, config.window_y));
-    
-        info!("Set {:?} to {}x{}.", handle, config.screen_width, config.screen_height);
+    // Only a mirrored window needs to be sized, unless it's left where the user put it.
+    if CaptureBackend::parse(&config.capture_backend) == CaptureBackend::Window {
+        if config.manage_window {
+            unsafe {
+                // Fetch the window handle and set the size.
+                let handle = screen::get_window(&config.device_name);
+                screen::set_size(handle,
+                                 config.screen_width, config.screen_height,
+                                 (config.window_x, config.window_y),
+                                 config.client_area);
+
+                info!("Set {:?} to {}x{}.", handle, config.screen_width, config.screen_height);
+            }
+        } else {
+            check_window_aspect(&config);
+        }
     }
     
+    // Let the user know if the mouse should be calibrated again.
+    calibrate::check_calibration(&config);
+
     // Read the dictionary.
-    load_dictionary(&config.dictionary);
+    load_dictionary(&config.dictionary, config.strip_diacritics,
+                    DictionaryBackend::parse(&config.dictionary_backend));
     // Load the letters library.
-    load_letters(&config.font);
+    load_letters(&config.font, config.letter_threshold, CompareMetric::parse(&config.letter_metric));
     
     // Create a new game instance.
     let game = Game::new(&config).await?;
-    game.start_game().await?;
-    
+    let events = (!config.events_file.is_empty())
+        .then(|| events::write_events(game.subscribe(), config.events_file.clone()));
+    let mut switch = DictionarySwitch::new(&config);
+    loop {
+        // Swap the dictionary if one was requested since the last round.
+        switch.poll().await;
+
+        if let Err(error) = game.start_game().await {
+            sound::play_and_wait(&config.sound_error);
+
+            // Let the events be written before exiting.
+            drop(game);
+            if let Some(events) = events {
+                let _ = events.await;
+            }
+            return Err(error);
+        }
+
+        // Keep playing once the opponent has taken their turn.
+        if !config.wait_for_turn {
+            break;
+        }
+        game.wait_for_turn().await?;
+    }
+
+    // Let the events be written before exiting.
+    drop(game);
+    if let Some(events) = events {
+        let _ = events.await;
+    }
+
     Ok(())
 }
 
-/// Loads a dictionary file.
-/// path: The path to the dictionary file.
-pub fn load_dictionary(path: &String) {
-    let mut dictionary = TrieNode::new();
+/// Removes a flag from the command line arguments.
+/// Returns true if the flag was given.
+/// args: The command line arguments, excluding the program name.
+/// flag: The flag to remove. (e.g. "--dry-run")
+fn take_flag(args: &mut Vec<String>, flag: &str) -> bool {
+    let length = args.len();
+    args.retain(|arg| arg != flag);
 
-    // Check if the file exists.
-    let path = Path::new(&path);
-    if !path.exists() {
+    args.len() != length
+}
+
+/// Warns if the unmanaged window's shape doesn't match the layout.
+/// Frames are scaled to the layout, so a different shape stretches the board.
+/// config: The application configuration.
+fn check_window_aspect(config: &Config) {
+    let size = unsafe {
+        let handle = screen::get_window(&config.device_name);
+        screen::window_size(handle, config.client_area)
+    };
+    let Some((width, height)) = size else {
+        warn!("Couldn't read the window's size.");
         return;
-    }
+    };
 
-    // Read the dictionary file.
-    let contents = std::fs::read_to_string(&path)
-        .expect("Couldn't read the dictionary file.");
+    info!("Leaving the window at {}x{}, scaling frames to {}x{}.",
+        width, height, config.screen_width, config.screen_height);
 
-    // Split the contents by newlines.
-    for word in contents.lines() {
-        dictionary.insert(word.to_lowercase().to_string());
+    let expected = (config.screen_width as u32, config.screen_height as u32);
+    if width <= 0 || height <= 0 || !capture::aspect_matches((width as u32, height as u32), expected) {
+        warn!("The window's shape is outside of the supported bounds; resize it to about {}x{} or enable manage_window.",
+            config.screen_width, config.screen_height);
     }
+}
+
+/// Starts writing tracing spans to a Chrome trace in the working directory.
+/// The trace is written until the returned guard is dropped.
+#[cfg(feature = "profiling")]
+fn start_profiling() -> tracing_chrome::FlushGuard {
+    use tracing_subscriber::prelude::*;
+
+    let (layer, guard) = tracing_chrome::ChromeLayerBuilder::new()
+        .include_args(true)
+        .build();
+    tracing_subscriber::registry().with(layer).init();
 
-    info!("Loaded the dictionary with {} root words.", dictionary.len());
+    info!("Recording a profile of this run.");
+    guard
+}
+
+/// Loads a dictionary file into the global dictionary.
+/// Nothing is loaded if the file doesn't exist.
+/// path: The path to the dictionary file.
+/// strip_diacritics: Should diacritics be removed from words?
+/// backend: The data structure to store the dictionary in.
+pub fn load_dictionary(path: &String, strip_diacritics: bool, backend: DictionaryBackend) {
+    let Some(dictionary) = build_dictionary(path, strip_diacritics, backend) else {
+        return;
+    };
 
     // Lock and write to the dictionary global.
     let mut lock = DICTIONARY.write().unwrap();
-    *lock = Arc::new(dictionary);
+    *lock = dictionary;
 
     // Unlock the dictionary.
     drop(lock);
 }
 
-/// Loads the letters map.
+/// Reads a dictionary file, then builds it without replacing the global dictionary.
+/// Returns None if the file doesn't exist.
+/// path: The path to the dictionary file.
+/// strip_diacritics: Should diacritics be removed from words?
+/// backend: The data structure to store the dictionary in.
+pub fn build_dictionary(path: &String, strip_diacritics: bool, backend: DictionaryBackend) -> Option<Arc<dyn Dictionary>> {
+    // Check if the file exists.
+    if !Path::new(path).exists() {
+        return None;
+    }
+
+    // Skip parsing and building the word list if it has been compiled.
+    let compiled = compiled::compiled_path(path);
+    if compiled.exists() {
+        match CompiledDictionary::load(&compiled) {
+            Ok(dictionary) if dictionary.is_current(path, strip_diacritics, backend) => {
+                info!("Loaded the compiled dictionary.");
+                return Some(dictionary.into_dictionary());
+            },
+            Ok(_) => warn!("The compiled dictionary is out of date, reading the word list instead."),
+            Err(error) => warn!("Failed to load the compiled dictionary: {}", error)
+        }
+    }
+
+    let words = read_dictionary(path, strip_diacritics)?;
+    info!("Loaded the dictionary with {} words.", words.len());
+
+    Some(backend.build(&words))
+}
+
+/// Reads the words in a dictionary file.
+/// Returns the sorted, unique words with their frequencies,
+/// or None if the file doesn't exist.
+/// path: The path to the dictionary file.
+/// strip_diacritics: Should diacritics be removed from words?
+pub fn read_dictionary(path: &String, strip_diacritics: bool) -> Option<Vec<(String, u32)>> {
+    // Check if the file exists.
+    let path = Path::new(&path);
+    if !path.exists() {
+        return None;
+    }
+
+    // Read the dictionary file.
+    let contents = std::fs::read_to_string(path)
+        .expect("Couldn't read the dictionary file.");
+
+    // Split the contents by newlines.
+    // Each line is a word, optionally followed by its frequency.
+    let mut words: Vec<(String, u32)> = contents.lines()
+        .filter_map(|line| {
+            let mut columns = line.split_whitespace();
+            let word = trie::normalize(columns.next()?, strip_diacritics);
+            let frequency = columns.next()
+                .and_then(|frequency| frequency.parse().ok())
+                .unwrap_or(0);
+
+            Some((word, frequency))
+        })
+        .collect();
+
+    // The bulk builders expect sorted, unique words.
+    words.sort_unstable_by(|a, b| a.0.cmp(&b.0).then(b.1.cmp(&a.1)));
+    words.dedup_by(|a, b| a.0 == b.0);
+
+    Some(words)
+}
+
+/// Loads the letters map into the global letters library.
 /// path: The path to the letters directory.
-pub fn load_letters(path: &String) {
-    let letters = Letters::new(path);
+/// threshold: A score which is accepted without checking other letters.
+/// metric: The similarity metric to compare with.
+pub fn load_letters(path: &String, threshold: f64, metric: CompareMetric) {
+    let letters = Letters::new(path, threshold, metric);
 
     info!("Loaded the letters library.");
 
@@ -103,15 +337,14 @@ pub fn load_letters(path: &String) {
 mod test {
     use std::collections::HashMap;
     use image::{DynamicImage, RgbImage};
-    use crate::{load_dictionary, load_letters, LETTERS};
-    use crate::board::crop_image;
+    use crate::letters::{CompareMetric, Letters};
+    use crate::board::{crop_image, Layout, TileGrid};
 
     #[test]
     pub fn solve_boards() {
-        // Load the dictionary.
-        load_dictionary(&"words.txt".to_string());
         // Read the font images.
-        load_letters(&"images".to_string());
+        // This doesn't use the global library, so tests can run in parallel.
+        let letters = Letters::new(&"images".to_string(), 1.0, CompareMetric::Hybrid);
 
         // Load each board.
         for i in 1..10 {
@@ -120,9 +353,6 @@ mod test {
                 .map(|image| image.to_rgb8())
                 .unwrap();
 
-            // Lock the letters library.
-            let letters = LETTERS.read().unwrap();
-
             // Split the image into a 4x4 grid.
             let mut board = String::new();
 
@@ -131,13 +361,13 @@ mod test {
             let image = DynamicImage::ImageRgb8(board_image.clone());
             for column in 0..4 {
                 for row in 0..4 {
-                    let image = crop_image(image.clone(), row, column);
+                    let image = crop_image(image.clone(), &TileGrid::from_layout(Layout::default()), row, column);
                     // image.save(format!("test/{}_{}_{}.png", i, row, column)).unwrap();
 
                     print!("({}, {}) - ", row, column);
 
                     // Determine which letter matches the image.
-                    let letter = letters.compare(&image);
+                    let (letter, _) = letters.compare(&image);
                     board.push(letter);
 
                     map.insert((row, column), image);
@@ -158,13 +388,11 @@ mod test {
             let mut correct_map: HashMap<(u32, u32), char> = HashMap::new();
             let mut found_map: HashMap<(u32, u32), char> = HashMap::new();
 
-            let mut row = 0;
-            for line in correct.split('\n') {
+            for (row, line) in correct.split('\n').enumerate() {
                 for column in 0..4u32 {
                     let c = line.chars().nth(column as usize).unwrap();
-                    correct_map.insert((row, column), c);
+                    correct_map.insert((row as u32, column), c);
                 }
-                row += 1;
             }
 
             for i in 0..16 {
@@ -182,9 +410,9 @@ mod test {
 
                     // Print the image similarity scores.
                     let found_image = map.get(&(row, column)).unwrap();
-                    let correct_image = letters.letters.get(&correct).unwrap();
+                    let _correct_image = letters.letters.get(correct).unwrap();
 
-                    let found_image = DynamicImage::ImageRgb8(found_image.clone()).to_luma8();
+                    let _found_image = DynamicImage::ImageRgb8(found_image.clone()).to_luma8();
 
                     // let result = image_compare::gray_similarity_histogram(Metric::Hellinger, &found_image, correct_image).unwrap();
                     // println!("({}, {}) - {} vs {}: {}", row, column, found, correct, result);
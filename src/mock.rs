@@ -0,0 +1,384 @@
+use image::{Rgb, RgbImage};
+use log::{info, warn};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use crate::config::Config;
+use crate::mouse::{Capabilities, Opcode, Status, ACK_MAGIC, AUTH_MAGIC};
+
+/// The size of an instruction's header. (opcode, x, y, group size)
+pub const HEADER_SIZE: usize = 10;
+
+/// The pixels drawn per mouse unit when rendering a path.
+pub const RENDER_SCALE: u32 = 2;
+/// The blank pixels around a rendered path.
+pub const RENDER_MARGIN: u32 = 16;
+
+/// A line the cursor moved along. (start, end, was the button held?)
+pub type Segment = ((i32, i32), (i32, i32), bool);
+
+/// A mouse which moves on paper, recording where it has been.
+/// Instructions are handled the same way as the mouse server's firmware.
+#[derive(Default, Debug)]
+pub struct SimulatedMouse {
+    /// The cursor's position since the last normalize.
+    pub cursor: (i32, i32),
+    /// Is the left button held?
+    pub pressed: bool,
+    /// The lines the cursor moved along.
+    pub segments: Vec<Segment>
+}
+
+impl SimulatedMouse {
+    /// Performs an instruction.
+    /// Returns the status reported back to the client.
+    /// opcode: The instruction's opcode byte.
+    /// position: The instruction's (x, y) position.
+    /// group: The points of a group move.
+    pub fn apply(&mut self, opcode: u8, position: (i32, i32), group: &[(i32, i32)]) -> Status {
+        let Some(opcode) = Opcode::from_byte(opcode) else {
+            return Status::InvalidOpcode;
+        };
+
+        match opcode {
+            Opcode::Hello | Opcode::ScrollUp | Opcode::ScrollDown | Opcode::RightClick => {},
+            Opcode::LeftDown => self.pressed = true,
+            Opcode::LeftUp => self.pressed = false,
+            Opcode::Move => self.move_by(position),
+            Opcode::Normalize => self.cursor = (0, 0),
+            Opcode::NormalMove => {
+                self.cursor = (0, 0);
+                self.move_by(position);
+            },
+            Opcode::MoveGroup => {
+                // The button is pressed once the first point is reached.
+                for (i, point) in group.iter().enumerate() {
+                    self.move_by(*point);
+                    if i == 0 {
+                        self.pressed = true;
+                    }
+                }
+                self.pressed = false;
+            }
+        }
+
+        Status::Ok
+    }
+
+    /// Returns the positions passed through while the button was held.
+    #[cfg(test)]
+    /// Each stroke starts where the button was pressed.
+    pub fn strokes(&self) -> Vec<Vec<(i32, i32)>> {
+        let mut strokes: Vec<Vec<(i32, i32)>> = Vec::new();
+        let mut holding = false;
+
+        for (start, end, pressed) in &self.segments {
+            if !pressed {
+                holding = false;
+                continue;
+            }
+
+            if !holding {
+                strokes.push(vec![*start]);
+                holding = true;
+            }
+            strokes.last_mut().unwrap().push(*end);
+        }
+
+        strokes
+    }
+
+    /// Moves the cursor relative to its position.
+    /// delta: The change in position.
+    fn move_by(&mut self, (dx, dy): (i32, i32)) {
+        let start = self.cursor;
+        self.cursor = (start.0 + dx, start.1 + dy);
+        self.segments.push((start, self.cursor, self.pressed));
+    }
+
+    /// Draws the cursor's path.
+    /// Moves with the button held are red, and other moves are gray.
+    pub fn render(&self) -> RgbImage {
+        let points = self.segments.iter().flat_map(|(start, end, _)| [*start, *end]);
+        let (min, max) = points.fold(((0, 0), (0, 0)), |(min, max), (x, y)| {
+            ((x.min(min.0), y.min(min.1)), (x.max(max.0), y.max(max.1)))
+        });
+
+        let width = (max.0 - min.0) as u32 * RENDER_SCALE + RENDER_MARGIN * 2;
+        let height = (max.1 - min.1) as u32 * RENDER_SCALE + RENDER_MARGIN * 2;
+        let mut image = RgbImage::from_pixel(width, height, Rgb([255, 255, 255]));
+
+        let pixel = |(x, y): (i32, i32)| (
+            ((x - min.0) as u32 * RENDER_SCALE + RENDER_MARGIN) as f64,
+            ((y - min.1) as u32 * RENDER_SCALE + RENDER_MARGIN) as f64
+        );
+
+        // Draw the moves without the button first, so held moves are on top.
+        for held in [false, true] {
+            let color = if held { Rgb([220, 40, 40]) } else { Rgb([180, 180, 180]) };
+
+            for (start, end, _) in self.segments.iter().filter(|(_, _, pressed)| *pressed == held) {
+                let (start, end) = (pixel(*start), pixel(*end));
+                let steps = (end.0 - start.0).abs().max((end.1 - start.1).abs()).max(1.0) as u32;
+
+                for step in 0..=steps {
+                    let t = step as f64 / steps as f64;
+                    let x = start.0 + (end.0 - start.0) * t;
+                    let y = start.1 + (end.1 - start.1) * t;
+                    image.put_pixel(x.round() as u32, y.round() as u32, color);
+                }
+            }
+        }
+
+        image
+    }
+}
+
+/// A decoded instruction. (opcode, position, group, sequence number)
+pub type Decoded = (u8, (i32, i32), Vec<(i32, i32)>, u32);
+
+/// Decodes an instruction sent by the client.
+/// Returns None if the instruction is too short.
+/// bytes: The serialized instruction, followed by its sequence number.
+pub fn decode(bytes: &[u8]) -> Option<Decoded> {
+    let int = |offset: usize| Some(i32::from_le_bytes(bytes.get(offset..offset + 4)?.try_into().ok()?));
+
+    let opcode = *bytes.first()?;
+    let position = (int(1)?, int(5)?);
+    let count = *bytes.get(HEADER_SIZE - 1)? as usize;
+
+    let group = (0..count)
+        .map(|i| Some((int(HEADER_SIZE + i * 8)?, int(HEADER_SIZE + i * 8 + 4)?)))
+        .collect::<Option<Vec<_>>>()?;
+    let sequence = int(HEADER_SIZE + count * 8)? as u32;
+
+    Some((opcode, position, group, sequence))
+}
+
+/// Runs a mock mouse server on the configured address.
+/// Each connection's cursor path is saved as an image when it closes.
+/// config: The application configuration.
+/// output: The path to save the cursor path to.
+pub async fn serve(config: &Config, output: &str) -> anyhow::Result<()> {
+    let listener = TcpListener::bind((config.server_address.as_str(), config.server_port)).await?;
+    info!("The mock mouse server is listening on {}.", listener.local_addr()?);
+
+    loop {
+        let (mut stream, address) = listener.accept().await?;
+        info!("A client connected from {}.", address);
+
+        let mut mouse = SimulatedMouse::default();
+        if let Err(error) = handle_client(&mut stream, &mut mouse, &config.server_token).await {
+            warn!("The connection closed: {}", error);
+        }
+
+        mouse.render().save(output)?;
+        info!("Saved the cursor path to {}.", output);
+    }
+}
+
+/// Handles instructions from a client until it disconnects.
+/// stream: The connection to the client.
+/// mouse: The simulated mouse to move.
+/// token: The token the client must send first. (empty to accept any client)
+async fn handle_client(stream: &mut TcpStream, mouse: &mut SimulatedMouse, token: &str) -> anyhow::Result<()> {
+    if !token.is_empty() {
+        let mut header = [0u8; 5];
+        stream.read_exact(&mut header).await?;
+        let mut received = vec![0u8; header[4] as usize];
+        stream.read_exact(&mut received).await?;
+
+        let status = if &header[..4] == AUTH_MAGIC && received == token.as_bytes() {
+            Status::Ok
+        } else {
+            Status::Unauthorized
+        };
+        stream.write_all(&ack(status, 0, mouse.cursor)).await?;
+
+        if status != Status::Ok {
+            return Err(anyhow::anyhow!("The client sent the wrong token."));
+        }
+    }
+
+    loop {
+        // Read the header, the group, then the sequence number.
+        let mut bytes = vec![0u8; HEADER_SIZE];
+        stream.read_exact(&mut bytes).await?;
+
+        let length = bytes.len() + bytes[HEADER_SIZE - 1] as usize * 8 + 4;
+        bytes.resize(length, 0);
+        stream.read_exact(&mut bytes[HEADER_SIZE..]).await?;
+
+        let (opcode, (x, y), group, sequence) = decode(&bytes)
+            .ok_or_else(|| anyhow::anyhow!("Received a malformed instruction."))?;
+
+        // Perform the instruction.
+        let status = mouse.apply(opcode, (x, y), &group);
+        info!("#{} {:?} ({}, {}) {:?} -> {:?}, cursor at {:?}",
+            sequence, Opcode::from_byte(opcode), x, y, group, status, mouse.cursor);
+
+        // Acknowledge the instruction.
+        // The handshake is answered with the server's capabilities instead of the cursor.
+        let position = if opcode == Opcode::Hello.as_byte() { Capabilities::all().as_position() } else { mouse.cursor };
+        stream.write_all(&ack(status, sequence, position)).await?;
+    }
+}
+
+/// Serializes an acknowledgement.
+/// status: The result of the instruction.
+/// sequence: The instruction's sequence number.
+/// cursor: The cursor's position, or the server's capabilities for the handshake.
+fn ack(status: Status, sequence: u32, cursor: (i32, i32)) -> Vec<u8> {
+    let mut ack = Vec::with_capacity(16);
+    ack.extend_from_slice(ACK_MAGIC);
+    ack.push(status.as_byte());
+    ack.extend_from_slice(&sequence.to_le_bytes());
+    ack.extend_from_slice(&cursor.0.to_le_bytes());
+    ack.extend_from_slice(&cursor.1.to_le_bytes());
+    ack
+}
+
+#[cfg(test)]
+mod test {
+    use tokio::io::AsyncReadExt;
+    use tokio::net::TcpListener;
+    use crate::board::GridPos;
+    use crate::mock::{decode, handle_client, SimulatedMouse, HEADER_SIZE, RENDER_MARGIN, RENDER_SCALE};
+    use crate::mouse::{Instruction, Mouse, Reconnected, ServerSecurity, Status, SwipeSpeed, TcpBackend};
+    use crate::planner::{plan_word, CoordinateMapper, Trace, TimingProfile};
+    use crate::solver::{find_path, Word};
+
+    /// Replays planned traces the same way a round sends them.
+    /// Returns the simulated mouse after every trace.
+    /// traces: The planned traces, in order.
+    /// origin: The mouse coordinates of the first tile.
+    fn replay(traces: &[Trace], origin: (i32, i32)) -> SimulatedMouse {
+        let mut mouse = SimulatedMouse::default();
+        let instructions = traces.iter().flat_map(|trace| [
+            Instruction::absolute(origin.0, origin.1),
+            Instruction::group(&trace.points, SwipeSpeed::default())
+        ]);
+
+        for (sequence, instruction) in instructions.enumerate() {
+            let mut bytes = instruction.serialize();
+            bytes.extend_from_slice(&(sequence as u32).to_le_bytes());
+
+            let (opcode, position, group, _) = decode(&bytes).unwrap();
+            mouse.apply(opcode, position, &group);
+        }
+
+        mouse
+    }
+
+    #[test]
+    pub fn simulates_instructions() {
+        let mut mouse = SimulatedMouse::default();
+
+        assert_eq!(mouse.apply(6, (35, 165), &[]), Status::Ok);
+        assert_eq!(mouse.apply(5, (0, 0), &[(0, 0), (30, 0), (0, 33)]), Status::Ok);
+        assert_eq!(mouse.apply(9, (0, 0), &[]), Status::InvalidOpcode);
+
+        assert_eq!(mouse.cursor, (65, 198));
+        assert!(!mouse.pressed);
+        assert_eq!(mouse.segments.iter().filter(|(_, _, pressed)| *pressed).count(), 2);
+
+        let image = mouse.render();
+        assert_eq!(image.dimensions(), (
+            65 * RENDER_SCALE + RENDER_MARGIN * 2,
+            198 * RENDER_SCALE + RENDER_MARGIN * 2
+        ));
+    }
+
+    #[test]
+    pub fn replays_rounds() {
+        let board = vec![
+            vec!["c".to_string(), "a".to_string(), "t".to_string(), "s".to_string()],
+            vec!["o".to_string(), "r".to_string(), "e".to_string(), "d".to_string()]
+        ];
+        let mapper = CoordinateMapper::default();
+        let timing = TimingProfile::default();
+
+        // Plan each word, the same way a round does.
+        let words = ["cat", "cats", "cored", "tea"];
+        let traces: Vec<_> = words.iter()
+            .map(|text| {
+                let mut word = Word::new();
+                for pos in find_path(&board, text).unwrap() {
+                    word.append(&board[pos.row][pos.col], pos);
+                }
+                plan_word(&word, &mapper, &timing)
+            })
+            .collect();
+
+        // Each stroke should pass through the centers of the word's tiles.
+        let tile = |row, col| mapper.position(GridPos::new(row, col));
+        let golden = vec![
+            vec![tile(0, 0), tile(0, 1), tile(0, 2)],
+            vec![tile(0, 0), tile(0, 1), tile(0, 2), tile(0, 3)],
+            vec![tile(0, 0), tile(1, 0), tile(1, 1), tile(1, 2), tile(1, 3)],
+            vec![tile(0, 2), tile(1, 2), tile(0, 1)]
+        ];
+
+        let mouse = replay(&traces, mapper.origin);
+        assert_eq!(mouse.strokes(), golden);
+        assert!(!mouse.pressed);
+    }
+
+    #[tokio::test]
+    pub async fn resumes_after_dropped_connections() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+
+        let server = tokio::spawn(async move {
+            // Drop the first connection partway through an instruction.
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let mut header = [0u8; HEADER_SIZE];
+            stream.read_exact(&mut header).await.unwrap();
+            drop(stream);
+
+            // Serve the reconnected client until it disconnects.
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let mut mouse = SimulatedMouse::default();
+            let _ = handle_client(&mut stream, &mut mouse, "").await;
+            mouse
+        });
+
+        let security = ServerSecurity { token: String::new(), tls: None };
+        let backend = TcpBackend::connect(address.ip().to_string(), address.port(), security).await.unwrap();
+        let mut mouse = Mouse::new(Box::new(backend));
+
+        // The lost instruction fails once the mouse has reconnected, so it can be sent again.
+        let error = mouse.send(Instruction::absolute(30, 40)).await.unwrap_err();
+        assert!(error.is::<Reconnected>());
+        mouse.send(Instruction::absolute(30, 40)).await.unwrap();
+        drop(mouse);
+
+        assert_eq!(server.await.unwrap().cursor, (30, 40));
+    }
+
+    #[tokio::test]
+    pub async fn checks_tokens() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+
+        let server = tokio::spawn(async move {
+            for _ in 0..2 {
+                let (mut stream, _) = listener.accept().await.unwrap();
+                let _ = handle_client(&mut stream, &mut SimulatedMouse::default(), "secret").await;
+            }
+        });
+
+        let security = |token: &str| ServerSecurity { token: token.to_string(), tls: None };
+        let host = address.ip().to_string();
+
+        // The right token is accepted, and instructions are performed after it.
+        let backend = TcpBackend::connect(&host, address.port(), security("secret")).await.unwrap();
+        let mut mouse = Mouse::new(Box::new(backend));
+        assert_eq!(mouse.send(Instruction::absolute(30, 40)).await.unwrap().position, Some((30, 40)));
+        drop(mouse);
+
+        // The wrong token is rejected during the handshake.
+        assert!(TcpBackend::connect(&host, address.port(), security("guess")).await.is_err());
+
+        server.await.unwrap();
+    }
+}
@@ -1,18 +1,82 @@
+use std::collections::VecDeque;
+use std::fmt::{Display, Formatter};
+use std::io::ErrorKind;
 use std::net::SocketAddr;
-use std::time::Duration;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use async_trait::async_trait;
+use futures_util::{SinkExt, StreamExt};
+use log::{debug, info, warn};
 use tokio::net::{TcpSocket, TcpStream};
+use tokio_tungstenite::tungstenite::Message;
+use tokio_tungstenite::{MaybeTlsStream, WebSocketStream};
 use anyhow::{anyhow, Result};
 use bytes::{BufMut, BytesMut};
-use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use rustls::pki_types::ServerName;
+use rustls::{ClientConfig, RootCertStore};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
 use tokio::time::sleep;
+use tokio_rustls::TlsConnector;
+use crate::adb::AdbBackend;
+use crate::config::Config;
+use crate::dryrun::DryRunBackend;
+use crate::sendinput::SendInputBackend;
 
+/// The prefix of a structured acknowledgement.
+/// Older servers reply with "OKAY" instead.
+pub const ACK_MAGIC: &[u8; 3] = b"ACK";
+/// The reply sent by servers without structured acknowledgements.
+pub const LEGACY_ACK: &[u8; 4] = b"OKAY";
+/// The start of the handshake which sends the server's token.
+/// It's followed by the token's length (u8), then the token.
+pub const AUTH_MAGIC: &[u8; 4] = b"AUTH";
+
+/// The version of the mouse protocol this client speaks.
+/// Servers without the handshake are treated as version 0.
+pub const PROTOCOL_VERSION: i32 = 1;
+
+/// The most points sent in one group move.
+/// Older servers read each instruction into a 512-byte buffer, which fits 62 points.
+pub const MAX_GROUP_POINTS: usize = 62;
+
+/// How long to wait on each point of an emulated group by default. (the server's default)
+pub const GROUP_DWELL: Duration = Duration::from_millis(60);
+/// How long to wait before pressing or releasing the button in an emulated group.
+pub const PRESS_TIME: Duration = Duration::from_millis(10);
+
+/// The times to try reconnecting after the connection to the backend drops.
+pub const RECONNECT_ATTEMPTS: u32 = 6;
+/// The wait before the first reconnect. Each later attempt waits twice as long.
+pub const RECONNECT_DELAY: Duration = Duration::from_millis(500);
+
+/// The round trips averaged into the mouse's latency.
+pub const LATENCY_SAMPLES: usize = 20;
+
+/// The error returned when an instruction was lost because the connection dropped.
+/// The mouse has reconnected and normalized, so the caller can redo its work.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct Reconnected;
+
+impl Display for Reconnected {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "The connection to the mouse dropped, and was restored.")
+    }
+}
+
+impl std::error::Error for Reconnected {}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
 pub enum Opcode {
     LeftDown,
     LeftUp,
     Move,
     Normalize,
     MoveGroup,
-    NormalMove
+    NormalMove,
+    Hello,
+    ScrollUp,
+    ScrollDown,
+    RightClick
 }
 
 impl Opcode {
@@ -24,15 +88,220 @@ impl Opcode {
             Opcode::Move => 3,
             Opcode::Normalize => 4,
             Opcode::MoveGroup => 5,
-            Opcode::NormalMove => 6
+            Opcode::NormalMove => 6,
+            Opcode::Hello => 7,
+            Opcode::ScrollUp => 8,
+            Opcode::ScrollDown => 9,
+            Opcode::RightClick => 10
+        }
+    }
+
+    /// Converts a byte into an opcode.
+    /// Returns None if the byte isn't a known opcode.
+    /// byte: The opcode's byte.
+    pub fn from_byte(byte: u8) -> Option<Self> {
+        match byte {
+            1 => Some(Opcode::LeftDown),
+            2 => Some(Opcode::LeftUp),
+            3 => Some(Opcode::Move),
+            4 => Some(Opcode::Normalize),
+            5 => Some(Opcode::MoveGroup),
+            6 => Some(Opcode::NormalMove),
+            7 => Some(Opcode::Hello),
+            8 => Some(Opcode::ScrollUp),
+            9 => Some(Opcode::ScrollDown),
+            10 => Some(Opcode::RightClick),
+            _ => None
+        }
+    }
+
+    /// Checks if the server acknowledges the opcode as soon as it's received.
+    /// Moves are acknowledged once they finish, so their round trips include the move.
+    pub fn is_immediate(&self) -> bool {
+        matches!(self, Opcode::LeftDown | Opcode::LeftUp | Opcode::Hello | Opcode::RightClick)
+    }
+}
+
+/// The rolling round-trip time of instructions to the mouse.
+#[derive(Clone, Debug, Default)]
+pub struct Latency {
+    /// The latest round trips, oldest first.
+    samples: VecDeque<Duration>
+}
+
+impl Latency {
+    /// Records a round trip, forgetting the oldest once there are enough.
+    /// rtt: The time between sending an instruction and receiving its acknowledgement.
+    pub fn record(&mut self, rtt: Duration) {
+        if self.samples.len() == LATENCY_SAMPLES {
+            self.samples.pop_front();
+        }
+        self.samples.push_back(rtt);
+    }
+
+    /// Returns the average of the latest round trips. (zero before any are recorded)
+    pub fn average(&self) -> Duration {
+        match self.samples.len() {
+            0 => Duration::ZERO,
+            count => self.samples.iter().sum::<Duration>() / count as u32
+        }
+    }
+}
+
+/// The result of an instruction, as reported by the server.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Status {
+    /// The instruction was performed.
+    Ok,
+    /// The opcode isn't supported by the server.
+    InvalidOpcode,
+    /// The instruction couldn't be parsed.
+    Malformed,
+    /// The instruction failed while being performed.
+    Failed,
+    /// The handshake's token was wrong.
+    Unauthorized,
+    /// The server sent a status this client doesn't know.
+    Unknown(u8)
+}
+
+impl Status {
+    /// Converts a status code from the server.
+    /// code: The status code.
+    pub fn from_byte(code: u8) -> Self {
+        match code {
+            0 => Status::Ok,
+            1 => Status::InvalidOpcode,
+            2 => Status::Malformed,
+            3 => Status::Failed,
+            4 => Status::Unauthorized,
+            code => Status::Unknown(code)
+        }
+    }
+
+    /// Converts the status into a status code.
+    pub fn as_byte(&self) -> u8 {
+        match self {
+            Status::Ok => 0,
+            Status::InvalidOpcode => 1,
+            Status::Malformed => 2,
+            Status::Failed => 3,
+            Status::Unauthorized => 4,
+            Status::Unknown(code) => *code
         }
     }
 }
 
+/// The server's reply to an instruction.
+/// Structured acknowledgements are "ACK", a status byte, then the echoed
+/// sequence number (u32) and the cursor's position (2x i32), in little-endian.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct Ack {
+    /// The result of the instruction.
+    pub status: Status,
+    /// The sequence number of the instruction, if the server echoes it.
+    pub sequence: Option<u32>,
+    /// The cursor's position relative to the last normalize, if reported.
+    pub position: Option<(i32, i32)>
+}
+
+impl Ack {
+    /// The acknowledgement assumed from servers which only reply "OKAY".
+    pub fn legacy() -> Self {
+        Ack { status: Status::Ok, sequence: None, position: None }
+    }
+
+    /// Parses the body of a structured acknowledgement.
+    /// status: The status byte following the magic.
+    /// body: The 12 bytes following the status.
+    pub fn parse(status: u8, body: &[u8; 12]) -> Self {
+        let field = |offset: usize| [body[offset], body[offset + 1], body[offset + 2], body[offset + 3]];
+
+        Ack {
+            status: Status::from_byte(status),
+            sequence: Some(u32::from_le_bytes(field(0))),
+            position: Some((i32::from_le_bytes(field(4)), i32::from_le_bytes(field(8))))
+        }
+    }
+
+    /// Parses a whole reply, as received in a single message.
+    /// Returns None if the reply isn't an acknowledgement.
+    /// reply: The reply's bytes.
+    pub fn from_reply(reply: &[u8]) -> Option<Self> {
+        if reply == LEGACY_ACK {
+            return Some(Ack::legacy());
+        }
+
+        let body: &[u8; 12] = reply.get(4..16)?.try_into().ok()?;
+        (reply.len() == 16 && &reply[..3] == ACK_MAGIC).then(|| Ack::parse(reply[3], body))
+    }
+}
+
+/// What a backend supports, as exchanged in the handshake.
+/// The handshake is a `Hello` instruction holding the client's (version, opcodes),
+/// which the server acknowledges with its own in place of the cursor's position.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct Capabilities {
+    /// The protocol version spoken. (0 for servers without the handshake)
+    pub version: i32,
+    /// A bit for each supported opcode, where bit n is set for opcode n.
+    pub opcodes: u32
+}
+
+impl Capabilities {
+    /// The capabilities assumed of servers without the handshake.
+    pub fn legacy() -> Self {
+        let legacy = [Opcode::LeftDown, Opcode::LeftUp, Opcode::Move,
+            Opcode::Normalize, Opcode::MoveGroup, Opcode::NormalMove];
+        let opcodes = legacy.iter().fold(0, |mask, opcode| mask | (1 << opcode.as_byte()));
+        Capabilities { version: 0, opcodes }
+    }
+
+    /// The capabilities of this client, which every local backend shares.
+    pub fn all() -> Self {
+        let opcodes = (0..32).filter_map(Opcode::from_byte).fold(0, |mask, opcode| mask | (1 << opcode.as_byte()));
+        Capabilities { version: PROTOCOL_VERSION, opcodes }
+    }
+
+    /// Reads the server's capabilities from its reply to the handshake.
+    /// Servers which don't know the handshake are treated as legacy servers.
+    /// ack: The reply to the `Hello` instruction.
+    pub fn from_ack(ack: &Ack) -> Self {
+        match (ack.status, ack.position) {
+            (Status::Ok, Some((version, opcodes))) => Capabilities { version, opcodes: opcodes as u32 },
+            _ => Capabilities::legacy()
+        }
+    }
+
+    /// Converts the capabilities into the position sent in the handshake.
+    pub fn as_position(&self) -> (i32, i32) {
+        (self.version, self.opcodes as i32)
+    }
+
+    /// Checks if an opcode is supported.
+    /// opcode: The opcode to check.
+    pub fn supports(&self, opcode: Opcode) -> bool {
+        self.opcodes & (1 << opcode.as_byte()) != 0
+    }
+}
+
+/// How the server moves through the points of a group.
+/// Zero fields use the server's defaults.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct SwipeSpeed {
+    /// How long to wait on each point, in milliseconds. (the server waits 60 by default)
+    pub dwell: i32,
+    /// The most units moved at once. Smaller steps move slower. (the server moves 127 by default)
+    pub step: i32
+}
+
 pub struct Instruction {
-    opcode: Opcode,
-    position: Option<(i32, i32)>,
-    group: Option<Vec<(i32, i32)>>
+    /// The operation to perform.
+    pub opcode: Opcode,
+    /// The instruction's (x, y) position, if it has one.
+    pub position: Option<(i32, i32)>,
+    /// The points of a group move.
+    pub group: Option<Vec<(i32, i32)>>
 }
 
 impl Instruction {
@@ -56,10 +325,30 @@ impl Instruction {
         Instruction { opcode: Opcode::NormalMove, position: Some((x, y)), group: None }
     }
 
+    /// Creates a scroll instruction.
+    /// The notches are sent in the position's x-coordinate.
+    /// notches: The notches to scroll. Positive values scroll down, and negative values scroll up.
+    pub fn scroll(notches: i32) -> Self {
+        let opcode = if notches < 0 { Opcode::ScrollUp } else { Opcode::ScrollDown };
+        Instruction { opcode, position: Some((notches.abs(), 0)), group: None }
+    }
+
+    /// Creates a handshake instruction, holding this client's capabilities.
+    pub fn hello() -> Self {
+        let capabilities = Capabilities::all();
+        Instruction { opcode: Opcode::Hello, position: Some(capabilities.as_position()), group: None }
+    }
+
     /// Creates a group move instruction.
+    /// The swipe speed is sent in the position, as (dwell, step).
     /// group: The group of positions to move to.
-    pub fn group(group: &Vec<(i32, i32)>) -> Self {
-        Instruction { opcode: Opcode::MoveGroup, position: None, group: Some(group.clone()) }
+    /// speed: How the server moves through the group.
+    pub fn group(group: &[(i32, i32)], speed: SwipeSpeed) -> Self {
+        Instruction {
+            opcode: Opcode::MoveGroup,
+            position: Some((speed.dwell, speed.step)),
+            group: Some(group.to_vec())
+        }
     }
 
     /// Serializes this instruction into binary.
@@ -92,43 +381,398 @@ impl Instruction {
     }
 }
 
-pub struct Mouse {
-    stream: TcpStream,
+/// Where mouse instructions are performed.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum MouseBackendKind {
+    /// The mouse server, over the network.
+    Server,
+    /// Desktop input on this PC, for devices mirrored to the same PC.
+    SendInput,
+    /// Touch input on an Android device, over ADB.
+    Adb,
+    /// A simulated mouse, which only records the instructions.
+    DryRun
+}
 
-    // This is the current 'left mouse' button state.
-    left: bool,
+impl MouseBackendKind {
+    /// Parses a mouse backend from the configuration.
+    /// Unknown values are treated as `Server`.
+    /// value: The configured value. ("server", "sendinput", "adb", or "dryrun")
+    pub fn parse(value: &str) -> Self {
+        match value.to_lowercase().as_str() {
+            "sendinput" => MouseBackendKind::SendInput,
+            "adb" => MouseBackendKind::Adb,
+            "dryrun" | "dry-run" => MouseBackendKind::DryRun,
+            _ => MouseBackendKind::Server
+        }
+    }
+}
 
-    // These instructions are used for exact movement.
-    normalized: bool,
-    current: (i32, i32)
+/// Performs mouse instructions.
+#[async_trait]
+pub trait MouseBackend: Send {
+    /// Performs an instruction, then returns its acknowledgement.
+    /// A failed instruction may have been lost, so the backend should reconnect.
+    /// instruction: The instruction to perform.
+    /// sequence: The instruction's sequence number.
+    async fn transmit(&mut self, instruction: &Instruction, sequence: u32) -> std::io::Result<Ack>;
+
+    /// Restores the backend after an instruction failed.
+    async fn reconnect(&mut self) -> std::io::Result<()>;
+
+    /// Checks if round trips are spent on a link to a server, so they measure its latency.
+    /// Local backends spend them performing instructions instead, so they're not recorded.
+    fn measures_latency(&self) -> bool {
+        false
+    }
 }
 
-impl Mouse {
-    /// Creates a new networked mouse instance.
+/// Sends instructions to the mouse server through a WebSocket, for servers behind a proxy.
+/// Each instruction is a binary message, framed the same way as over TCP,
+/// and each reply is a binary message holding the acknowledgement.
+pub struct WebSocketBackend {
+    socket: WebSocketStream<MaybeTlsStream<TcpStream>>,
+    url: String,
+    token: String
+}
+
+impl WebSocketBackend {
+    /// Connects to the mouse server.
+    /// url: The server's WebSocket URL. (e.g. "wss://example.com/mouse")
+    /// token: The token sent to the server before any instruction. (empty to skip the handshake)
+    pub async fn connect(url: &str, token: &str) -> Result<Self> {
+        if token.len() > u8::MAX as usize {
+            return Err(anyhow!("The server token can't be longer than {} bytes.", u8::MAX));
+        }
+
+        Ok(WebSocketBackend {
+            socket: Self::open_socket(url, token).await?,
+            url: url.to_string(),
+            token: token.to_string()
+        })
+    }
+
+    /// Opens a WebSocket to the mouse server, then sends the token.
+    /// url: The server's WebSocket URL.
+    /// token: The token sent to the server before any instruction. (empty to skip the handshake)
+    async fn open_socket(url: &str, token: &str) -> std::io::Result<WebSocketStream<MaybeTlsStream<TcpStream>>> {
+        let (mut socket, _) = tokio_tungstenite::connect_async(url).await.map_err(std::io::Error::other)?;
+        if token.is_empty() {
+            return Ok(socket);
+        }
+
+        // The server closes connections which don't send the token first.
+        socket.send(Message::Binary(auth_handshake(token))).await.map_err(std::io::Error::other)?;
+        match Self::read_ack(&mut socket).await {
+            Ok(ack) if ack.status == Status::Ok => Ok(socket),
+            _ => Err(std::io::Error::new(ErrorKind::PermissionDenied, "The mouse server rejected the token."))
+        }
+    }
+
+    /// Reads the next acknowledgement from the server.
+    /// Pings are answered while reading, so only the reply is handled.
+    /// socket: The WebSocket to the server.
+    async fn read_ack(socket: &mut WebSocketStream<MaybeTlsStream<TcpStream>>) -> std::io::Result<Ack> {
+        loop {
+            let message = socket.next().await
+                .ok_or_else(|| std::io::Error::new(ErrorKind::ConnectionAborted, "The WebSocket closed."))?
+                .map_err(std::io::Error::other)?;
+
+            match message {
+                Message::Binary(reply) => return Ack::from_reply(&reply)
+                    .ok_or_else(|| std::io::Error::new(ErrorKind::InvalidData, "Failed to read the server reply.")),
+                Message::Close(_) => return Err(std::io::Error::new(ErrorKind::ConnectionAborted, "The WebSocket closed.")),
+                _ => continue
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl MouseBackend for WebSocketBackend {
+    async fn transmit(&mut self, instruction: &Instruction, sequence: u32) -> std::io::Result<Ack> {
+        let mut bytes = instruction.serialize();
+        bytes.extend_from_slice(&sequence.to_le_bytes());
+        self.socket.send(Message::Binary(bytes)).await.map_err(std::io::Error::other)?;
+
+        Self::read_ack(&mut self.socket).await
+    }
+
+    async fn reconnect(&mut self) -> std::io::Result<()> {
+        self.socket = Self::open_socket(&self.url, &self.token).await?;
+        Ok(())
+    }
+    fn measures_latency(&self) -> bool {
+        true
+    }
+}
+
+/// Serializes the token handshake, which the server expects before any instruction.
+/// token: The server's token. (at most 255 bytes)
+fn auth_handshake(token: &str) -> Vec<u8> {
+    let mut handshake = AUTH_MAGIC.to_vec();
+    handshake.push(token.len() as u8);
+    handshake.extend_from_slice(token.as_bytes());
+    handshake
+}
+
+/// Sends instructions to the mouse server.
+pub struct TcpBackend {
+    stream: Box<dyn ServerStream>,
+    address: SocketAddr,
+    security: ServerSecurity
+}
+
+/// A connection to the mouse server, which may be encrypted.
+pub trait ServerStream: AsyncRead + AsyncWrite + Unpin + Send {}
+
+impl<T: AsyncRead + AsyncWrite + Unpin + Send> ServerStream for T {}
+
+/// How the connection to the mouse server is secured.
+#[derive(Clone)]
+pub struct ServerSecurity {
+    /// The token sent to the server before any instruction. (empty to skip the handshake)
+    pub token: String,
+    /// Encrypts the connection, checking the server's certificate against the name.
+    pub tls: Option<(TlsConnector, ServerName<'static>)>
+}
+
+impl ServerSecurity {
+    /// Reads the token and TLS settings from the configuration.
+    /// config: The application configuration.
+    pub fn from_config(config: &Config) -> Result<Self> {
+        if config.server_token.len() > u8::MAX as usize {
+            return Err(anyhow!("The server token can't be longer than {} bytes.", u8::MAX));
+        }
+        if !config.server_tls {
+            return Ok(ServerSecurity { token: config.server_token.clone(), tls: None });
+        }
+
+        // Trust the configured certificate, for servers with a self-signed one.
+        let mut roots = RootCertStore::empty();
+        if config.server_ca.is_empty() {
+            roots.extend(webpki_roots::TLS_SERVER_ROOTS.iter().cloned());
+        } else {
+            let pem = std::fs::read(&config.server_ca)?;
+            for certificate in rustls_pemfile::certs(&mut pem.as_slice()) {
+                roots.add(certificate?)?;
+            }
+        }
+
+        let provider = Arc::new(rustls::crypto::ring::default_provider());
+        let tls = ClientConfig::builder_with_provider(provider)
+            .with_safe_default_protocol_versions()?
+            .with_root_certificates(roots)
+            .with_no_client_auth();
+
+        let name = if config.server_tls_name.is_empty() { &config.server_address } else { &config.server_tls_name };
+        Ok(ServerSecurity {
+            token: config.server_token.clone(),
+            tls: Some((TlsConnector::from(Arc::new(tls)), ServerName::try_from(name.clone())?))
+        })
+    }
+}
+
+impl TcpBackend {
+    /// Connects to the mouse server.
     /// hostname: The address of the mouse server.
     /// port: The port of the mouse server.
-    pub async fn new<S: AsRef<str>>(hostname: S, port: u16) -> Result<Self> {
+    /// security: How the connection is secured.
+    pub async fn connect<S: AsRef<str>>(hostname: S, port: u16, security: ServerSecurity) -> Result<Self> {
         // Parse the server address.
         let address = SocketAddr::new(hostname.as_ref().parse()?, port);
 
-        // Connect to the server.
+        Ok(TcpBackend {
+            stream: Self::open_stream(address, &security).await?,
+            address,
+            security
+        })
+    }
+
+    /// Opens a connection to the mouse server, then sends the token.
+    /// address: The address of the mouse server.
+    /// security: How the connection is secured.
+    async fn open_stream(address: SocketAddr, security: &ServerSecurity) -> std::io::Result<Box<dyn ServerStream>> {
         let socket = TcpSocket::new_v4()?;
         let stream = socket.connect(address).await?;
         stream.set_nodelay(true)?;
 
-        Ok(Mouse {
-            stream,
+        let mut stream: Box<dyn ServerStream> = match &security.tls {
+            Some((connector, name)) => Box::new(connector.connect(name.clone(), stream).await?),
+            None => Box::new(stream)
+        };
+        if security.token.is_empty() {
+            return Ok(stream);
+        }
+
+        // The server closes connections which don't send the token first.
+        stream.write_all(&auth_handshake(&security.token)).await?;
+
+        let mut reply = [0u8; 16];
+        stream.read_exact(&mut reply).await?;
+        match Ack::from_reply(&reply) {
+            Some(ack) if ack.status == Status::Ok => Ok(stream),
+            _ => Err(std::io::Error::new(ErrorKind::PermissionDenied, "The mouse server rejected the token."))
+        }
+    }
+}
+
+#[async_trait]
+impl MouseBackend for TcpBackend {
+    /// Writes an instruction to the server, then reads the reply.
+    async fn transmit(&mut self, instruction: &Instruction, sequence: u32) -> std::io::Result<Ack> {
+        // Serialize the instruction.
+        // Older servers ignore the sequence number after the group.
+        let mut bytes = instruction.serialize();
+        bytes.extend_from_slice(&sequence.to_le_bytes());
+
+        // Send the instruction.
+        self.stream.write_all(&bytes).await?;
+
+        // Wait for the server to reply.
+        let mut header = [0u8; 4];
+        self.stream.read_exact(&mut header).await?;
+
+        if &header == LEGACY_ACK {
+            Ok(Ack::legacy())
+        } else if &header[..3] == ACK_MAGIC {
+            let mut body = [0u8; 12];
+            self.stream.read_exact(&mut body).await?;
+            Ok(Ack::parse(header[3], &body))
+        } else {
+            // The stream is out of step with the server, so start over.
+            Err(std::io::Error::new(ErrorKind::InvalidData, "Failed to read the server reply."))
+        }
+    }
+
+    async fn reconnect(&mut self) -> std::io::Result<()> {
+        self.stream = Self::open_stream(self.address, &self.security).await?;
+        Ok(())
+    }
+    fn measures_latency(&self) -> bool {
+        true
+    }
+}
+
+pub struct Mouse {
+    backend: Box<dyn MouseBackend>,
+    capabilities: Capabilities,
+    latency: Latency,
+
+    // This is the current 'left mouse' button state.
+    left: bool,
+
+    // These instructions are used for exact movement.
+    normalized: bool,
+    current: (i32, i32),
+
+    // The sequence number of the next instruction.
+    sequence: u32
+}
+
+impl Mouse {
+    /// Creates a new mouse instance.
+    /// backend: Where instructions are performed.
+    pub fn new(backend: Box<dyn MouseBackend>) -> Self {
+        Mouse {
+            backend,
+            capabilities: Capabilities::all(),
+            latency: Latency::default(),
             normalized: false,
             current: (0, 0),
-            left: false
-        })
+            left: false,
+            sequence: 0
+        }
+    }
+
+    /// Opens the configured mouse backend.
+    /// config: The application configuration.
+    pub async fn open(config: &Config) -> Result<Self> {
+        let backend: Box<dyn MouseBackend> = match MouseBackendKind::parse(&config.mouse_backend) {
+            MouseBackendKind::Server if !config.server_url.is_empty() => Box::new(WebSocketBackend::connect(&config.server_url, &config.server_token).await?),
+            MouseBackendKind::Server => Box::new(TcpBackend::connect(&config.server_address, config.server_port,
+                                                                     ServerSecurity::from_config(config)?).await?),
+            MouseBackendKind::SendInput => Box::new(SendInputBackend::new(config)),
+            MouseBackendKind::Adb => Box::new(AdbBackend::new(config)),
+            MouseBackendKind::DryRun => Box::new(DryRunBackend::new(config))
+        };
+
+        let mut mouse = Mouse::new(backend);
+        mouse.negotiate().await?;
+
+        Ok(mouse)
+    }
+
+    /// Exchanges capabilities with the backend.
+    /// Opcodes the backend doesn't support are emulated with simpler ones where possible.
+    async fn negotiate(&mut self) -> Result<()> {
+        let ack = self.transmit(&Instruction::hello()).await?;
+        self.capabilities = Capabilities::from_ack(&ack);
+
+        if self.capabilities.version == 0 {
+            info!("The mouse server doesn't support the handshake, assuming an older server.");
+        } else {
+            info!("The mouse server speaks protocol version {}.", self.capabilities.version);
+        }
+
+        // Everything else is built from these.
+        for opcode in [Opcode::LeftDown, Opcode::LeftUp, Opcode::Move, Opcode::Normalize] {
+            self.require(opcode)?;
+        }
+        for opcode in [Opcode::MoveGroup, Opcode::NormalMove] {
+            if !self.capabilities.supports(opcode) {
+                warn!("The mouse server doesn't support {:?}; it will be emulated, which is slower.", opcode);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Reconnects to the backend, waiting longer after each failed attempt.
+    /// The mouse is normalized afterwards, since its position is unknown.
+    async fn reconnect(&mut self) -> Result<()> {
+        let mut delay = RECONNECT_DELAY;
+        for attempt in 1..=RECONNECT_ATTEMPTS {
+            sleep(delay).await;
+            delay *= 2;
+
+            if let Err(error) = self.backend.reconnect().await {
+                warn!("Reconnect attempt {} of {} failed: {}", attempt, RECONNECT_ATTEMPTS, error);
+                continue;
+            }
+
+            // The server may have been updated while it was away.
+            if let Err(error) = self.negotiate().await {
+                warn!("Reconnect attempt {} of {} failed: {}", attempt, RECONNECT_ATTEMPTS, error);
+                continue;
+            }
+
+            // The server may have moved the mouse before the connection dropped.
+            if let Err(error) = self.transmit(&Instruction::empty(Opcode::Normalize)).await {
+                warn!("Reconnect attempt {} of {} failed: {}", attempt, RECONNECT_ATTEMPTS, error);
+                continue;
+            }
+            self.normalized = true;
+            self.current = (0, 0);
+            self.left = false;
+
+            info!("Reconnected to the mouse.");
+            return Ok(());
+        }
+
+        Err(anyhow!("Couldn't reconnect to the mouse after {} attempts.", RECONNECT_ATTEMPTS))
     }
 
     /// Normalizes this mouse instance.
     /// This allows absolute movement to be used.
     pub async fn normalize(&mut self) {
         // Send the instruction.
-        self.send(Instruction::empty(Opcode::Normalize)).await.unwrap();
+        // Reconnecting normalizes the mouse, so a lost instruction doesn't matter.
+        if let Err(error) = self.send(Instruction::empty(Opcode::Normalize)).await {
+            if !error.is::<Reconnected>() {
+                panic!("Failed to normalize the mouse: {}", error);
+            }
+        }
 
         // Set the mouse to normalized mode.
         self.normalized = true;
@@ -138,16 +782,16 @@ impl Mouse {
     }
 
     /// Performs a single left click.
-    pub async fn click(&mut self) {
-        self.button(Some(true)).await;
+    pub async fn click(&mut self) -> Result<()> {
+        self.button(Some(true)).await?;
         sleep(Duration::from_millis(50)).await;
-        self.button(Some(false)).await;
+        self.button(Some(false)).await
     }
 
     /// Presses or releases the left mouse button.
     /// down: If true, the button is pressed. If false, the button is released.
     ///       When None, the button is toggled.
-    pub async fn button(&mut self, down: Option<bool>) {
+    pub async fn button(&mut self, down: Option<bool>) -> Result<()> {
         // Get the 'left down' state.
         let new_state = match down {
             Some(state) => state,
@@ -157,7 +801,38 @@ impl Mouse {
 
         // Send the instruction.
         let opcode = if new_state { Opcode::LeftDown } else { Opcode::LeftUp };
-        self.send(Instruction::empty(opcode)).await.unwrap();
+        self.send(Instruction::empty(opcode)).await?;
+
+        Ok(())
+    }
+
+    /// Performs a single right click.
+    /// This opens context menus, and goes back on Android.
+    pub async fn right_click(&mut self) -> Result<()> {
+        self.require(Opcode::RightClick)?;
+        self.send(Instruction::empty(Opcode::RightClick)).await?;
+
+        Ok(())
+    }
+
+    /// Scrolls the wheel under the cursor.
+    /// notches: The notches to scroll. Positive values scroll down, and negative values scroll up.
+    pub async fn scroll(&mut self, notches: i32) -> Result<()> {
+        let instruction = Instruction::scroll(notches);
+        self.require(instruction.opcode)?;
+        self.send(instruction).await?;
+
+        Ok(())
+    }
+
+    /// Fails if the backend doesn't support an opcode which can't be emulated.
+    /// opcode: The opcode to check.
+    fn require(&self, opcode: Opcode) -> Result<()> {
+        if !self.capabilities.supports(opcode) {
+            return Err(anyhow!("The mouse server doesn't support {:?}.", opcode));
+        }
+
+        Ok(())
     }
 
     /// Moves the mouse relative to the current position.
@@ -177,9 +852,15 @@ impl Mouse {
     /// Moves the mouse relative using a list of points.
     /// UPDATE: This will also hold the left mouse button.
     /// group: The group of points to move to.
-    pub async fn move_group(&mut self, group: Vec<(i32, i32)>) -> Result<()> {
-        // Send the instruction.
-        self.send(Instruction::group(&group)).await?;
+    /// speed: How the server moves through the group.
+    pub async fn move_group(&mut self, group: Vec<(i32, i32)>, speed: SwipeSpeed) -> Result<()> {
+        // Send the instruction, or each point if the server can't move through groups.
+        // Groups too large for one instruction are sent a point at a time as well.
+        if self.capabilities.supports(Opcode::MoveGroup) && group.len() <= MAX_GROUP_POINTS {
+            self.send(Instruction::group(&group, speed)).await?;
+        } else {
+            self.move_points(&group, speed).await?;
+        }
 
         // Update the current position.
         self.current = group[group.len() - 1];
@@ -187,6 +868,29 @@ impl Mouse {
         Ok(())
     }
 
+    /// Moves through a group of points one move at a time, holding the button after the first.
+    /// This is the same as a group move, for servers without one.
+    /// The step of the speed is ignored, since single moves use the server's.
+    /// group: The group of points to move to.
+    /// speed: How to move through the group.
+    async fn move_points(&mut self, group: &[(i32, i32)], speed: SwipeSpeed) -> Result<()> {
+        let dwell = if speed.dwell > 0 { Duration::from_millis(speed.dwell as u64) } else { GROUP_DWELL };
+
+        for (i, (dx, dy)) in group.iter().enumerate() {
+            self.send(Instruction::delta(*dx, *dy)).await?;
+
+            // The button is pressed once the first point is reached.
+            if i == 0 {
+                sleep(PRESS_TIME).await;
+                self.button(Some(true)).await?;
+            }
+            sleep(dwell).await;
+        }
+
+        sleep(PRESS_TIME).await;
+        self.button(Some(false)).await
+    }
+
     /// Moves the mouse to the specified position.
     /// x: The x-coordinate to move to.
     /// y: The y-coordinate to move to.
@@ -207,8 +911,13 @@ impl Mouse {
         } else {
             self.normalized = true;
             
-            // Send the instruction.
-            self.send(Instruction::absolute(position.0, position.1)).await?;
+            // Send the instruction, or normalize first if the server can't do both.
+            if self.capabilities.supports(Opcode::NormalMove) {
+                self.send(Instruction::absolute(position.0, position.1)).await?;
+            } else {
+                self.send(Instruction::empty(Opcode::Normalize)).await?;
+                self.send(Instruction::delta(position.0, position.1)).await?;
+            }
 
             // Update the current position.
             self.current = position;
@@ -217,23 +926,126 @@ impl Mouse {
         Ok(())
     }
 
-    /// Moves the mouse to the specified position.
-    /// instruction: The instruction to move the mouse.
-    pub async fn send(&mut self, instruction: Instruction) -> Result<()> {
-        // Serialize the instruction.
-        let bytes = instruction.serialize();
+    /// Sends an instruction and waits for the server to acknowledge it.
+    /// Fails if the server reports an error or acknowledges another instruction.
+    /// If the connection drops, the mouse reconnects and fails with `Reconnected`.
+    /// instruction: The instruction to send.
+    #[tracing::instrument(skip_all, fields(sequence = self.sequence))]
+    pub async fn send(&mut self, instruction: Instruction) -> Result<Ack> {
+        let sequence = self.sequence;
+        let ack = match self.transmit(&instruction).await {
+            Ok(ack) => ack,
+            Err(error) => {
+                warn!("Lost the connection to the mouse: {}", error);
+                self.reconnect().await?;
+                return Err(Reconnected.into());
+            }
+        };
 
-        // Send the instruction.
-        self.stream.write_all(&bytes).await?;
+        if ack.status != Status::Ok {
+            return Err(anyhow!("The server rejected instruction {}: {:?}", sequence, ack.status));
+        }
+        if ack.sequence.is_some_and(|echoed| echoed != sequence) {
+            return Err(anyhow!("The server acknowledged instruction {:?}, expected {}.",
+                ack.sequence.unwrap(), sequence));
+        }
 
-        // Wait for the server to reply.
-        let mut reply = [0u8; 4];
-        let size = self.stream.read(&mut reply).await?;
+        Ok(ack)
+    }
+
+    /// Returns the average round trip of instructions the server performs immediately.
+    /// This is the time taken by the link, and the server's handling, rather than by moves.
+    /// This is zero for local backends, which have no link to measure.
+    pub fn latency(&self) -> Duration {
+        self.latency.average()
+    }
+
+    /// Performs an instruction with the next sequence number, timing the round trip.
+    /// instruction: The instruction to send.
+    async fn transmit(&mut self, instruction: &Instruction) -> std::io::Result<Ack> {
+        let sequence = self.sequence;
+        self.sequence = self.sequence.wrapping_add(1);
 
-        if size != 4 {
-            return Err(anyhow!("Failed to read the server reply."));
+        let sent = Instant::now();
+        let ack = self.backend.transmit(instruction, sequence).await?;
+        let rtt = sent.elapsed();
+
+        debug!("Instruction {} ({:?}) took {:.1}ms.", sequence, instruction.opcode, rtt.as_secs_f64() * 1000.0);
+        if instruction.opcode.is_immediate() && self.backend.measures_latency() {
+            self.latency.record(rtt);
         }
 
-        Ok(())
+        Ok(ack)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::Duration;
+    use crate::mouse::{Ack, Capabilities, Latency, Opcode, Status, LATENCY_SAMPLES, PROTOCOL_VERSION};
+
+    #[test]
+    pub fn parses_whole_replies() {
+        let mut reply = b"ACK\x00".to_vec();
+        reply.extend_from_slice(&7u32.to_le_bytes());
+        reply.extend_from_slice(&[0; 8]);
+
+        assert_eq!(Ack::from_reply(&reply).unwrap().sequence, Some(7));
+        assert_eq!(Ack::from_reply(b"OKAY"), Some(Ack::legacy()));
+        assert_eq!(Ack::from_reply(&reply[..10]), None);
+        assert_eq!(Ack::from_reply(b"NOPE"), None);
+    }
+
+    #[test]
+    pub fn parses_acks() {
+        let mut body = [0u8; 12];
+        body[..4].copy_from_slice(&7u32.to_le_bytes());
+        body[4..8].copy_from_slice(&(-30i32).to_le_bytes());
+        body[8..].copy_from_slice(&165i32.to_le_bytes());
+
+        assert_eq!(Ack::parse(0, &body), Ack {
+            status: Status::Ok,
+            sequence: Some(7),
+            position: Some((-30, 165))
+        });
+        assert_eq!(Ack::parse(2, &body).status, Status::Malformed);
+        assert_eq!(Ack::parse(9, &body).status, Status::Unknown(9));
+    }
+
+    #[test]
+    pub fn negotiates_capabilities() {
+        let hello = |status, position| Ack { status, sequence: Some(0), position };
+
+        let current = Capabilities::from_ack(&hello(Status::Ok, Some(Capabilities::all().as_position())));
+        assert_eq!(current.version, PROTOCOL_VERSION);
+        assert!(current.supports(Opcode::MoveGroup) && current.supports(Opcode::Hello));
+
+        // A server which only moves one point at a time.
+        let basic = Capabilities::from_ack(&hello(Status::Ok, Some((1, 0b11110))));
+        assert!(basic.supports(Opcode::Move) && !basic.supports(Opcode::MoveGroup));
+
+        // Older servers reject the opcode, or only say "OKAY".
+        assert_eq!(Capabilities::from_ack(&hello(Status::InvalidOpcode, Some((0, 0)))), Capabilities::legacy());
+        assert_eq!(Capabilities::from_ack(&Ack::legacy()), Capabilities::legacy());
+        assert!(Capabilities::legacy().supports(Opcode::NormalMove) && !Capabilities::legacy().supports(Opcode::Hello));
+        assert!(current.supports(Opcode::RightClick) && !Capabilities::legacy().supports(Opcode::ScrollDown));
+    }
+
+    #[test]
+    pub fn tracks_latency() {
+        let mut latency = Latency::default();
+        assert_eq!(latency.average(), Duration::ZERO);
+
+        latency.record(Duration::from_millis(10));
+        latency.record(Duration::from_millis(30));
+        assert_eq!(latency.average(), Duration::from_millis(20));
+
+        // Only the latest round trips count.
+        for _ in 0..LATENCY_SAMPLES {
+            latency.record(Duration::from_millis(50));
+        }
+        assert_eq!(latency.average(), Duration::from_millis(50));
+
+        assert!(Opcode::LeftDown.is_immediate() && !Opcode::MoveGroup.is_immediate());
     }
-}
\ No newline at end of file
+}
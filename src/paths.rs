@@ -0,0 +1,43 @@
+use std::path::{Path, PathBuf};
+use directories::ProjectDirs;
+
+/// Returns the directory runtime files are stored in.
+/// This is the platform's data directory, unless one is configured.
+/// configured: The configured data directory. (empty for the default)
+pub fn data_dir(configured: &str) -> PathBuf {
+    if !configured.is_empty() {
+        return PathBuf::from(configured);
+    }
+
+    ProjectDirs::from("", "", "word-terminator")
+        .map(|dirs| dirs.data_dir().to_path_buf())
+        .unwrap_or_else(|| PathBuf::from("."))
+}
+
+/// Resolves a configured path to a runtime file.
+/// Absolute paths, and paths which exist in the working directory, are kept as-is.
+/// Other paths are placed in the data directory.
+/// data_dir: The data directory.
+/// path: The configured path. (empty paths stay empty)
+pub fn resolve(data_dir: &Path, path: &str) -> String {
+    if path.is_empty() || Path::new(path).is_absolute() || Path::new(path).exists() {
+        return path.to_string();
+    }
+
+    data_dir.join(path).to_string_lossy().into_owned()
+}
+
+#[cfg(test)]
+mod test {
+    use std::path::Path;
+    use crate::paths::resolve;
+
+    #[test]
+    pub fn resolves_paths() {
+        let data = Path::new("data");
+
+        assert_eq!(resolve(data, ""), "");
+        assert_eq!(resolve(data, "src"), "src");
+        assert_eq!(Path::new(&resolve(data, "words.txt")), data.join("words.txt"));
+    }
+}
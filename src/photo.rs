@@ -0,0 +1,192 @@
+use image::imageops::FilterType;
+use image::{Rgba, RgbaImage};
+use imageproc::geometric_transformations::{warp_into, Interpolation, Projection};
+use crate::board::{BOARD_PADDING, BOARD_SIZE, BOARD_TOP};
+
+/// The color of a board tile.
+pub const TILE_COLOR: [u8; 3] = [231, 203, 145];
+/// How far a pixel's color may be from a tile's, per channel.
+pub const TILE_TOLERANCE: u8 = 48;
+/// The share of a row or column's tile pixels needed to count it as part of the board.
+pub const COVERAGE: f64 = 0.2;
+
+/// A region of an image. (x, y, width, height)
+pub type Region = (u32, u32, u32, u32);
+
+/// Reads an image from the system clipboard.
+pub fn read_clipboard() -> anyhow::Result<RgbaImage> {
+    let image = arboard::Clipboard::new()?.get_image()?;
+    RgbaImage::from_raw(image.width as u32, image.height as u32, image.bytes.into_owned())
+        .ok_or_else(|| anyhow::anyhow!("The clipboard image is malformed."))
+}
+
+/// Finds the tiles of a board within a screenshot or photo.
+/// The board is expected to be mostly upright, but can be any size.
+/// Returns the region covering the tiles, or None if no board was found.
+/// image: The image to search.
+pub fn find_board(image: &RgbaImage) -> Option<Region> {
+    let (width, height) = image.dimensions();
+    let mut rows = vec![0u32; height as usize];
+    let mut cols = vec![0u32; width as usize];
+
+    // Count the tile-colored pixels in each row and column.
+    for (x, y, pixel) in image.enumerate_pixels() {
+        if is_tile(pixel) {
+            rows[y as usize] += 1;
+            cols[x as usize] += 1;
+        }
+    }
+
+    let (top, bottom) = span(&rows)?;
+    let (left, right) = span(&cols)?;
+    Some((left, top, right - left + 1, bottom - top + 1))
+}
+
+/// Finds the corners of a board's tiles, which may be photographed at an angle.
+/// Returns the (top-left, top-right, bottom-right, bottom-left) corners,
+/// or None if no board was found.
+/// image: The image to search.
+pub fn find_corners(image: &RgbaImage) -> Option<[(f32, f32); 4]> {
+    // Ignore stray tile-colored pixels outside of the board.
+    let (left, top, width, height) = find_board(image)?;
+
+    // Each corner is the tile pixel furthest in its diagonal direction.
+    let mut corners: Option<[(i64, (u32, u32)); 4]> = None;
+    for y in top..top + height {
+        for x in left..left + width {
+            if !is_tile(image.get_pixel(x, y)) {
+                continue;
+            }
+
+            let (sx, sy) = (x as i64, y as i64);
+            let scores = [-sx - sy, sx - sy, sx + sy, sy - sx];
+            let found = corners.get_or_insert(scores.map(|score| (score, (x, y))));
+            for (corner, score) in found.iter_mut().zip(scores) {
+                if score > corner.0 {
+                    *corner = (score, (x, y));
+                }
+            }
+        }
+    }
+
+    corners.map(|corners| corners.map(|(_, (x, y))| (x as f32, y as f32)))
+}
+
+/// Warps a board's tiles into an upright square.
+/// Returns None if the corners don't form a valid quadrilateral.
+/// image: The image containing the board.
+/// corners: The (top-left, top-right, bottom-right, bottom-left) corners of the tiles.
+/// size: The side length of the square.
+pub fn rectify(image: &RgbaImage, corners: [(f32, f32); 4], size: u32) -> Option<RgbaImage> {
+    // The corners are pixel centers, so map them to the square's outer pixels.
+    let last = size.saturating_sub(1) as f32;
+    let square = [(0.0, 0.0), (last, 0.0), (last, last), (0.0, last)];
+    let projection = Projection::from_control_points(corners, square)?;
+
+    let mut tiles = RgbaImage::new(size, size);
+    warp_into(image, &projection, Interpolation::Bilinear, Rgba([255, 255, 255, 255]), &mut tiles);
+    Some(tiles)
+}
+
+/// Redraws a board's tiles as they appear in a canonical frame.
+/// The result can be read like a captured frame.
+/// image: The image containing the board.
+/// region: The region covering the tiles.
+pub fn to_frame(image: &RgbaImage, (x, y, width, height): Region) -> RgbaImage {
+    let tiles = image::imageops::crop_imm(image, x, y, width, height).to_image();
+
+    // The tiles fill the board, except for its padding.
+    let size = BOARD_SIZE - 2 * BOARD_PADDING;
+    let tiles = image::imageops::resize(&tiles, size, size, FilterType::Triangle);
+
+    let mut frame = RgbaImage::from_pixel(
+        BOARD_TOP.0 + BOARD_SIZE,
+        BOARD_TOP.1 + BOARD_SIZE,
+        Rgba([255, 255, 255, 255])
+    );
+    image::imageops::overlay(
+        &mut frame, &tiles,
+        (BOARD_TOP.0 + BOARD_PADDING) as i64,
+        (BOARD_TOP.1 + BOARD_PADDING) as i64
+    );
+
+    frame
+}
+
+/// Checks if a pixel is close to the color of a tile.
+/// pixel: The pixel to check.
+fn is_tile(pixel: &Rgba<u8>) -> bool {
+    pixel.0.iter().zip(TILE_COLOR)
+        .all(|(channel, tile)| channel.abs_diff(tile) <= TILE_TOLERANCE)
+}
+
+/// Finds the first and last lines with enough tile pixels.
+/// Lines with only a few stray tile-colored pixels are ignored.
+/// counts: The number of tile pixels in each line.
+fn span(counts: &[u32]) -> Option<(u32, u32)> {
+    let most = *counts.iter().max()?;
+    if most == 0 {
+        return None;
+    }
+
+    let threshold = (most as f64 * COVERAGE).ceil() as u32;
+    let first = counts.iter().position(|count| *count >= threshold)?;
+    let last = counts.iter().rposition(|count| *count >= threshold)?;
+
+    Some((first as u32, last as u32))
+}
+
+#[cfg(test)]
+mod test {
+    use image::{Rgba, RgbaImage};
+    use crate::board::{BOARD_PADDING, BOARD_SIZE, BOARD_TOP};
+    use crate::photo::{find_board, find_corners, rectify, to_frame, TILE_COLOR};
+
+    #[test]
+    pub fn finds_boards_in_photos() {
+        let [r, g, b] = TILE_COLOR;
+        let mut image = RgbaImage::from_pixel(300, 200, Rgba([40, 90, 40, 255]));
+
+        // Draw the tiles, slightly off-color, with a stray tile-colored pixel.
+        for y in 50..150 {
+            for x in 120..220 {
+                image.put_pixel(x, y, Rgba([r - 10, g + 5, b, 255]));
+            }
+        }
+        image.put_pixel(5, 5, Rgba([r, g, b, 255]));
+
+        let region = find_board(&image).unwrap();
+        assert_eq!(region, (120, 50, 100, 100));
+        assert_eq!(find_board(&RgbaImage::new(10, 10)), None);
+
+        let frame = to_frame(&image, region);
+        assert_eq!(frame.dimensions(), (BOARD_TOP.0 + BOARD_SIZE, BOARD_TOP.1 + BOARD_SIZE));
+        assert_eq!(frame.get_pixel(BOARD_TOP.0 + BOARD_PADDING, BOARD_TOP.1 + BOARD_PADDING).0, [r - 10, g + 5, b, 255]);
+    }
+
+    #[test]
+    pub fn rectifies_skewed_boards() {
+        let [r, g, b] = TILE_COLOR;
+        let mut image = RgbaImage::from_pixel(200, 200, Rgba([40, 90, 40, 255]));
+
+        // Draw a parallelogram, leaning right by a pixel every two rows.
+        for y in 40..140 {
+            let shift = (y - 40) / 2;
+            for x in 30 + shift..110 + shift {
+                image.put_pixel(x, y, Rgba([r, g, b, 255]));
+            }
+        }
+
+        let corners = find_corners(&image).unwrap();
+        assert_eq!(corners, [(30.0, 40.0), (109.0, 40.0), (158.0, 139.0), (79.0, 139.0)]);
+
+        // Away from the jagged edges, every pixel should land on a tile.
+        let tiles = rectify(&image, corners, 64).unwrap();
+        assert_eq!(tiles.dimensions(), (64, 64));
+        for y in 2..62 {
+            for x in 2..62 {
+                assert_eq!(tiles.get_pixel(x, y).0, [r, g, b, 255], "({}, {})", x, y);
+            }
+        }
+    }
+}
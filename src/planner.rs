@@ -0,0 +1,516 @@
+use std::time::Duration;
+use rand::Rng;
+use serde::Serialize;
+use crate::board::{GridPos, Layout, BOARD_TOP, START_POS};
+use crate::mouse::MAX_GROUP_POINTS;
+use crate::solver::Word;
+
+/// Converts grid positions into mouse coordinates.
+#[derive(Clone, Copy, Debug)]
+pub struct CoordinateMapper {
+    /// The mouse coordinates of the first tile on the board.
+    pub origin: (i32, i32),
+    /// The mouse distance between two adjacent tiles.
+    pub offset: (i32, i32),
+    /// The number of rows and columns on the board.
+    pub layout: Layout,
+    /// How far the mouse may go past the outer tiles.
+    pub margin: i32,
+    /// The correction for the mirror's pointer acceleration.
+    pub correction: PointerCorrection,
+    /// The shape of the moves between tiles.
+    pub path: PathStyle
+}
+
+/// The most points added along each move between tiles.
+pub const MAX_PATH_POINTS: usize = 8;
+
+/// The shape of the moves between tiles.
+/// Curved moves look less robotic than straight tile-to-tile moves.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct PathStyle {
+    /// The points added along each move between tiles. (0 for straight moves)
+    pub points: usize,
+    /// The most a move bows away from a straight line, as a fraction of its length.
+    pub curve: f64,
+    /// The most each tile's point is moved off the tile's center, in mouse units.
+    /// This is limited to a quarter of the tile spacing, so points stay on their tiles.
+    pub jitter: f64
+}
+
+impl PathStyle {
+    /// Moves a point off of a tile's center by a random amount.
+    /// point: The mouse coordinates of the tile's center.
+    /// limit: The most the point may be moved.
+    /// rng: The source of randomness.
+    pub fn jitter_point(&self, (x, y): (i32, i32), limit: f64, rng: &mut impl Rng) -> (i32, i32) {
+        let jitter = self.jitter.min(limit);
+        if jitter <= 0.0 {
+            return (x, y);
+        }
+
+        (
+            x + rng.gen_range(-jitter..=jitter).round() as i32,
+            y + rng.gen_range(-jitter..=jitter).round() as i32
+        )
+    }
+
+    /// Returns this style with no more curve points than fit in one group move.
+    /// Longer words get fewer points along each move, down to straight moves.
+    /// tiles: The number of tiles in the word.
+    pub fn fit_group(&self, tiles: usize) -> PathStyle {
+        // The first tile takes one point, and each move takes its curve points and its target.
+        let moves = tiles.saturating_sub(1).max(1);
+        let per_move = (MAX_GROUP_POINTS - 1) / moves;
+
+        PathStyle { points: self.points.min(per_move.saturating_sub(1)), ..*self }
+    }
+
+    /// Finds the points along a curved move, ending at the target.
+    /// The move is a quadratic Bezier curve, bowed to a random side by a random amount.
+    /// from: The mouse coordinates the move starts at.
+    /// to: The mouse coordinates the move ends at.
+    /// rng: The source of randomness.
+    pub fn curve_points(&self, from: (i32, i32), to: (i32, i32), rng: &mut impl Rng) -> Vec<(i32, i32)> {
+        if self.points == 0 || self.curve <= 0.0 || from == to {
+            return vec![to];
+        }
+
+        // Push the control point away from the middle of the move, at a right angle.
+        let (dx, dy) = ((to.0 - from.0) as f64, (to.1 - from.1) as f64);
+        // The curve's peak is halfway to the control point.
+        let bow = 2.0 * rng.gen_range(-self.curve..=self.curve);
+        let control = (
+            (from.0 + to.0) as f64 / 2.0 - dy * bow,
+            (from.1 + to.1) as f64 / 2.0 + dx * bow
+        );
+
+        let mut points: Vec<(i32, i32)> = (1..=self.points)
+            .map(|index| {
+                let t = index as f64 / (self.points + 1) as f64;
+                let point = |start: i32, control: f64, end: i32| {
+                    (1.0 - t).powi(2) * start as f64 + 2.0 * (1.0 - t) * t * control + t.powi(2) * end as f64
+                };
+
+                (point(from.0, control.0, to.0).round() as i32, point(from.1, control.1, to.1).round() as i32)
+            })
+            .collect();
+        points.push(to);
+
+        points
+    }
+}
+
+/// Corrects for pointer acceleration applied by a mirroring app.
+/// The mirror is modelled as moving the pointer `gain * requested ^ curve` units.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct PointerCorrection {
+    /// The distance moved for each requested unit, along the (x, y) axes.
+    pub gain: (f64, f64),
+    /// How much faster longer moves are. (1 for no acceleration)
+    pub curve: f64
+}
+
+impl PointerCorrection {
+    /// Converts a delta into the delta to request, so the pointer lands on target.
+    /// delta: The distance the pointer should move.
+    pub fn apply(&self, (dx, dy): (i32, i32)) -> (i32, i32) {
+        let correct = |delta: i32, gain: f64| {
+            let distance = (delta.unsigned_abs() as f64 / gain).powf(1.0 / self.curve);
+            delta.signum() * distance.round() as i32
+        };
+
+        (correct(dx, self.gain.0), correct(dy, self.gain.1))
+    }
+}
+
+impl Default for PointerCorrection {
+    fn default() -> Self {
+        PointerCorrection {
+            gain: (1.0, 1.0),
+            curve: 1.0
+        }
+    }
+}
+
+impl CoordinateMapper {
+    /// Creates a mapper for a board layout.
+    /// Tiles on larger boards are closer together.
+    /// layout: The number of rows and columns on the board.
+    pub fn new(layout: Layout) -> Self {
+        let default = CoordinateMapper::default();
+        let scale = |offset: i32| (offset as f64 / layout.scale()).round() as i32;
+
+        CoordinateMapper {
+            offset: (scale(default.offset.0), scale(default.offset.1)),
+            layout,
+            ..default
+        }
+    }
+
+    /// Converts a grid position into absolute mouse coordinates.
+    /// pos: The grid position.
+    pub fn position(&self, pos: GridPos) -> (i32, i32) {
+        (
+            self.origin.0 + (pos.col as i32 * self.offset.0),
+            self.origin.1 + (pos.row as i32 * self.offset.1)
+        )
+    }
+
+    /// Converts a pixel in a captured frame into absolute mouse coordinates.
+    /// The scale is derived from the tile spacing in pixels and mouse units.
+    /// pixel: The (x, y) pixel coordinates.
+    pub fn pixel_position(&self, (x, y): (u32, u32)) -> (i32, i32) {
+        let center = self.first_center();
+        let pitch = self.layout.pitch();
+
+        let scale = |pixel: u32, center: i32, origin: i32, offset: i32| {
+            origin + ((pixel as i32 - center) as f64 * offset as f64 / pitch).round() as i32
+        };
+
+        (
+            scale(x, center.0, self.origin.0, self.offset.0),
+            scale(y, center.1, self.origin.1, self.offset.1)
+        )
+    }
+
+    /// Converts absolute mouse coordinates into a pixel in a captured frame.
+    /// This is the inverse of `pixel_position`, so it's rounded to the nearest pixel.
+    /// position: The absolute mouse coordinates.
+    pub fn frame_pixel(&self, (x, y): (i32, i32)) -> (i32, i32) {
+        let center = self.first_center();
+        let pitch = self.layout.pitch();
+
+        let scale = |mouse: i32, center: i32, origin: i32, offset: i32| {
+            center + ((mouse - origin) as f64 * pitch / offset as f64).round() as i32
+        };
+
+        (
+            scale(x, center.0, self.origin.0, self.offset.0),
+            scale(y, center.1, self.origin.1, self.offset.1)
+        )
+    }
+
+    /// Returns the center of the first tile, in pixels.
+    fn first_center(&self) -> (i32, i32) {
+        let first = self.layout.tile_origin(0, 0);
+        (
+            (BOARD_TOP.0 + first.0 + self.layout.tile_size() / 2) as i32,
+            (BOARD_TOP.1 + first.1 + self.layout.tile_size() / 2) as i32
+        )
+    }
+
+    /// Returns the (min, max) mouse coordinates the board allows.
+    /// This includes the safety margin around the outer tiles.
+    pub fn bounds(&self) -> ((i32, i32), (i32, i32)) {
+        let last = self.position(GridPos::new(
+            self.layout.rows - 1,
+            self.layout.cols - 1
+        ));
+
+        (
+            (self.origin.0 - self.margin, self.origin.1 - self.margin),
+            (last.0 + self.margin, last.1 + self.margin)
+        )
+    }
+
+    /// Clamps mouse coordinates to the board's bounds.
+    /// position: The mouse coordinates to clamp.
+    pub fn clamp(&self, (x, y): (i32, i32)) -> (i32, i32) {
+        let (min, max) = self.bounds();
+        (x.clamp(min.0, max.0), y.clamp(min.1, max.1))
+    }
+}
+
+impl Default for CoordinateMapper {
+    fn default() -> Self {
+        CoordinateMapper {
+            origin: START_POS,
+            offset: (30, 33),
+            layout: Layout::default(),
+            margin: 12,
+            correction: PointerCorrection::default(),
+            path: PathStyle::default()
+        }
+    }
+}
+
+/// The delays used while tracing words.
+#[derive(Clone, Copy, Debug)]
+pub struct TimingProfile {
+    /// The delay after moving to the first tile.
+    pub start_delay: Duration,
+    /// The delay after a word has been traced.
+    pub word_delay: Duration,
+    /// The time the server takes to move back to the first tile.
+    pub reset_time: Duration,
+    /// The time the server waits on each point of a group.
+    pub point_time: Duration,
+    /// The time the server takes per step of a relative move.
+    pub step_time: Duration,
+    /// The most units the server moves per step.
+    pub step_size: u32
+}
+
+impl Default for TimingProfile {
+    fn default() -> Self {
+        TimingProfile {
+            start_delay: Duration::from_millis(50),
+            word_delay: Duration::from_millis(100),
+            reset_time: Duration::from_millis(150),
+            point_time: Duration::from_millis(70),
+            step_time: Duration::from_millis(20),
+            step_size: 127
+        }
+    }
+}
+
+/// A planned trace for a single word.
+#[derive(Clone, Debug, Serialize)]
+pub struct Trace {
+    /// The word being traced.
+    pub word: String,
+    /// The mouse deltas to move through, starting at the first tile.
+    pub points: Vec<(i32, i32)>,
+    /// The delay before tracing.
+    pub start_delay: Duration,
+    /// The delay after tracing.
+    pub end_delay: Duration,
+    /// The points awarded for the word.
+    pub score: u32
+}
+
+/// Plans the mouse movement needed to trace a word.
+/// The mouse is expected to start on the first tile of the board.
+/// word: The word to trace.
+/// mapper: The grid to mouse coordinate mapper.
+/// timing: The timing profile to use.
+#[tracing::instrument(skip_all, fields(word = %word.word))]
+pub fn plan_word(word: &Word, mapper: &CoordinateMapper, timing: &TimingProfile) -> Trace {
+    let mut rng = rand::thread_rng();
+    let limit = mapper.offset.0.min(mapper.offset.1) as f64 / 4.0;
+    let mut current = mapper.origin;
+    let mut points: Vec<(i32, i32)> = Vec::new();
+    let path = mapper.path.fit_group(word.characters.len());
+
+    // Calculate the points to move to, keeping them on the board.
+    // The move to the first tile is straight, since nothing is held yet.
+    for (index, pos) in word.positions().enumerate() {
+        let target = mapper.path.jitter_point(mapper.position(pos), limit, &mut rng);
+        let target = mapper.clamp(target);
+        let path = if index == 0 {
+            vec![target]
+        } else {
+            path.curve_points(current, target, &mut rng)
+        };
+
+        for point in path {
+            let point = mapper.clamp(point);
+            points.push(mapper.correction.apply((point.0 - current.0, point.1 - current.1)));
+            current = point;
+        }
+    }
+
+    Trace {
+        word: word.word.clone(),
+        points,
+        start_delay: timing.start_delay,
+        end_delay: timing.word_delay,
+        score: word.score
+    }
+}
+
+/// Estimates how long a trace takes to perform, including delays.
+/// trace: The trace to estimate.
+/// timing: The timing profile to use.
+pub fn estimate_duration(trace: &Trace, timing: &TimingProfile) -> Duration {
+    timing.reset_time + trace.start_delay + trace.end_delay + trace_time(&trace.points, timing)
+}
+
+/// Estimates how long the server takes to move through a list of points, without delays.
+/// Each point costs the steps needed to cover its distance, and the time waited on it.
+/// points: The mouse deltas to move through.
+/// timing: The timing profile to use.
+pub fn trace_time(points: &[(i32, i32)], timing: &TimingProfile) -> Duration {
+    let mut duration = Duration::ZERO;
+
+    for (dx, dy) in points {
+        // The server moves a limited number of units per step.
+        let steps = dx.unsigned_abs().max(dy.unsigned_abs()).div_ceil(timing.step_size.max(1));
+        duration += timing.step_time * steps + timing.point_time;
+    }
+
+    duration
+}
+
+/// Estimates how long a word's path takes to trace, without delays.
+/// Unlike a planned trace, the path is straight between tile centers, so the estimate is repeatable.
+/// word: The word to estimate.
+/// mapper: The grid to mouse coordinate mapper.
+/// timing: The timing profile to use.
+pub fn path_time(word: &Word, mapper: &CoordinateMapper, timing: &TimingProfile) -> Duration {
+    let mut current = mapper.origin;
+    let points: Vec<(i32, i32)> = word.positions()
+        .map(|pos| {
+            let point = mapper.position(pos);
+            let delta = mapper.correction.apply((point.0 - current.0, point.1 - current.1));
+            current = point;
+            delta
+        })
+        .collect();
+
+    trace_time(&points, timing)
+}
+
+#[cfg(test)]
+mod test {
+    use crate::board::{GridPos, Layout, BOARD_PADDING, BOARD_TOP, TILE_GAP, TILE_SIZE};
+    use std::time::Duration;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+    use crate::planner::{estimate_duration, path_time, plan_word, CoordinateMapper, PathStyle, PointerCorrection, TimingProfile};
+    use crate::solver::word;
+
+    #[test]
+    pub fn maps_tiles_to_mouse() {
+        let mapper = CoordinateMapper::default();
+
+        assert_eq!(mapper.position(GridPos::new(0, 0)), mapper.origin);
+        assert_eq!(mapper.position(GridPos::new(1, 2)), (mapper.origin.0 + 60, mapper.origin.1 + 33));
+        assert_eq!(mapper.position(GridPos::new(3, 3)), (mapper.origin.0 + 90, mapper.origin.1 + 99));
+
+        // Tiles on a 5x5 board are closer together.
+        let mapper = CoordinateMapper::new(Layout::new(5, 5));
+        assert_eq!(mapper.offset, (24, 26));
+        assert_eq!(mapper.bounds().1, (mapper.origin.0 + 96 + 12, mapper.origin.1 + 104 + 12));
+    }
+
+    #[test]
+    pub fn keeps_points_on_board() {
+        let mapper = CoordinateMapper::default();
+        let timing = TimingProfile::default();
+        let (min, max) = mapper.bounds();
+
+        // Trace every tile, then a position past the last row and column.
+        let mut tiles: Vec<(usize, usize)> = (0..16).map(|i| (i / 4, i % 4)).collect();
+        tiles.push((5, 6));
+
+        let text = "a".repeat(tiles.len());
+        let trace = plan_word(&word(&text, &tiles), &mapper, &timing);
+
+        let mut position = mapper.origin;
+        for (dx, dy) in &trace.points {
+            position = (position.0 + dx, position.1 + dy);
+            assert!(position.0 >= min.0 && position.0 <= max.0);
+            assert!(position.1 >= min.1 && position.1 <= max.1);
+        }
+        assert_eq!(position, max);
+    }
+
+    #[test]
+    pub fn maps_pixels_to_mouse() {
+        let mapper = CoordinateMapper::default();
+        let center = |row: u32, col: u32| (
+            BOARD_TOP.0 + BOARD_PADDING + TILE_SIZE / 2 + col * (TILE_SIZE + TILE_GAP),
+            BOARD_TOP.1 + BOARD_PADDING + TILE_SIZE / 2 + row * (TILE_SIZE + TILE_GAP)
+        );
+
+        assert_eq!(mapper.pixel_position(center(0, 0)), mapper.origin);
+        assert_eq!(mapper.pixel_position(center(1, 2)), mapper.position(GridPos::new(1, 2)));
+
+        // Mouse coordinates map back onto the same pixels.
+        let (x, y) = center(1, 2);
+        assert_eq!(mapper.frame_pixel(mapper.position(GridPos::new(1, 2))), (x as i32, y as i32));
+    }
+
+    #[test]
+    pub fn plans_relative_points() {
+        let mapper = CoordinateMapper::default();
+        let timing = TimingProfile::default();
+
+        let trace = plan_word(&word("cat", &[(1, 1), (1, 2), (2, 2)]), &mapper, &timing);
+
+        assert_eq!(trace.word, "cat");
+        assert_eq!(trace.points, vec![(30, 33), (30, 0), (0, 33)]);
+        assert_eq!(trace.end_delay, timing.word_delay);
+        assert_eq!(trace.score, 100);
+    }
+
+    #[test]
+    pub fn estimates_duration() {
+        let mapper = CoordinateMapper::default();
+        let timing = TimingProfile {
+            start_delay: Duration::from_millis(50),
+            word_delay: Duration::from_millis(100),
+            reset_time: Duration::from_millis(150),
+            point_time: Duration::from_millis(70),
+            step_time: Duration::from_millis(20),
+            step_size: 127
+        };
+
+        // Every point is within 127 units, so each is a single step.
+        let trace = plan_word(&word("cat", &[(1, 2), (1, 3), (2, 3)]), &mapper, &timing);
+        assert_eq!(estimate_duration(&trace, &timing), Duration::from_millis(300 + 3 * 90));
+
+        // A path with longer jumps takes more steps.
+        let timing = TimingProfile { step_size: 30, ..timing };
+        let near = path_time(&word("cat", &[(0, 0), (0, 1), (0, 2)]), &mapper, &timing);
+        let far = path_time(&word("cat", &[(0, 0), (1, 1), (2, 2)]), &mapper, &timing);
+        assert_eq!(near, Duration::from_millis(70 + 2 * 90));
+        assert_eq!(far, Duration::from_millis(70 + 2 * 110));
+    }
+
+    #[test]
+    pub fn corrects_pointer_acceleration() {
+        assert_eq!(PointerCorrection::default().apply((30, -33)), (30, -33));
+
+        // A mirror which moves the pointer 1.5x as far needs shorter moves.
+        let correction = PointerCorrection { gain: (1.5, 1.0), curve: 1.0 };
+        assert_eq!(correction.apply((30, -33)), (20, -33));
+
+        // An accelerated pointer needs longer moves shortened more.
+        let correction = PointerCorrection { gain: (1.0, 1.0), curve: 1.2 };
+        let (short, long) = (correction.apply((10, 0)).0, correction.apply((100, 0)).0);
+        assert!(short as f64 / 10.0 > long as f64 / 100.0);
+
+        let mapper = CoordinateMapper { correction, ..CoordinateMapper::default() };
+        let trace = plan_word(&word("at", &[(0, 0), (0, 3)]), &mapper, &TimingProfile::default());
+        assert_eq!(trace.points, vec![(0, 0), correction.apply((90, 0))]);
+    }
+
+    #[test]
+    pub fn curves_paths_between_tiles() {
+        let mut rng = StdRng::seed_from_u64(7);
+        assert_eq!(PathStyle::default().curve_points((0, 0), (30, 0), &mut rng), vec![(30, 0)]);
+
+        // The points bow away from the straight line, but no further than the curve allows.
+        let style = PathStyle { points: 4, curve: 0.2, ..PathStyle::default() };
+        let points = style.curve_points((0, 0), (30, 0), &mut rng);
+        assert_eq!(points.len(), 5);
+        assert_eq!(points.last(), Some(&(30, 0)));
+        assert!(points.windows(2).all(|pair| pair[1].0 > pair[0].0));
+        assert!(points.iter().all(|(_, y)| y.abs() <= 6));
+
+        // The word still ends on its last tile.
+        let mapper = CoordinateMapper { path: style, ..CoordinateMapper::default() };
+        let trace = plan_word(&word("cat", &[(1, 1), (1, 2), (2, 2)]), &mapper, &TimingProfile::default());
+        assert_eq!(trace.points.len(), 1 + 2 * 5);
+        let end = trace.points.iter().fold((0, 0), |end, (dx, dy)| (end.0 + dx, end.1 + dy));
+        assert_eq!(end, (60, 66));
+
+        // Long words get fewer points along each move, so the word fits in one group move.
+        let style = PathStyle { points: 8, ..style };
+        assert_eq!(style.fit_group(3).points, 8);
+        assert_eq!(style.fit_group(8).points, 7);
+        assert_eq!(style.fit_group(16).points, 3);
+        assert_eq!(style.fit_group(70).points, 0);
+
+        // Jittered points stay within a quarter of the tile spacing.
+        let style = PathStyle { jitter: 20.0, ..PathStyle::default() };
+        for _ in 0..20 {
+            let (x, y) = style.jitter_point((100, 100), 7.5, &mut rng);
+            assert!((x - 100).abs() <= 8 && (y - 100).abs() <= 8);
+        }
+        assert_eq!(PathStyle::default().jitter_point((100, 100), 7.5, &mut rng), (100, 100));
+    }
+}
@@ -0,0 +1,126 @@
+use anyhow::anyhow;
+use image::imageops::FilterType;
+use image::{Rgba, RgbaImage};
+
+/// A step applied to every frame right after capture.
+/// Steps are configured as a list separated by semicolons.
+/// (e.g. "crop 0 40 0 0; corners 48; scale")
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Preprocess {
+    /// Removes margins from each edge. ("crop <left> <top> <right> <bottom>")
+    Crop { left: u32, top: u32, right: u32, bottom: u32 },
+    /// Paints rounded screen corners white. ("corners <radius>")
+    Corners { radius: u32 },
+    /// Scales the frame to the configured screen size. ("scale")
+    Scale
+}
+
+impl Preprocess {
+    /// Parses a list of steps from the configuration.
+    /// steps: The configured steps.
+    pub fn parse_all(steps: &str) -> anyhow::Result<Vec<Self>> {
+        steps.split(';')
+            .map(str::trim)
+            .filter(|step| !step.is_empty())
+            .map(Self::parse)
+            .collect()
+    }
+
+    /// Parses a single step.
+    /// step: The step, as its name followed by its arguments.
+    pub fn parse(step: &str) -> anyhow::Result<Self> {
+        let mut parts = step.split_whitespace();
+        let name = parts.next().unwrap_or_default().to_lowercase();
+        let args = parts
+            .map(str::parse)
+            .collect::<Result<Vec<u32>, _>>()
+            .map_err(|_| anyhow!("Invalid arguments in preprocessing step: {}", step))?;
+
+        match (name.as_str(), args.as_slice()) {
+            ("crop", [left, top, right, bottom]) => Ok(Preprocess::Crop {
+                left: *left, top: *top, right: *right, bottom: *bottom
+            }),
+            ("corners", [radius]) => Ok(Preprocess::Corners { radius: *radius }),
+            ("scale", []) => Ok(Preprocess::Scale),
+            _ => Err(anyhow!("Unknown preprocessing step: {}", step))
+        }
+    }
+
+    /// Applies this step to a frame.
+    /// image: The frame to process.
+    /// size: The canonical (width, height) of a frame.
+    pub fn apply(&self, image: RgbaImage, size: (u32, u32)) -> RgbaImage {
+        match *self {
+            Preprocess::Crop { left, top, right, bottom } => {
+                let width = image.width().saturating_sub(left + right);
+                let height = image.height().saturating_sub(top + bottom);
+                image::imageops::crop_imm(&image, left, top, width, height).to_image()
+            },
+            Preprocess::Corners { radius } => remove_corners(image, radius),
+            Preprocess::Scale if image.dimensions() == size => image,
+            Preprocess::Scale => image::imageops::resize(&image, size.0, size.1, FilterType::Triangle)
+        }
+    }
+}
+
+/// Applies a list of steps to a frame, in order.
+/// image: The frame to process.
+/// steps: The steps to apply.
+/// size: The canonical (width, height) of a frame.
+pub fn apply_all(image: RgbaImage, steps: &[Preprocess], size: (u32, u32)) -> RgbaImage {
+    steps.iter().fold(image, |image, step| step.apply(image, size))
+}
+
+/// Paints the pixels outside of rounded corners white.
+/// Dark corners would otherwise be read as letter pixels.
+/// image: The frame to process.
+/// radius: The radius of the corners.
+fn remove_corners(mut image: RgbaImage, radius: u32) -> RgbaImage {
+    let (width, height) = image.dimensions();
+    let radius = radius.min(width / 2).min(height / 2);
+
+    for y in 0..radius {
+        for x in 0..radius {
+            // The distance from the corner's center, measured at the pixel's center.
+            let dx = radius as f64 - x as f64 - 0.5;
+            let dy = radius as f64 - y as f64 - 0.5;
+            if dx * dx + dy * dy <= (radius * radius) as f64 {
+                continue;
+            }
+
+            for (px, py) in [(x, y), (width - 1 - x, y), (x, height - 1 - y), (width - 1 - x, height - 1 - y)] {
+                image.put_pixel(px, py, Rgba([255, 255, 255, 255]));
+            }
+        }
+    }
+
+    image
+}
+
+#[cfg(test)]
+mod test {
+    use image::{Rgba, RgbaImage};
+    use crate::preprocess::{apply_all, Preprocess};
+
+    #[test]
+    pub fn preprocesses_frames() {
+        let steps = Preprocess::parse_all("crop 1 2 3 4; corners 4 ;scale").unwrap();
+        assert_eq!(steps, vec![
+            Preprocess::Crop { left: 1, top: 2, right: 3, bottom: 4 },
+            Preprocess::Corners { radius: 4 },
+            Preprocess::Scale
+        ]);
+        assert!(Preprocess::parse_all("crop 1 2").is_err());
+
+        let image = RgbaImage::from_pixel(24, 26, Rgba([0, 0, 0, 255]));
+        let image = apply_all(image, &steps[..2], (10, 10));
+
+        assert_eq!(image.dimensions(), (20, 20));
+        assert_eq!(image.get_pixel(0, 0).0, [255, 255, 255, 255]);
+        assert_eq!(image.get_pixel(19, 19).0, [255, 255, 255, 255]);
+        assert_eq!(image.get_pixel(1, 1).0, [0, 0, 0, 255]);
+
+        let image = apply_all(image, &steps[2..], (10, 10));
+        assert_eq!(image.dimensions(), (10, 10));
+    }
+}
@@ -0,0 +1,126 @@
+use std::time::Instant;
+use serde::{Deserialize, Serialize};
+use crate::planner::Trace;
+
+/// How far apart two points can be before they count as divergent, in mouse units.
+/// Points are jittered, so traces of the same plan never match exactly.
+pub const DIFF_TOLERANCE: i32 = 8;
+
+/// A trace which was performed during a round.
+/// Both its points and its timestamp are stored as deltas, to keep recordings small.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct RecordedTrace {
+    /// The word traced.
+    pub word: String,
+    /// The milliseconds since the previous trace started. (since the recording started, for the first)
+    pub delay: u64,
+    /// The milliseconds the trace took.
+    pub duration: u64,
+    /// The mouse deltas requested, each relative to the last, starting at the mapper's origin.
+    /// These are corrected for pointer acceleration, so they're in requested units rather than on-screen ones.
+    pub points: Vec<(i32, i32)>
+}
+
+impl RecordedTrace {
+    /// Returns the points of the trace, relative to the mapper's origin. (the board's first tile, not the word's)
+    /// Like the deltas, these are in requested units, corrected for pointer acceleration.
+    pub fn positions(&self) -> Vec<(i32, i32)> {
+        let mut current = (0, 0);
+        self.points.iter()
+            .map(|(dx, dy)| {
+                current = (current.0 + dx, current.1 + dy);
+                current
+            })
+            .collect()
+    }
+}
+
+/// The traces performed during a round, in order.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct TraceRecording {
+    /// The board which was traced.
+    pub board: Vec<Vec<String>>,
+    /// The traces performed.
+    pub traces: Vec<RecordedTrace>,
+
+    // When the last trace started.
+    #[serde(skip, default = "Instant::now")]
+    last: Instant
+}
+
+/// A point where two traces of the same word diverge.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct PointDiff {
+    /// The point's index in the traces.
+    pub index: usize,
+    /// The point in the first trace, relative to the mapper's origin. (None if it's shorter)
+    pub left: Option<(i32, i32)>,
+    /// The point in the second trace, relative to the mapper's origin. (None if it's shorter)
+    pub right: Option<(i32, i32)>
+}
+
+impl TraceRecording {
+    /// Starts recording the traces of a round.
+    /// board: The board being traced.
+    pub fn new(board: Vec<Vec<String>>) -> Self {
+        TraceRecording { board, traces: Vec::new(), last: Instant::now() }
+    }
+
+    /// Loads a recording from a file.
+    /// path: The path to the recording.
+    pub fn load(path: &str) -> anyhow::Result<Self> {
+        Ok(serde_json::from_str(&std::fs::read_to_string(path)?)?)
+    }
+
+    /// Records a trace which was just performed.
+    /// trace: The trace performed.
+    /// started: When the trace started.
+    pub fn record(&mut self, trace: &Trace, started: Instant) {
+        self.traces.push(RecordedTrace {
+            word: trace.word.clone(),
+            delay: started.saturating_duration_since(self.last).as_millis() as u64,
+            duration: started.elapsed().as_millis() as u64,
+            points: trace.points.clone()
+        });
+        self.last = started;
+    }
+}
+
+/// Finds the points where two traces diverge by more than the tolerance.
+/// Points past the end of the shorter trace always diverge.
+/// left: The first trace.
+/// right: The second trace.
+/// tolerance: The furthest apart matching points can be, on either axis.
+pub fn diff_points(left: &RecordedTrace, right: &RecordedTrace, tolerance: i32) -> Vec<PointDiff> {
+    let (left, right) = (left.positions(), right.positions());
+
+    (0..left.len().max(right.len()))
+        .map(|index| PointDiff { index, left: left.get(index).copied(), right: right.get(index).copied() })
+        .filter(|diff| match (diff.left, diff.right) {
+            (Some(a), Some(b)) => (a.0 - b.0).abs() > tolerance || (a.1 - b.1).abs() > tolerance,
+            _ => true
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use crate::recording::{diff_points, PointDiff, RecordedTrace};
+
+    #[test]
+    pub fn diffs_traces() {
+        let trace = |points: Vec<(i32, i32)>| RecordedTrace { word: "cat".to_string(), delay: 0, duration: 0, points };
+
+        let left = trace(vec![(0, 0), (30, 0), (0, 33)]);
+        assert_eq!(left.positions(), vec![(0, 0), (30, 0), (30, 33)]);
+
+        // Small jitter is within the tolerance, but a drift carries into every later point.
+        let right = trace(vec![(2, -3), (40, 0), (0, 33), (30, 0)]);
+        assert_eq!(diff_points(&left, &right, 8), vec![
+            PointDiff { index: 1, left: Some((30, 0)), right: Some((42, -3)) },
+            PointDiff { index: 2, left: Some((30, 33)), right: Some((42, 30)) },
+            PointDiff { index: 3, left: None, right: Some((72, 30)) }
+        ]);
+        assert!(diff_points(&left, &left, 0).is_empty());
+    }
+}
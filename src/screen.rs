@@ -3,12 +3,15 @@ use std::iter::once;
 use std::thread::sleep;
 use std::time::Duration;
 use image::{DynamicImage, RgbImage, RgbaImage};
-use log::{error, info};
+use log::{error, info, warn};
 use windows::core::PCWSTR;
-use windows::Win32::Foundation::{HWND, RECT};
+use windows::Win32::Foundation::{FALSE, HWND, POINT, RECT};
+use windows::Win32::Graphics::Gdi::{ClientToScreen, ScreenToClient};
 use windows::Win32::UI::WindowsAndMessaging::*;
 use xcap::Window;
-use crate::{board, LETTERS};
+use crate::board;
+use crate::board::{GridPos, Layout, TileGrid};
+use crate::letters::Letters;
 
 /// Takes a screenshot of the window at the coordinates.
 /// device_name: The name of the device to take a screenshot of. (window name)
@@ -25,29 +28,79 @@ pub fn take_screenshot<S: AsRef<str>>(device_name: S) -> anyhow::Result<RgbaImag
     Ok(window.capture_image()?)
 }
 
+/// Crops a screenshot of a window down to its client area.
+/// This removes the title bar and borders, which differ between systems.
+/// If the client area can't be found, the image is returned as-is.
+/// handle: The handle of the captured window.
+/// image: The screenshot of the window.
+pub unsafe fn crop_to_client(handle: HWND, image: RgbaImage) -> RgbaImage {
+    let mut window = RECT::default();
+    let mut client = RECT::default();
+    let mut origin = POINT::default();
+
+    if GetWindowRect(handle, &mut window).is_err() ||
+        GetClientRect(handle, &mut client).is_err() ||
+        !ClientToScreen(handle, &mut origin).as_bool() {
+        warn!("Failed to find the client area, using the full window.");
+        return image;
+    }
+
+    // Find the client area relative to the window.
+    let x = (origin.x - window.left).max(0) as u32;
+    let y = (origin.y - window.top).max(0) as u32;
+    let width = (client.right as u32).min(image.width().saturating_sub(x));
+    let height = (client.bottom as u32).min(image.height().saturating_sub(y));
+
+    image::imageops::crop_imm(&image, x, y, width, height).to_image()
+}
+
 /// Processes a bare-bones game board.
+/// Returns the rows of letters, with the similarity score of each.
 /// image: The image to process.
-pub fn process_board(image: &RgbImage) -> String {
-    // Lock the letters library.
-    let letters = LETTERS.read().unwrap();
+/// letters: The letters library to match tiles against.
+/// layout: The number of rows and columns on the board.
+pub fn process_board(image: &RgbImage, letters: &Letters, layout: Layout) -> Vec<Vec<(char, f64)>> {
+    process_candidates(image, letters, layout, 1)
+        .into_iter()
+        .map(|line| line.into_iter()
+            .map(|candidates| candidates.first().copied().unwrap_or((' ', 0f64)))
+            .collect())
+        .collect()
+}
 
-    // Split the image into a 4x4 grid.
-    let mut board = String::new();
+/// Processes the board into its closest matching letters for each tile.
+/// image: The image to process.
+/// letters: The letters library to match tiles against.
+/// layout: The number of rows and columns on the board.
+/// count: The number of candidates to keep for each tile.
+pub fn process_candidates(
+    image: &RgbImage, letters: &Letters,
+    layout: Layout, count: usize
+) -> Vec<Vec<Vec<(char, f64)>>> {
+    // Split the image into a grid.
+    let mut board = Vec::new();
 
-    // Crop the board into 4x4 tiles.
+    // Crop the board into its tiles, wherever they are this round.
+    let grid = TileGrid::find(image, layout);
     let image = DynamicImage::ImageRgb8(image.clone());
-    for row in 0..4 {
-        for column in 0..4 {
-            let image = board::crop_image(image.clone(), row, column);
+    for row in 0..layout.rows as u32 {
+        let mut line = Vec::new();
+        for column in 0..layout.cols as u32 {
+            // Holes are read as blank tiles, which are never uncertain.
+            if !layout.is_playable(GridPos::new(row as usize, column as usize)) {
+                line.push(vec![(' ', 1f64)]);
+                continue;
+            }
 
-            // Determine which letter matches the image.
-            let letter = letters.compare(&image);
-            board.push(letter);
+            let image = board::crop_image(image.clone(), &grid, row, column);
+
+            // Determine which letters match the image.
+            line.push(letters.candidates(&image, count));
         }
-        board.push('\n');
+        board.push(line);
     }
 
-    board.trim().to_string()
+    board
 }
 
 /// Finds the window handle by the name of the device.
@@ -76,19 +129,138 @@ pub unsafe fn get_window<S: AsRef<str> + Display>(device_name: S) -> HWND {
     }
 }
 
+/// Finds the cursor relative to a window.
+/// Returns the cursor's position and the window's size, or None if either is unavailable.
+/// handle: The handle of the window.
+/// client: Should the position be relative to the client area?
+pub unsafe fn cursor_position(handle: HWND, client: bool) -> Option<((i32, i32), (i32, i32))> {
+    let mut cursor = POINT::default();
+    GetCursorPos(&mut cursor).ok()?;
+
+    if client {
+        let mut rect = RECT::default();
+        GetClientRect(handle, &mut rect).ok()?;
+        if !ScreenToClient(handle, &mut cursor).as_bool() {
+            return None;
+        }
+
+        return Some(((cursor.x, cursor.y), (rect.right, rect.bottom)));
+    }
+
+    let mut rect = RECT::default();
+    GetWindowRect(handle, &mut rect).ok()?;
+    Some((
+        (cursor.x - rect.left, cursor.y - rect.top),
+        (rect.right - rect.left, rect.bottom - rect.top)
+    ))
+}
+
+/// Finds the size of a window.
+/// Returns None if the window's size is unavailable.
+/// handle: The handle of the window.
+/// client: Should the size be of the client area?
+pub unsafe fn window_size(handle: HWND, client: bool) -> Option<(i32, i32)> {
+    let mut rect = RECT::default();
+    if client {
+        GetClientRect(handle, &mut rect).ok()?;
+    } else {
+        GetWindowRect(handle, &mut rect).ok()?;
+    }
+
+    Some((rect.right - rect.left, rect.bottom - rect.top))
+}
+
+/// Converts a position in a window into desktop coordinates.
+/// Returns None if the window's position is unavailable.
+/// handle: The handle of the window.
+/// client: Is the position relative to the client area?
+/// position: The (x, y) position in the window.
+pub unsafe fn to_desktop(handle: HWND, client: bool, (x, y): (i32, i32)) -> Option<(i32, i32)> {
+    if client {
+        let mut point = POINT { x, y };
+        return ClientToScreen(handle, &mut point).as_bool().then_some((point.x, point.y));
+    }
+
+    let mut rect = RECT::default();
+    GetWindowRect(handle, &mut rect).ok()?;
+    Some((rect.left + x, rect.top + y))
+}
+
+/// Moves the desktop cursor off of a window, so it isn't captured.
+/// Returns the cursor's previous position, to restore it afterwards.
+/// handle: The handle of the window.
+pub unsafe fn park_cursor(handle: HWND) -> Option<POINT> {
+    let mut cursor = POINT::default();
+    let mut rect = RECT::default();
+    GetCursorPos(&mut cursor).ok()?;
+    GetWindowRect(handle, &mut rect).ok()?;
+
+    // Only move the cursor if it's over the window.
+    let inside = cursor.x >= rect.left && cursor.x < rect.right &&
+        cursor.y >= rect.top && cursor.y < rect.bottom;
+    if !inside {
+        return None;
+    }
+
+    // Move just past the window's right edge.
+    SetCursorPos(rect.right + 1, cursor.y).ok()?;
+    Some(cursor)
+}
+
+/// Moves the desktop cursor back after it was parked.
+/// cursor: The position returned by `park_cursor`.
+pub unsafe fn restore_cursor(cursor: POINT) {
+    if let Err(error) = SetCursorPos(cursor.x, cursor.y) {
+        warn!("Failed to restore the cursor: {:?}", error);
+    }
+}
+
+/// Checks if a window is in front of every other window.
+/// Mouse input only reaches the window while it is.
+/// handle: The handle of the window.
+pub unsafe fn is_foreground(handle: HWND) -> bool {
+    GetForegroundWindow() == handle
+}
+
+/// Converts a client area size into the outer window size.
+/// handle: The handle of the window.
+/// width: The width of the client area.
+/// height: The height of the client area.
+unsafe fn outer_size(handle: HWND, width: i32, height: i32) -> (i32, i32) {
+    let style = WINDOW_STYLE(GetWindowLongW(handle, GWL_STYLE) as u32);
+    let ex_style = WINDOW_EX_STYLE(GetWindowLongW(handle, GWL_EXSTYLE) as u32);
+
+    let mut rect = RECT { left: 0, top: 0, right: width, bottom: height };
+    if let Err(error) = AdjustWindowRectEx(&mut rect, style, FALSE, ex_style) {
+        warn!("Failed to calculate the window size: {:?}", error);
+        return (width, height);
+    }
+
+    (rect.right - rect.left, rect.bottom - rect.top)
+}
+
 /// Sets the size of the window.
 /// handle: The handle of the window to resize.
 /// width: The new width of the window.
 /// height: The new height of the window.
+/// client: Are the width and height of the client area?
 pub unsafe fn set_size(
     handle: HWND,
     width: i32, height: i32,
-    (x, y): (i32, i32)
+    (x, y): (i32, i32),
+    client: bool
 ) {
+    // Convert the client area size into the window size.
+    let (width, height) = if client {
+        outer_size(handle, width, height)
+    } else {
+        (width, height)
+    };
+
     // Log the current window position if none was specified.
     if (x, y) == (0, 0) {
         let mut rect = RECT::default();
-        if let Ok(_) = GetWindowRect(handle, &mut rect) {
+        if GetWindowRect(handle, &mut rect).is_ok() {
             info!("Window is currently at ({}, {})", rect.left, rect.top);
         }
     }
@@ -105,3 +277,44 @@ pub unsafe fn set_size(
         std::process::exit(1);
     }
 }
+
+/// Checks the window's size and position, restoring them if they changed.
+/// Returns true if the window had to be corrected.
+/// handle: The handle of the window to check.
+/// width: The expected width of the window.
+/// height: The expected height of the window.
+/// (x, y): The expected position of the window. (0, 0) ignores the position.
+/// client: Are the width and height of the client area?
+pub unsafe fn enforce_size(
+    handle: HWND,
+    width: i32, height: i32,
+    (x, y): (i32, i32),
+    client: bool
+) -> bool {
+    let mut rect = RECT::default();
+    if GetWindowRect(handle, &mut rect).is_err() {
+        return false;
+    }
+
+    // Find the expected outer size of the window.
+    let expected = if client {
+        outer_size(handle, width, height)
+    } else {
+        (width, height)
+    };
+
+    // Compare the window against the configuration.
+    let (current_width, current_height) = (rect.right - rect.left, rect.bottom - rect.top);
+    let resized = (current_width, current_height) != expected;
+    let moved = (x, y) != (0, 0) && (rect.left, rect.top) != (x, y);
+
+    if !resized && !moved {
+        return false;
+    }
+
+    warn!("Window changed to {}x{} at ({}, {}), restoring it.",
+        current_width, current_height, rect.left, rect.top);
+    set_size(handle, width, height, (x, y), client);
+
+    true
+}
@@ -0,0 +1,192 @@
+use std::io::{Error, ErrorKind};
+use std::time::Duration;
+use async_trait::async_trait;
+use tokio::time::sleep;
+use windows::Win32::UI::Input::KeyboardAndMouse::*;
+use windows::Win32::UI::WindowsAndMessaging::*;
+use crate::config::Config;
+use crate::mouse::{Ack, Capabilities, Instruction, MouseBackend, Opcode, Status};
+use crate::planner::CoordinateMapper;
+use crate::screen;
+
+/// How long to wait on each point of a group by default. (the server's default)
+pub const DEFAULT_DWELL: Duration = Duration::from_millis(60);
+/// The most mouse units moved at once by default. (the server's default)
+pub const DEFAULT_STEP: i32 = 127;
+/// How long to wait between the steps of a move.
+pub const STEP_TIME: Duration = Duration::from_millis(20);
+/// How long to wait before pressing or releasing the button in a group.
+pub const PRESS_TIME: Duration = Duration::from_millis(10);
+
+/// Moves the desktop cursor over the mirrored device's window.
+/// Instructions are performed the same way as the mouse server's firmware,
+/// with mouse units mapped onto the window through the board's tile spacing.
+pub struct SendInputBackend {
+    /// The name of the mirrored device's window.
+    device_name: String,
+    /// Is the window's client area captured, rather than the whole window?
+    client_area: bool,
+    /// The size of a captured frame, which the window is scaled from.
+    frame: (i32, i32),
+    /// Converts mouse units into frame pixels.
+    mapper: CoordinateMapper,
+
+    // The cursor's position since the last normalize, in mouse units.
+    cursor: (i32, i32)
+}
+
+impl SendInputBackend {
+    /// Creates a backend for the configured window.
+    /// config: The application configuration.
+    pub fn new(config: &Config) -> Self {
+        SendInputBackend {
+            device_name: config.device_name.clone(),
+            client_area: config.client_area,
+            frame: (config.screen_width.max(1), config.screen_height.max(1)),
+            mapper: CoordinateMapper::new(config.layout()),
+            cursor: (0, 0)
+        }
+    }
+
+    /// Sends a mouse event at the cursor's position.
+    /// flags: The button events to send with the move.
+    /// data: The wheel's movement, for wheel events.
+    fn send_event(&self, flags: MOUSE_EVENT_FLAGS, data: i32) -> std::io::Result<()> {
+        // Scale the cursor from frame pixels onto the window.
+        let (x, y) = self.mapper.frame_pixel(self.cursor);
+        unsafe {
+            let handle = screen::get_window(&self.device_name);
+            let (width, height) = screen::window_size(handle, self.client_area)
+                .ok_or_else(|| Error::new(ErrorKind::NotFound, "Failed to find the window's size."))?;
+            let position = (x * width / self.frame.0, y * height / self.frame.1);
+
+            let (x, y) = screen::to_desktop(handle, self.client_area, position)
+                .ok_or_else(|| Error::new(ErrorKind::NotFound, "Failed to find the window's position."))?;
+
+            // Absolute positions are scaled to 0-65535 across the whole desktop.
+            let origin = (GetSystemMetrics(SM_XVIRTUALSCREEN), GetSystemMetrics(SM_YVIRTUALSCREEN));
+            let size = (GetSystemMetrics(SM_CXVIRTUALSCREEN).max(2), GetSystemMetrics(SM_CYVIRTUALSCREEN).max(2));
+            let normalize = |value: i32, origin: i32, size: i32| ((value - origin) as i64 * 65535 / (size - 1) as i64) as i32;
+
+            let input = INPUT {
+                r#type: INPUT_MOUSE,
+                Anonymous: INPUT_0 {
+                    mi: MOUSEINPUT {
+                        dx: normalize(x, origin.0, size.0),
+                        dy: normalize(y, origin.1, size.1),
+                        mouseData: data as u32,
+                        dwFlags: flags | MOUSEEVENTF_MOVE | MOUSEEVENTF_ABSOLUTE | MOUSEEVENTF_VIRTUALDESK,
+                        time: 0,
+                        dwExtraInfo: 0
+                    }
+                }
+            };
+
+            if SendInput(&[input], std::mem::size_of::<INPUT>() as i32) != 1 {
+                return Err(Error::last_os_error());
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Presses or releases the left mouse button.
+    /// pressed: Should the button be held?
+    fn set_button(&self, pressed: bool) -> std::io::Result<()> {
+        self.send_event(if pressed { MOUSEEVENTF_LEFTDOWN } else { MOUSEEVENTF_LEFTUP }, 0)
+    }
+
+    /// Presses and releases the right mouse button.
+    async fn right_click(&self) -> std::io::Result<()> {
+        self.send_event(MOUSEEVENTF_RIGHTDOWN, 0)?;
+        sleep(PRESS_TIME).await;
+        self.send_event(MOUSEEVENTF_RIGHTUP, 0)
+    }
+
+    /// Turns the wheel under the cursor.
+    /// notches: The notches to turn. Positive values scroll down, and negative values scroll up.
+    fn scroll(&self, notches: i32) -> std::io::Result<()> {
+        // A positive wheel delta scrolls up.
+        self.send_event(MOUSEEVENTF_WHEEL, -notches * WHEEL_DELTA as i32)
+    }
+
+    /// Moves the cursor relative to its position, a step at a time.
+    /// delta: The change in position.
+    /// step: The most units moved at once.
+    async fn move_by(&mut self, (dx, dy): (i32, i32), step: i32) -> std::io::Result<()> {
+        let (mut rx, mut ry) = (dx, dy);
+        while rx != 0 || ry != 0 {
+            let (sx, sy) = (rx.clamp(-step, step), ry.clamp(-step, step));
+            self.cursor = (self.cursor.0 + sx, self.cursor.1 + sy);
+            self.send_event(MOUSE_EVENT_FLAGS(0), 0)?;
+
+            rx -= sx;
+            ry -= sy;
+            sleep(STEP_TIME).await;
+        }
+
+        Ok(())
+    }
+
+    /// Moves through a group of points, holding the button after the first.
+    /// group: The points, relative to each other.
+    /// (dwell, step): The milliseconds to wait on each point, and the most units moved at once.
+    async fn move_group(&mut self, group: &[(i32, i32)], (dwell, step): (i32, i32)) -> std::io::Result<()> {
+        let dwell = if dwell > 0 { Duration::from_millis(dwell as u64) } else { DEFAULT_DWELL };
+        let step = if step > 0 { step.min(DEFAULT_STEP) } else { DEFAULT_STEP };
+
+        for (i, point) in group.iter().enumerate() {
+            self.move_by(*point, step).await?;
+
+            // The button is pressed once the first point is reached.
+            if i == 0 {
+                sleep(PRESS_TIME).await;
+                self.set_button(true)?;
+            }
+            sleep(dwell).await;
+        }
+
+        sleep(PRESS_TIME).await;
+        self.set_button(false)
+    }
+}
+
+#[async_trait]
+impl MouseBackend for SendInputBackend {
+    async fn transmit(&mut self, instruction: &Instruction, sequence: u32) -> std::io::Result<Ack> {
+        let position = instruction.position.unwrap_or_default();
+
+        match instruction.opcode {
+            Opcode::Hello => {
+                // Every opcode is performed locally.
+                let capabilities = Capabilities::all().as_position();
+                return Ok(Ack { status: Status::Ok, sequence: Some(sequence), position: Some(capabilities) });
+            },
+            Opcode::LeftDown => self.set_button(true)?,
+            Opcode::LeftUp => self.set_button(false)?,
+            Opcode::Move => self.move_by(position, DEFAULT_STEP).await?,
+            Opcode::Normalize => {
+                self.cursor = (0, 0);
+                self.send_event(MOUSE_EVENT_FLAGS(0), 0)?;
+            },
+            Opcode::NormalMove => {
+                self.cursor = position;
+                self.send_event(MOUSE_EVENT_FLAGS(0), 0)?;
+            },
+            Opcode::MoveGroup => {
+                let group = instruction.group.as_deref().unwrap_or_default();
+                self.move_group(group, position).await?;
+            },
+            Opcode::ScrollUp => self.scroll(-position.0)?,
+            Opcode::ScrollDown => self.scroll(position.0)?,
+            Opcode::RightClick => self.right_click().await?
+        }
+
+        Ok(Ack { status: Status::Ok, sequence: Some(sequence), position: Some(self.cursor) })
+    }
+
+    async fn reconnect(&mut self) -> std::io::Result<()> {
+        // Desktop input has no connection, so the button is released to start over.
+        self.set_button(false)
+    }
+}
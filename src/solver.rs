@@ -1,24 +1,46 @@
 use std::cmp::Ordering;
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use log::warn;
+use rayon::prelude::*;
 use crate::DICTIONARY;
+use crate::board::{GridPos, MAX_SIDE};
+use crate::dictionary::{Cursor, Dictionary};
+use crate::planner::{self, CoordinateMapper, TimingProfile};
 
-/// All valid directions for locating adjacent characters.
+/// All valid (row, column) directions for locating adjacent characters.
 const DIRECTIONS: [(i32, i32); 8] = [
     (-1, -1), (-1, 0), (-1, 1),
     (0, -1),           (0, 1),
     (1, -1),  (1, 0),  (1, 1),
 ];
 
+/// The tile text of a wildcard (blank) tile, which can stand for any letter.
+pub const WILDCARD: &str = "?";
+
+/// The letters in the shortest and longest easy words.
+pub const EASY_LENGTH: (usize, usize) = (3, 4);
+/// The most changes of direction along an easy word's path.
+pub const EASY_MAX_TURNS: usize = 1;
+
 /// A word found on the game board.
 #[derive(Clone, Debug)]
 #[derive(Eq, Hash, PartialEq)]
 pub struct Word {
     /// The word itself.
     pub word: String,
-    /// The characters that make up the word.
-    /// These are paired in X, Y coordinates.
+    /// The indices of the tiles that make up the word.
     /// They follow the order of the word.
-    pub characters: Vec<(usize, usize)>
+    pub characters: Vec<u8>,
+    /// How common the word is, according to the dictionary.
+    pub frequency: u32,
+    /// The points awarded for the word.
+    pub score: u32,
+    /// Was the word found under every reading of the board?
+    pub safe: bool,
+    /// The letters assumed for wildcard tiles, by tile index.
+    pub assumed: Vec<(u8, char)>
 }
 
 impl Word {
@@ -26,13 +48,18 @@ impl Word {
     pub fn new() -> Self {
         Word {
             word: String::new(),
-            characters: Vec::new()
+            characters: Vec::new(),
+            frequency: 0,
+            score: 0,
+            safe: false,
+            assumed: Vec::new()
         }
     }
     
-    /// Returns the length of the word.
+    /// Returns the number of letters in the word.
+    /// A "qu" tile counts as two letters, like in Boggle.
     pub fn len(&self) -> usize {
-        self.word.len()
+        self.word.chars().count()
     }
 
     /// Compares this word with the given.
@@ -43,105 +70,582 @@ impl Word {
 
     /// Appends a character to the word.
     /// char: The character to append.
-    /// x: The X coordinate of the character.
-    /// y: The Y coordinate of the character.
-    pub fn append(&mut self, char: &String, x: usize, y: usize) {
+    /// pos: The position of the character's tile.
+    pub fn append(&mut self, char: &str, pos: GridPos) {
         self.word.push_str(char);
-        self.characters.push((x, y));
+        self.characters.push(pos.index());
+        self.score = points(self.len());
+    }
+
+    /// Appends a wildcard tile to the word, standing for the given letter.
+    /// letter: The letter the wildcard is assumed to be.
+    /// pos: The position of the wildcard's tile.
+    pub fn assume(&mut self, letter: char, pos: GridPos) {
+        self.append(&letter.to_string(), pos);
+        self.assumed.push((pos.index(), letter));
     }
     
-    /// Truncates the word to a given length.
-    pub fn truncate(&mut self, len: usize) {
-        self.word.truncate(len);
-        self.characters.truncate(len);
+    /// Describes the letters assumed for wildcard tiles, like "?=a ?=e".
+    /// This is empty if the word has no wildcards.
+    pub fn assumptions(&self) -> String {
+        self.assumed.iter()
+            .map(|(_, letter)| format!("{}={}", WILDCARD, letter))
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+
+    /// Returns the positions of the tiles that make up the word.
+    pub fn positions(&self) -> impl Iterator<Item = GridPos> + '_ {
+        self.characters.iter().map(|index| GridPos::from_index(*index))
+    }
+
+    /// Counts the changes of direction along the word's path.
+    /// A path in a straight line has none.
+    pub fn turns(&self) -> usize {
+        let steps: Vec<(i32, i32)> = self.positions()
+            .zip(self.positions().skip(1))
+            .map(|(from, to)| (to.row as i32 - from.row as i32, to.col as i32 - from.col as i32))
+            .collect();
+
+        steps.windows(2).filter(|pair| pair[0] != pair[1]).count()
+    }
+
+    /// Checks if the word is short, with a path that's close to straight.
+    /// These are quick to trace, and unlikely to go wrong.
+    pub fn is_easy(&self) -> bool {
+        (EASY_LENGTH.0..=EASY_LENGTH.1).contains(&self.len()) && self.turns() <= EASY_MAX_TURNS
+    }
+
+    /// Removes the last tile from the word.
+    /// Tiles can hold more than one letter, so the tile's letters are needed.
+    /// tile: The letters on the removed tile. (the assumed letter for a wildcard)
+    pub fn pop(&mut self, tile: &str) {
+        debug_assert!(self.word.ends_with(tile));
+        self.word.truncate(self.word.len() - tile.len());
+
+        // Forget the assumed letter if the tile was a wildcard.
+        let index = self.characters.pop();
+        if index.is_some() && self.assumed.last().map(|(tile, _)| *tile) == index {
+            self.assumed.pop();
+        }
+
+        self.score = points(self.len());
+    }
+}
+
+/// Options which limit a board solve.
+#[derive(Clone, Debug, Default)]
+pub struct SolveOptions {
+    /// Stop searching at this time, keeping the words found so far.
+    pub deadline: Option<Instant>,
+    /// The tiles which can't be used, as a mask of tile indices.
+    /// These are holes in shaped boards, or tiles which are occluded or misread.
+    pub excluded: u64,
+    /// The trace-time model used to choose between paths which spell the same word.
+    /// Without one, the path which comes first in tile order is kept.
+    pub trace_model: Option<(CoordinateMapper, TimingProfile)>
+}
+
+impl SolveOptions {
+    /// Marks tiles as unusable, in addition to any already excluded.
+    /// tiles: The positions of the tiles.
+    pub fn exclude(mut self, tiles: &[GridPos]) -> Self {
+        for tile in tiles {
+            self.excluded |= 1 << tile.index();
+        }
+
+        self
+    }
+
+    /// Checks if the deadline has passed.
+    pub fn expired(&self) -> bool {
+        self.deadline.is_some_and(|deadline| Instant::now() >= deadline)
+    }
+
+    /// Estimates how long a word's path takes to trace, under the trace-time model.
+    /// This is zero without a model.
+    /// word: The word to estimate.
+    pub fn path_time(&self, word: &Word) -> Duration {
+        self.trace_model.as_ref()
+            .map_or(Duration::ZERO, |(mapper, timing)| planner::path_time(word, mapper, timing))
+    }
+}
+
+/// Returns the points awarded for a word of the given length.
+/// length: The number of letters in the word.
+pub fn points(length: usize) -> u32 {
+    match length {
+        0..=2 => 0,
+        3 => 100,
+        4 => 400,
+        5 => 800,
+        6 => 1400,
+        // Every letter after 6 is worth another 400 points.
+        _ => 1400 + (length as u32 - 6) * 400
+    }
+}
+
+/// Sorts words by the highest score, preferring more common words.
+/// words: The words to sort.
+pub fn sort_words(words: &mut [Word]) {
+    words.sort_by(|a, b| {
+        b.score.cmp(&a.score)
+            .then(b.frequency.cmp(&a.frequency))
+            .then(a.cmp(b))
+    });
+}
+
+/// Solves boards against a single dictionary.
+/// Cloning is cheap, since the dictionary is shared.
+#[derive(Clone)]
+pub struct Solver {
+    /// The dictionary which words are checked against.
+    dictionary: Arc<dyn Dictionary>
+}
+
+impl Solver {
+    /// Creates a solver for a dictionary.
+    /// dictionary: The dictionary to check words against.
+    pub fn new(dictionary: Arc<dyn Dictionary>) -> Self {
+        Self { dictionary }
+    }
+
+    /// Creates a solver for the loaded dictionary.
+    /// The dictionary is taken once, so a later switch doesn't affect this solver.
+    pub fn global() -> Self {
+        Self::new(DICTIONARY.read().unwrap().clone())
+    }
+
+    /// Finds all valid words in a 2D board.
+    /// board: The game board.
+    /// excluded: The tiles which can't be used.
+    pub fn find_all_words(&self, board: &[Vec<String>], excluded: &[GridPos]) -> Vec<Word> {
+        self.find_words(board, &SolveOptions::default().exclude(excluded))
+    }
+
+    /// Finds the valid words in a 2D board, within the given limits.
+    /// If the deadline passes, only the words found so far are returned.
+    /// Boards larger than the maximum size have no words.
+    /// board: The game board.
+    /// options: The limits of the solve.
+    #[tracing::instrument(skip_all)]
+    pub fn find_words(&self, board: &[Vec<String>], options: &SolveOptions) -> Vec<Word> {
+        let rows = board.len();
+        let cols = board.first().map_or(0, Vec::len);
+
+        // Tiles are tracked in a 64-bit mask, so larger boards can't be searched.
+        if rows > MAX_SIDE || cols > MAX_SIDE {
+            warn!("The board is {}x{}, which is larger than {}x{}. It won't be solved.", rows, cols, MAX_SIDE, MAX_SIDE);
+            return Vec::new();
+        }
+
+        let Some(root) = self.dictionary.root() else {
+            return Vec::new();
+        };
+
+        // Search from each starting tile in parallel, then merge the words.
+        let starts: Vec<(usize, usize)> = (0..rows)
+            .flat_map(|row| (0..cols).map(move |col| (row, col)))
+            .collect();
+        let words = starts.into_par_iter()
+            .map(|(row, col)| {
+                let mut search = Search::new(board, &*self.dictionary, options);
+                search.visit(row, col, root);
+                search.words
+            })
+            .reduce(HashSet::new, |mut words, other| {
+                words.extend(other);
+                words
+            });
+
+        finish_words(words, options)
+    }
+
+    /// Finds the valid words which can be made from a rack of letters.
+    /// Unlike a board, any unused letter can follow any other.
+    /// Each word's positions are the rack indices of its letters, in row 0.
+    /// rack: The letters in the rack.
+    /// options: The limits of the solve.
+    pub fn find_anagrams(&self, rack: &[String], options: &SolveOptions) -> Vec<Word> {
+        assert!(rack.len() <= MAX_SIDE, "The rack has more than {} letters.", MAX_SIDE);
+
+        let board = [rack.to_vec()];
+        let Some(root) = self.dictionary.root() else {
+            return Vec::new();
+        };
+
+        let mut search = Search::new(&board, &*self.dictionary, options);
+        search.unordered = true;
+        for col in 0..rack.len() {
+            search.visit(0, col, root);
+        }
+
+        finish_words(search.words, options)
+    }
+
+    /// Finds all valid words across several readings of a board.
+    /// The reading whose words are most common is chosen, and its paths are preferred.
+    /// Words found under every reading are marked safe, and sorted first.
+    /// Readings which are not solved before the deadline are skipped.
+    /// Returns the index of the chosen reading, and the merged words.
+    /// boards: The readings of the board, with the most likely first.
+    /// options: The limits of the solve.
+    pub fn find_merged_words(&self, boards: &[Vec<Vec<String>>], options: &SolveOptions) -> (usize, Vec<Word>) {
+        let results: Vec<Vec<Word>> = boards.iter()
+            .enumerate()
+            .take_while(|(index, _)| *index == 0 || !options.expired())
+            .map(|(_, board)| self.find_words(board, options))
+            .collect();
+
+        // Use the chosen reading first, then the rest by likelihood.
+        let chosen = choose_hypothesis(&results);
+        let order = std::iter::once(chosen)
+            .chain((0..results.len()).filter(|index| *index != chosen));
+
+        let mut seen = HashSet::new();
+        let mut merged = Vec::new();
+        for index in order {
+            for word in &results[index] {
+                if !seen.insert(word.word.clone()) {
+                    continue;
+                }
+
+                let mut word = word.clone();
+                word.safe = results.iter()
+                    .all(|other| other.iter().any(|found| found.word == word.word));
+                merged.push(word);
+            }
+        }
+
+        // Trace the safe words first, keeping the solver's order otherwise.
+        merged.sort_by_key(|word| !word.safe);
+
+        (chosen, merged)
+    }
+
+    /// Validates and scores an external list of words against a board.
+    /// Each word must be traceable without reusing a tile, and in the dictionary.
+    /// Returns the valid words, sorted like `Solver::find_all_words`, and the rejected words.
+    /// board: The game board.
+    /// words: The words to check.
+    pub fn rank_words(&self, board: &[Vec<String>], words: &[String]) -> (Vec<Word>, Vec<(String, Rejection)>) {
+
+        let mut seen = HashSet::new();
+        let mut valid = Vec::new();
+        let mut rejected = Vec::new();
+        for text in words {
+            let text = text.trim().to_lowercase();
+
+            let rejection = if text.chars().count() < 3 {
+                Rejection::TooShort
+            } else if !seen.insert(text.clone()) {
+                Rejection::Duplicate
+            } else if let Some(characters) = find_path(board, &text) {
+                match self.dictionary.lookup(&text).filter(|entry| entry.is_word) {
+                    Some(entry) => {
+                        let frequency = entry.frequency;
+                        let assumed = assumed_letters(board, &text, &characters);
+                        let characters = characters.iter().map(GridPos::index).collect();
+                        let score = points(text.chars().count());
+                        valid.push(Word { word: text, characters, frequency, score, safe: true, assumed });
+                        continue;
+                    },
+                    None => Rejection::NotInDictionary
+                }
+            } else {
+                Rejection::NotOnBoard
+            };
+
+            rejected.push((text, rejection));
+        }
+
+        sort_words(&mut valid);
+
+        (valid, rejected)
+    }
+}
+
+/// Finds all valid words in a 2D board, using the loaded dictionary.
+/// board: The game board.
+/// excluded: The tiles which can't be used.
+pub fn find_all_words(board: &[Vec<String>], excluded: &[GridPos]) -> Vec<Word> {
+    Solver::global().find_all_words(board, excluded)
+}
+
+/// Finds the valid words in a 2D board, using the loaded dictionary.
+/// board: The game board.
+/// options: The limits of the solve.
+pub fn find_words(board: &[Vec<String>], options: &SolveOptions) -> Vec<Word> {
+    Solver::global().find_words(board, options)
+}
+
+/// Finds the valid words which can be made from a rack, using the loaded dictionary.
+/// rack: The letters in the rack.
+/// options: The limits of the solve.
+pub fn find_anagrams(rack: &[String], options: &SolveOptions) -> Vec<Word> {
+    Solver::global().find_anagrams(rack, options)
+}
+
+/// Finds all valid words across several readings of a board, using the loaded dictionary.
+/// boards: The readings of the board, with the most likely first.
+/// options: The limits of the solve.
+pub fn find_merged_words(boards: &[Vec<Vec<String>>], options: &SolveOptions) -> (usize, Vec<Word>) {
+    Solver::global().find_merged_words(boards, options)
+}
+
+/// Validates and scores an external list of words, using the loaded dictionary.
+/// board: The game board.
+/// words: The words to check.
+pub fn rank_words(board: &[Vec<String>], words: &[String]) -> (Vec<Word>, Vec<(String, Rejection)>) {
+    Solver::global().rank_words(board, words)
+}
+
+/// Sorts the words found by a search, dropping duplicates.
+/// Of the paths which spell a word, the quickest to trace is kept.
+/// words: The words found.
+/// options: The limits of the solve.
+fn finish_words(words: HashSet<Word>, options: &SolveOptions) -> Vec<Word> {
+    let mut fastest: HashMap<String, (Duration, Word)> = HashMap::new();
+    for word in words {
+        // Words too short to score aren't worth tracing.
+        if word.score == 0 {
+            continue;
+        }
+
+        // Ties go to the first path in tile order, so the choice doesn't depend on the search.
+        let time = options.path_time(&word);
+        let slower = fastest.get(&word.word)
+            .is_some_and(|(best, other)| (*best, &other.characters) <= (time, &word.characters));
+        if !slower {
+            fastest.insert(word.word.clone(), (time, word));
+        }
+    }
+
+    let mut result: Vec<Word> = fastest.into_values()
+        .map(|(_, word)| word)
+        .collect();
+    sort_words(&mut result);
+
+    result
+}
+
+/// Chooses the reading whose words overlap best with common words.
+/// Each word counts by the log of its frequency, so a single very
+/// common word can't outweigh the rest. Ties go to the likelier reading.
+/// results: The words found under each reading, with the most likely first.
+pub fn choose_hypothesis(results: &[Vec<Word>]) -> usize {
+    let overlap = |words: &Vec<Word>| words.iter()
+        .map(|word| (1.0 + word.frequency as f64).ln())
+        .sum::<f64>();
+
+    let mut chosen = (0, f64::MIN);
+    for (index, words) in results.iter().enumerate() {
+        let score = overlap(words);
+        if score > chosen.1 {
+            chosen = (index, score);
+        }
     }
+
+    chosen.0
+}
+
+/// The state of a single board solve.
+struct Search<'a> {
+    /// The game board.
+    board: &'a [Vec<String>],
+    /// The dictionary to look words up in.
+    dictionary: &'a dyn Dictionary,
+    /// The limits of the solve.
+    options: &'a SolveOptions,
+    /// The visited positions, as a mask of tile indices.
+    visited: u64,
+    /// Can any tile follow any other? (instead of only adjacent tiles)
+    unordered: bool,
+    /// The current word.
+    current_word: Word,
+    /// The set of valid words.
+    words: HashSet<Word>
 }
 
-/// Finds all valid words in a 2D board.
+impl<'a> Search<'a> {
+    /// Creates a search with nothing visited.
+    /// board: The game board.
+    /// dictionary: The dictionary to look words up in.
+    /// options: The limits of the solve.
+    fn new(board: &'a [Vec<String>], dictionary: &'a dyn Dictionary, options: &'a SolveOptions) -> Self {
+        Search {
+            board,
+            dictionary,
+            options,
+            visited: 0,
+            unordered: false,
+            current_word: Word::new(),
+            words: HashSet::new()
+        }
+    }
+
+    /// Visits a position on the game board.
+    /// The dictionary is walked one tile at a time, so dead prefixes stop the search immediately.
+    /// A wildcard tile is tried as every letter which can follow the current word.
+    /// row: The row index.
+    /// col: The column index.
+    /// parent: The dictionary's cursor for the current word.
+    fn visit(&mut self, row: usize, col: usize, parent: Cursor<'a>) {
+        let board = self.board;
+        if !in_bounds(board, row, col) || self.options.expired() {
+            return;
+        }
+
+        let pos = GridPos::new(row, col);
+        let bit = 1u64 << pos.index();
+        if (self.visited | self.options.excluded) & bit != 0 {
+            return;
+        }
+
+        self.visited |= bit;
+
+        let tile = &board[row][col];
+        if tile == WILDCARD {
+            for letter in self.dictionary.letters(parent) {
+                let text = letter.to_string();
+                let Some(cursor) = self.dictionary.step(parent, &text) else {
+                    continue;
+                };
+
+                self.current_word.assume(letter, pos);
+                self.extend(pos, cursor);
+                self.current_word.pop(&text);
+            }
+        } else if let Some(cursor) = self.dictionary.step(parent, tile) {
+            self.current_word.append(tile, pos);
+            self.extend(pos, cursor);
+            self.current_word.pop(tile);
+        }
+
+        self.visited &= !bit;
+    }
+
+    /// Records the current word if it's valid, then visits the tiles which can follow it.
+    /// pos: The position of the current word's last tile.
+    /// cursor: The dictionary's cursor for the current word.
+    fn extend(&mut self, pos: GridPos, cursor: Cursor<'a>) {
+        let entry = self.dictionary.entry(cursor);
+        if entry.is_word {
+            self.current_word.frequency = entry.frequency;
+            self.words.insert(self.current_word.clone());
+        }
+
+        if self.unordered {
+            for col in 0..self.board[pos.row].len() {
+                self.visit(pos.row, col, cursor);
+            }
+        } else {
+            for &direction in &DIRECTIONS {
+                if let Some(next) = pos.offset(direction) {
+                    self.visit(next.row, next.col, cursor);
+                }
+            }
+        }
+    }
+}
+
+/// Why a word was rejected by `rank_words`.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Rejection {
+    /// The word is shorter than 3 letters.
+    TooShort,
+    /// The word isn't in the dictionary.
+    NotInDictionary,
+    /// The word can't be traced on the board.
+    NotOnBoard,
+    /// The word was already listed.
+    Duplicate
+}
+
+/// Finds a path which spells a word on the board.
+/// Each tile is used at most once, and each step moves to an adjacent tile.
 /// board: The game board.
-pub fn find_all_words(board: &[Vec<String>]) -> Vec<Word> {
-    let mut words = HashSet::new();
+/// word: The lowercase word to find.
+pub fn find_path(board: &[Vec<String>], word: &str) -> Option<Vec<GridPos>> {
     let rows = board.len();
-    let cols = board[0].len();
-    let mut visited = vec![vec![false; cols]; rows];
-    let mut current_word = Word::new();
+    let cols = board.first().map_or(0, Vec::len);
+    let mut path = Vec::new();
 
     for row in 0..rows {
         for col in 0..cols {
-            visit(
-                board,
-                row,
-                col,
-                &mut visited,
-                &mut current_word,
-                &mut words
-            );
+            if trace_path(board, GridPos::new(row, col), word, &mut path) {
+                return Some(path);
+            }
         }
     }
 
-    let mut result: Vec<Word> = words.into_iter()
-        .filter(|word| word.len() >= 3)
-        .collect();
+    None
+}
 
-    // Sort by highest length.
-    result.sort_by(|a, b| {
-        b.len().cmp(&a.len()).then(a.cmp(b))
-    });
+/// Extends a path through a tile, if the tile starts the rest of the word.
+/// Returns true once the whole word has been traced.
+/// board: The game board.
+/// pos: The tile to visit.
+/// rest: The part of the word not yet traced.
+/// path: The tiles traced so far.
+fn trace_path(board: &[Vec<String>], pos: GridPos, rest: &str, path: &mut Vec<GridPos>) -> bool {
+    if !in_bounds(board, pos.row, pos.col) || path.contains(&pos) {
+        return false;
+    }
 
-    // Remove any duplicate entries.
-    result.dedup_by(|a, b| a.word == b.word);
+    // A wildcard tile matches any single letter.
+    let tile = &board[pos.row][pos.col];
+    let rest = if tile == WILDCARD {
+        let mut letters = rest.chars();
+        match letters.next() {
+            Some(_) => letters.as_str(),
+            None => return false
+        }
+    } else {
+        match rest.strip_prefix(tile.as_str()) {
+            Some(rest) => rest,
+            None => return false
+        }
+    };
 
-    result
+    path.push(pos);
+    if rest.is_empty() {
+        return true;
+    }
+
+    for &direction in &DIRECTIONS {
+        if let Some(next) = pos.offset(direction) {
+            if trace_path(board, next, rest, path) {
+                return true;
+            }
+        }
+    }
+
+    path.pop();
+    false
 }
 
-/// Visits a position on the game board.
+/// Finds the letters which the wildcard tiles in a traced path stand for.
 /// board: The game board.
-/// row: The row index.
-/// col: The column index.
-/// visited: The visited positions.
-/// current_word: The current word.
-/// words: The set of valid words.
-fn visit(
-    board: &[Vec<String>],
-    row: usize,
-    col: usize,
-    visited: &mut Vec<Vec<bool>>,
-    current_word: &mut Word,
-    words: &mut HashSet<Word>
-) {
-    let word_trie = DICTIONARY.read().unwrap();
-
-    if !in_bounds(board, row, col) || visited[row][col] {
-        return;
-    }
-
-    visited[row][col] = true;
-    current_word.append(&board[row][col], col, row);
-
-    if word_trie.has_prefix(&*current_word.word) {
-        if word_trie.is_word(&*current_word.word) {
-            words.insert(current_word.clone());
-        }
-
-        for &(dx, dy) in &DIRECTIONS {
-            let new_row = row as i32 + dx;
-            let new_col = col as i32 + dy;
-
-            if new_row >= 0 && new_col >= 0 {
-                visit(
-                    board,
-                    new_row as usize,
-                    new_col as usize,
-                    visited,
-                    current_word,
-                    words
-                );
-            }
+/// word: The word traced.
+/// path: The tiles which spell the word.
+fn assumed_letters(board: &[Vec<String>], word: &str, path: &[GridPos]) -> Vec<(u8, char)> {
+    let mut assumed = Vec::new();
+    let mut rest = word;
+    for pos in path {
+        let tile = &board[pos.row][pos.col];
+        if tile == WILDCARD {
+            let Some(letter) = rest.chars().next() else {
+                break;
+            };
+            assumed.push((pos.index(), letter));
+            rest = &rest[letter.len_utf8()..];
+        } else {
+            rest = rest.strip_prefix(tile.as_str()).unwrap_or(rest);
         }
     }
 
-    visited[row][col] = false;
-    current_word.truncate(current_word.len() - board[row][col].len());
+    assumed
 }
 
 /// Checks if a position is within the boundaries of a game board.
@@ -151,3 +655,180 @@ fn visit(
 fn in_bounds(board: &[Vec<String>], row: usize, col: usize) -> bool {
     row < board.len() && col < board[0].len()
 }
+
+/// Removes words whose path is a prefix of another word's path.
+/// The longer word is kept, since tracing it also traces the prefix.
+/// This should only be used when the game scores every prefix on release.
+/// words: The words to merge.
+pub fn merge_prefixes(words: Vec<Word>) -> Vec<Word> {
+    let mut result: Vec<Word> = Vec::new();
+
+    for word in &words {
+        // Check if another word extends this word's path.
+        let extended = words.iter().any(|other| {
+            other.characters.len() > word.characters.len() &&
+                other.characters.starts_with(&word.characters)
+        });
+
+        if !extended {
+            result.push(word.clone());
+        }
+    }
+
+    result
+}
+
+/// Creates a word from a list of (row, column) tiles, one letter per tile.
+/// text: The letters of the word.
+/// tiles: The tile of each letter.
+#[cfg(test)]
+pub fn word(text: &str, tiles: &[(usize, usize)]) -> Word {
+    let mut word = Word::new();
+    for (c, (row, col)) in text.chars().zip(tiles) {
+        word.append(&c.to_string(), GridPos::new(*row, *col));
+    }
+    word
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::HashSet;
+    use std::sync::Arc;
+    use crate::arena::ArenaTrie;
+    use crate::board::{GridPos, MAX_SIDE};
+    use crate::planner::{CoordinateMapper, TimingProfile};
+    use crate::solver::{finish_words, find_path, sort_words, word, SolveOptions, Solver, Word, WILDCARD};
+
+    #[test]
+    pub fn finds_paths() {
+        let board = vec![
+            vec!["c".to_string(), "a".to_string()],
+            vec!["qu".to_string(), "t".to_string()]
+        ];
+
+        assert_eq!(find_path(&board, "cat"), Some(vec![
+            GridPos::new(0, 0), GridPos::new(0, 1), GridPos::new(1, 1)
+        ]));
+        assert!(find_path(&board, "quat").is_some());
+        // Tiles can't be reused.
+        assert_eq!(find_path(&board, "tat"), None);
+        assert_eq!(find_path(&board, "dog"), None);
+    }
+
+    #[test]
+    pub fn sorts_by_score() {
+        let row: Vec<(usize, usize)> = (0..5).map(|col| (0, col)).collect();
+        let mut words: Vec<Word> = [("cat", 90), ("cart", 1), ("carts", 1), ("coat", 5)].into_iter()
+            .map(|(text, frequency)| Word { frequency, ..word(text, &row) })
+            .collect();
+        sort_words(&mut words);
+
+        let order: Vec<_> = words.iter().map(|word| (word.word.as_str(), word.score)).collect();
+        assert_eq!(order, vec![("carts", 800), ("coat", 400), ("cart", 400), ("cat", 100)]);
+
+        for tile in ["s", "t", "r"] {
+            words[0].pop(tile);
+        }
+        assert_eq!(words[0].score, 0);
+    }
+
+    #[test]
+    pub fn excludes_tiles() {
+        let options = SolveOptions::default().exclude(&[GridPos::new(1, 2), GridPos::new(0, 0)]);
+        assert_eq!(options.excluded, (1 << 10) | 1);
+
+        let options = SolveOptions { excluded: 1 << 3, ..SolveOptions::default() }.exclude(&[]);
+        assert_eq!(options.excluded, 1 << 3);
+
+        // Words through an excluded tile aren't found.
+        let solver = Solver::new(Arc::new(ArenaTrie::from_sorted(&[("act", 0), ("cat", 0)])));
+        let board = vec![
+            vec!["c".to_string(), "a".to_string()],
+            vec!["t".to_string(), "x".to_string()]
+        ];
+
+        let found = |excluded: &[GridPos]| -> Vec<String> {
+            let mut words: Vec<String> = solver.find_words(&board, &SolveOptions::default().exclude(excluded))
+                .into_iter().map(|word| word.word).collect();
+            words.sort();
+            words
+        };
+        assert_eq!(found(&[GridPos::new(1, 1)]), vec!["act", "cat"]);
+        assert!(found(&[GridPos::new(0, 0)]).is_empty());
+    }
+
+    #[test]
+    pub fn skips_oversized_boards() {
+        let solver = Solver::new(Arc::new(ArenaTrie::from_sorted(&[("aaa", 0)])));
+        let board = vec![vec!["a".to_string(); MAX_SIDE + 1]; 2];
+        assert!(solver.find_words(&board, &SolveOptions::default()).is_empty());
+    }
+
+    #[test]
+    pub fn prefers_quick_paths() {
+        // The diagonal path takes more steps than the straight one.
+        let slow = word("cat", &[(0, 1), (1, 2), (2, 3)]);
+        let quick = word("cat", &[(0, 2), (0, 1), (0, 0)]);
+        let words: HashSet<Word> = [slow.clone(), quick.clone()].into_iter().collect();
+
+        let timing = TimingProfile { step_size: 30, ..TimingProfile::default() };
+        let options = SolveOptions { trace_model: Some((CoordinateMapper::default(), timing)), ..SolveOptions::default() };
+        assert_eq!(finish_words(words.clone(), &options), vec![quick]);
+
+        // Without a model, the first path in tile order is kept.
+        assert_eq!(finish_words(words, &SolveOptions::default()), vec![slow]);
+    }
+
+    #[test]
+    pub fn tracks_multi_letter_tiles() {
+        let mut word = Word::new();
+        word.append("qu", GridPos::new(0, 0));
+        word.append("a", GridPos::new(0, 1));
+        word.append("d", GridPos::new(1, 1));
+        assert_eq!((word.len(), word.score), (4, 400));
+
+        word.pop("d");
+        assert_eq!(word.word, "qua");
+        assert_eq!(word.positions().collect::<Vec<_>>(), vec![GridPos::new(0, 0), GridPos::new(0, 1)]);
+        assert_eq!(word.score, 100);
+
+        word.pop("a");
+        word.pop("qu");
+        assert!(word.word.is_empty() && word.characters.is_empty());
+    }
+
+    #[test]
+    pub fn finds_easy_words() {
+        let line = word("cats", &[(0, 0), (0, 1), (0, 2), (0, 3)]);
+        let corner = word("cats", &[(0, 0), (0, 1), (1, 1), (2, 1)]);
+        let zigzag = word("cats", &[(0, 0), (1, 1), (0, 2), (1, 3)]);
+        assert_eq!((line.turns(), corner.turns(), zigzag.turns()), (0, 1, 2));
+        assert!(line.is_easy() && corner.is_easy() && !zigzag.is_easy());
+
+        // Longer words aren't easy, even in a straight line.
+        assert!(!word("carts", &[(0, 0), (0, 1), (0, 2), (0, 3), (0, 4)]).is_easy());
+    }
+
+    #[test]
+    pub fn assumes_wildcard_letters() {
+        let board = vec![
+            vec!["c".to_string(), WILDCARD.to_string()],
+            vec!["d".to_string(), "t".to_string()]
+        ];
+        assert!(find_path(&board, "cat").is_some());
+        assert!(find_path(&board, "cut").is_some());
+        assert_eq!(find_path(&board, "cart"), None);
+
+        let mut word = Word::new();
+        word.append("c", GridPos::new(0, 0));
+        word.assume('a', GridPos::new(0, 1));
+        word.append("t", GridPos::new(1, 1));
+        assert_eq!((word.word.as_str(), word.assumed.clone()), ("cat", vec![(1, 'a')]));
+
+        word.pop("t");
+        assert_eq!(word.assumed, vec![(1, 'a')]);
+        word.pop("a");
+        assert!(word.assumed.is_empty());
+        assert_eq!(word.word, "c");
+    }
+}
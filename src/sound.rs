@@ -0,0 +1,44 @@
+use std::fs::File;
+use std::io::BufReader;
+use std::thread;
+use log::warn;
+use rodio::{Decoder, OutputStream, Sink};
+
+/// Plays a sound file in the background.
+/// Does nothing if the path is empty.
+/// path: The path to the sound file.
+pub fn play(path: &str) {
+    if path.is_empty() {
+        return;
+    }
+
+    let path = path.to_string();
+    thread::spawn(move || play_and_wait(&path));
+}
+
+/// Plays a sound file, waiting for it to finish.
+/// Does nothing if the path is empty.
+/// path: The path to the sound file.
+pub fn play_and_wait(path: &str) {
+    if path.is_empty() {
+        return;
+    }
+
+    if let Err(error) = play_file(path) {
+        warn!("Failed to play sound '{}': {}", path, error);
+    }
+}
+
+/// Decodes and plays a sound file on the default output device.
+/// path: The path to the sound file.
+fn play_file(path: &str) -> anyhow::Result<()> {
+    // The stream must stay alive while the sound plays.
+    let (_stream, handle) = OutputStream::try_default()?;
+    let sink = Sink::try_new(&handle)?;
+
+    let source = Decoder::new(BufReader::new(File::open(path)?))?;
+    sink.append(source);
+    sink.sleep_until_end();
+
+    Ok(())
+}
@@ -0,0 +1,81 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use log::{info, warn};
+use tokio::task::JoinHandle;
+use crate::config::Config;
+use crate::dictionary::{Dictionary, DictionaryBackend};
+use crate::{paths, DICTIONARY};
+
+/// A dictionary being built in the background. (None if its file doesn't exist)
+type DictionaryBuild = JoinHandle<Option<Arc<dyn Dictionary>>>;
+
+/// Swaps the dictionary between rounds, without restarting.
+/// A switch is requested by writing a dictionary's path into the switch file.
+/// The dictionary is built in the background, then swapped in before a later round.
+pub struct DictionarySwitch {
+    /// The file switches are requested through.
+    request: String,
+    /// The directory relative dictionary paths are resolved against.
+    data_dir: PathBuf,
+    /// Should diacritics be removed from words?
+    strip_diacritics: bool,
+    /// The data structure to store the dictionary in.
+    backend: DictionaryBackend,
+
+    // The dictionary being built, with its path.
+    pending: Option<(String, DictionaryBuild)>
+}
+
+impl DictionarySwitch {
+    /// Creates a switch for the configured request file.
+    /// config: The application configuration.
+    pub fn new(config: &Config) -> Self {
+        DictionarySwitch {
+            request: config.dictionary_switch.clone(),
+            data_dir: paths::data_dir(&config.data_dir),
+            strip_diacritics: config.strip_diacritics,
+            backend: DictionaryBackend::parse(&config.dictionary_backend),
+            pending: None
+        }
+    }
+
+    /// Swaps in a dictionary which finished building, then starts building a newly requested one.
+    /// This should be called between rounds.
+    pub async fn poll(&mut self) {
+        if self.pending.as_ref().is_some_and(|(_, task)| task.is_finished()) {
+            let (path, task) = self.pending.take().unwrap();
+            match task.await {
+                Ok(Some(dictionary)) => {
+                    *DICTIONARY.write().unwrap() = dictionary;
+                    info!("Switched to the dictionary {}.", path);
+                },
+                Ok(None) => warn!("The dictionary {} doesn't exist, keeping the current one.", path),
+                Err(error) => warn!("Failed to build the dictionary {}: {}", path, error)
+            }
+        }
+
+        if self.pending.is_some() || self.request.is_empty() || !Path::new(&self.request).exists() {
+            return;
+        }
+
+        // Take the request, so it's only handled once.
+        let requested = fs::read_to_string(&self.request).unwrap_or_default();
+        if let Err(error) = fs::remove_file(&self.request) {
+            warn!("Failed to remove the dictionary switch request: {}", error);
+        }
+
+        let path = paths::resolve(&self.data_dir, requested.trim());
+        if path.is_empty() {
+            return;
+        }
+
+        info!("Building the dictionary {} in the background.", path);
+        let (strip_diacritics, backend) = (self.strip_diacritics, self.backend);
+        let task = {
+            let path = path.clone();
+            tokio::task::spawn_blocking(move || crate::build_dictionary(&path, strip_diacritics, backend))
+        };
+        self.pending = Some((path, task));
+    }
+}
@@ -0,0 +1,93 @@
+use image::imageops::FilterType;
+use image::{DynamicImage, GrayImage, RgbaImage};
+
+/// How much smaller the coarse search is than the full image.
+const COARSE_SCALE: u32 = 4;
+
+/// Finds where a template best matches an image.
+/// A coarse search on downscaled images is refined at full size.
+/// Returns the top-left pixel of the match and its similarity. (0 to 1)
+/// image: The image to search.
+/// template: The image to find.
+pub fn find_template(image: &RgbaImage, template: &RgbaImage) -> Option<((u32, u32), f64)> {
+    let image = DynamicImage::ImageRgba8(image.clone()).to_luma8();
+    let template = DynamicImage::ImageRgba8(template.clone()).to_luma8();
+    if template.width() > image.width() || template.height() > image.height() {
+        return None;
+    }
+
+    // Search every position of the downscaled images.
+    let shrink = |image: &GrayImage| image::imageops::resize(
+        image,
+        (image.width() / COARSE_SCALE).max(1),
+        (image.height() / COARSE_SCALE).max(1),
+        FilterType::Triangle
+    );
+    let (small_image, small_template) = (shrink(&image), shrink(&template));
+    let ((x, y), _) = search(&small_image, &small_template,
+                             (0, 0), (small_image.width(), small_image.height()))?;
+
+    // Refine the match around the coarse position.
+    let min = ((x * COARSE_SCALE).saturating_sub(COARSE_SCALE), (y * COARSE_SCALE).saturating_sub(COARSE_SCALE));
+    let max = ((x + 2) * COARSE_SCALE, (y + 2) * COARSE_SCALE);
+    search(&image, &template, min, max)
+}
+
+/// Finds the most similar position of a template within a region.
+/// Similarity is one minus the mean absolute difference of the pixels.
+/// image: The image to search.
+/// template: The image to find.
+/// min: The first top-left position to try.
+/// max: The top-left position to stop before.
+fn search(
+    image: &GrayImage,
+    template: &GrayImage,
+    min: (u32, u32),
+    max: (u32, u32)
+) -> Option<((u32, u32), f64)> {
+    let (width, height) = template.dimensions();
+    let max = (
+        max.0.min(image.width().checked_sub(width)? + 1),
+        max.1.min(image.height().checked_sub(height)? + 1)
+    );
+
+    let mut best: Option<((u32, u32), u64)> = None;
+    for y in min.1..max.1 {
+        for x in min.0..max.0 {
+            let mut difference = 0u64;
+            for (tx, ty, pixel) in template.enumerate_pixels() {
+                difference += image.get_pixel(x + tx, y + ty).0[0].abs_diff(pixel.0[0]) as u64;
+            }
+
+            if best.is_none_or(|(_, best)| difference < best) {
+                best = Some(((x, y), difference));
+            }
+        }
+    }
+
+    let pixels = (width * height).max(1) as f64;
+    best.map(|(position, difference)| (position, 1.0 - difference as f64 / (pixels * 255.0)))
+}
+
+#[cfg(test)]
+mod test {
+    use image::{Rgba, RgbaImage};
+    use crate::template::find_template;
+
+    #[test]
+    pub fn finds_templates() {
+        // A checkered button on a plain background.
+        let template = RgbaImage::from_fn(24, 16, |x, y| {
+            if (x / 4 + y / 4) % 2 == 0 { Rgba([0, 120, 255, 255]) } else { Rgba([255, 255, 255, 255]) }
+        });
+
+        let mut image = RgbaImage::from_pixel(200, 120, Rgba([240, 240, 240, 255]));
+        image::imageops::overlay(&mut image, &template, 131, 58);
+
+        let (position, similarity) = find_template(&image, &template).unwrap();
+        assert_eq!(position, (131, 58));
+        assert!(similarity > 0.99);
+
+        assert!(find_template(&template, &image).is_none());
+    }
+}
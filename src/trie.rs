@@ -1,9 +1,15 @@
 use std::collections::HashMap;
+use std::thread;
+use unicode_normalization::char::is_combining_mark;
+use unicode_normalization::UnicodeNormalization;
+use crate::dictionary::{Cursor, Dictionary, Entry};
 
 #[derive(Default, Clone)]
 pub struct TrieNode {
     children: HashMap<char, TrieNode>,
     is_end_of_word: bool,
+    // How common the word ending here is. Higher is more common.
+    frequency: u32,
 }
 
 impl TrieNode {
@@ -11,37 +17,248 @@ impl TrieNode {
         Self::default()
     }
 
+    /// Builds a trie from sorted, deduplicated words.
+    /// The words are expected to already be normalized.
+    /// Each node is created once, without walking the trie per word.
+    /// words: The words to insert with their frequencies, in sorted order.
+    pub fn from_sorted<S: AsRef<str>>(words: &[(S, u32)]) -> Self {
+        Self::build(words, 0)
+    }
+
+    /// Builds a trie from sorted, deduplicated words.
+    /// Each first letter is built on its own thread.
+    /// words: The words to insert with their frequencies, in sorted order.
+    pub fn from_sorted_parallel<S: AsRef<str> + Sync>(words: &[(S, u32)]) -> Self {
+        let (frequency, groups) = split_groups(words, 0);
+
+        let children = thread::scope(|scope| {
+            let handles: Vec<_> = groups.into_iter()
+                .map(|(ch, group)| {
+                    scope.spawn(move || (ch, Self::build(group, ch.len_utf8())))
+                })
+                .collect();
+
+            handles.into_iter()
+                .map(|handle| handle.join().expect("Failed to build the trie."))
+                .collect()
+        });
+
+        TrieNode {
+            children,
+            is_end_of_word: frequency.is_some(),
+            frequency: frequency.unwrap_or(0)
+        }
+    }
+
+    fn build<S: AsRef<str>>(words: &[(S, u32)], depth: usize) -> Self {
+        let (frequency, groups) = split_groups(words, depth);
+
+        let mut children = HashMap::with_capacity(groups.len());
+        for (ch, group) in groups {
+            children.insert(ch, Self::build(group, depth + ch.len_utf8()));
+        }
+
+        TrieNode {
+            children,
+            is_end_of_word: frequency.is_some(),
+            frequency: frequency.unwrap_or(0)
+        }
+    }
+
+    // Only used to check the bulk builders.
+    #[cfg(test)]
     pub fn insert<S: AsRef<str>>(&mut self, word: S) {
         let mut current = self;
-        for ch in word.as_ref().chars() {
+        for ch in fold(word.as_ref()) {
             current = current.children.entry(ch).or_default();
         }
         current.is_end_of_word = true;
     }
 
-    pub fn has_prefix(&self, prefix: &str) -> bool {
+    /// Finds the node at the end of a prefix.
+    /// Returns None if no word starts with the prefix.
+    /// prefix: The prefix to look up, relative to this node.
+    pub fn lookup(&self, prefix: &str) -> Option<&TrieNode> {
         let mut node = self;
-        for ch in prefix.chars() {
-            match node.children.get(&ch) {
-                Some(next) => node = next,
-                None => return false,
+        for ch in fold(prefix) {
+            node = node.children.get(&ch)?;
+        }
+        Some(node)
+    }
+
+    /// Checks if a word ends at this node.
+    pub fn is_terminal(&self) -> bool {
+        self.is_end_of_word
+    }
+
+    /// Returns the frequency of the word ending at this node.
+    /// This is 0 when no frequency was provided.
+    pub fn frequency(&self) -> u32 {
+        self.frequency
+    }
+
+    // Only used to check the bulk builders.
+    #[cfg(test)]
+    pub fn len(&self) -> usize {
+        self.children.len()
+    }
+}
+
+impl Dictionary for TrieNode {
+    fn root(&self) -> Option<Cursor<'_>> {
+        Some(Cursor::Trie(self))
+    }
+
+    fn step<'a>(&'a self, cursor: Cursor<'a>, letters: &str) -> Option<Cursor<'a>> {
+        let Cursor::Trie(node) = cursor else {
+            unreachable!("The cursor isn't from a HashMap trie.");
+        };
+
+        TrieNode::lookup(node, letters).map(Cursor::Trie)
+    }
+
+    fn entry(&self, cursor: Cursor<'_>) -> Entry {
+        let Cursor::Trie(node) = cursor else {
+            unreachable!("The cursor isn't from a HashMap trie.");
+        };
+
+        Entry { is_word: node.is_end_of_word, frequency: node.frequency }
+    }
+
+    fn letters(&self, cursor: Cursor<'_>) -> Vec<char> {
+        let Cursor::Trie(node) = cursor else {
+            unreachable!("The cursor isn't from a HashMap trie.");
+        };
+
+        // The children aren't ordered, so sort them.
+        let mut letters: Vec<char> = node.children.keys().copied().collect();
+        letters.sort_unstable();
+        letters
+    }
+
+    fn iter(&self) -> Box<dyn Iterator<Item = (String, u32)> + '_> {
+        let mut words = Vec::new();
+        let mut stack = vec![(String::new(), self)];
+        while let Some((prefix, node)) = stack.pop() {
+            if node.is_end_of_word {
+                words.push((prefix.clone(), node.frequency));
+            }
+
+            for (ch, child) in &node.children {
+                let mut word = prefix.clone();
+                word.push(*ch);
+                stack.push((word, child));
             }
         }
-        true
+
+        // The children aren't ordered, so sort the words afterwards.
+        words.sort_unstable();
+        Box::new(words.into_iter())
     }
+}
 
-    pub fn is_word(&self, word: &str) -> bool {
-        let mut node = self;
-        for ch in word.chars() {
-            match node.children.get(&ch) {
-                Some(next) => node = next,
-                None => return false,
+/// Normalizes a word before it is inserted into the trie.
+/// This folds the case, and optionally strips diacritics. (e.g. 'é' -> 'e')
+/// word: The word to normalize.
+/// strip_diacritics: Should diacritics be removed?
+pub fn normalize(word: &str, strip_diacritics: bool) -> String {
+    let word: String = fold(word).collect();
+    if !strip_diacritics {
+        return word;
+    }
+
+    // Decompose the characters and remove the combining marks.
+    word.nfd()
+        .filter(|ch| !is_combining_mark(*ch))
+        .collect()
+}
+
+/// Folds the case of every character in a word.
+/// word: The word to fold.
+pub fn fold(word: &str) -> impl Iterator<Item = char> + '_ {
+    word.chars().flat_map(char::to_lowercase)
+}
+
+/// Words sharing the same next character.
+pub type Group<'a, S> = (char, &'a [(S, u32)]);
+
+/// Splits sorted words into groups sharing the character at `depth`.
+/// Returns the frequency of the word ending at `depth`, if any, and the groups.
+/// words: The sorted words, which all share the first `depth` bytes.
+/// depth: The byte offset of the character to group by.
+pub fn split_groups<S: AsRef<str>>(
+    words: &[(S, u32)],
+    depth: usize
+) -> (Option<u32>, Vec<Group<'_, S>>) {
+    let mut frequency = None;
+    let mut groups: Vec<Group<S>> = Vec::new();
+
+    let mut start = 0;
+    while start < words.len() {
+        // Words which end here sort before any longer words.
+        let (word, word_frequency) = &words[start];
+        let Some(ch) = word.as_ref()[depth..].chars().next() else {
+            frequency = Some(*word_frequency);
+            start += 1;
+            continue;
+        };
+
+        // Find the end of the group sharing this character.
+        let mut end = start + 1;
+        while end < words.len() && words[end].0.as_ref()[depth..].starts_with(ch) {
+            end += 1;
+        }
+
+        groups.push((ch, &words[start..end]));
+        start = end;
+    }
+
+    (frequency, groups)
+}
+
+#[cfg(test)]
+mod test {
+    use crate::dictionary::Dictionary;
+    use crate::trie::{normalize, TrieNode};
+
+    #[test]
+    pub fn bulk_matches_insert() {
+        let words = ["car", "card", "cards", "cart", "dog", "do"];
+
+        let mut sorted: Vec<(&str, u32)> = words.iter()
+            .map(|word| (*word, word.len() as u32))
+            .collect();
+        sorted.sort_unstable();
+
+        let mut inserted = TrieNode::new();
+        for word in &words {
+            inserted.insert(word);
+        }
+
+        for trie in [TrieNode::from_sorted(&sorted), TrieNode::from_sorted_parallel(&sorted)] {
+            for word in &words {
+                assert_eq!(trie.is_word(word), inserted.is_word(word));
             }
+
+            assert_eq!(trie.lookup("cards").map(TrieNode::frequency), Some(5));
+            assert!(trie.has_prefix("CA"));
+            assert!(trie.lookup("car").and_then(|node| node.lookup("d"))
+                .is_some_and(TrieNode::is_terminal));
+            assert!(!trie.is_word("ca"));
+            assert!(!trie.is_word("cat"));
+            assert_eq!(trie.len(), inserted.len());
         }
-        node.is_end_of_word
     }
 
-    pub fn len(&self) -> usize {
-        self.children.len()
+    #[test]
+    pub fn normalizes_words() {
+        assert_eq!(normalize("Café", false), "café");
+        assert_eq!(normalize("Café", true), "cafe");
+
+        let mut trie = TrieNode::new();
+        trie.insert("Naïve");
+
+        assert!(trie.is_word("NAÏVE"));
+        assert!(trie.has_prefix("naï"));
     }
 }
@@ -0,0 +1,215 @@
+use std::fs;
+use std::io::{stdin, stdout, Read, Write};
+use anyhow::anyhow;
+use log::{info, warn};
+use serde::Deserialize;
+
+/// The GitHub repository releases are published to.
+pub const REPOSITORY: &str = "KingRainbow44/word-terminator";
+
+/// A release published on GitHub.
+#[derive(Clone, Debug, Deserialize)]
+pub struct Release {
+    /// The release's tag. (e.g. "v0.2.0")
+    pub tag_name: String,
+    /// Is this a pre-release?
+    #[serde(default)]
+    pub prerelease: bool,
+    /// The files attached to the release.
+    #[serde(default)]
+    pub assets: Vec<Asset>
+}
+
+/// A file attached to a release.
+#[derive(Clone, Debug, Deserialize)]
+pub struct Asset {
+    /// The file name.
+    pub name: String,
+    /// The URL to download the file from.
+    pub browser_download_url: String,
+    /// The file's size, in bytes.
+    #[serde(default)]
+    pub size: u64,
+    /// The file's digest, as "sha256:" and the hex hash. (missing for older releases)
+    #[serde(default)]
+    pub digest: Option<String>
+}
+
+impl Release {
+    /// Returns the release's version, as (major, minor, patch).
+    pub fn version(&self) -> Option<(u32, u32, u32)> {
+        parse_version(&self.tag_name)
+    }
+
+    /// Finds the asset built for this platform.
+    pub fn binary(&self) -> Option<&Asset> {
+        let extension = std::env::consts::EXE_EXTENSION;
+        self.assets.iter().find(|asset| {
+            asset.name.starts_with("word-terminator") &&
+                (extension.is_empty() || asset.name.ends_with(extension))
+        })
+    }
+
+    /// Describes the release, like "v0.2.0" or "v0.3.0 (pre-release)".
+    pub fn describe(&self) -> String {
+        match self.prerelease {
+            true => format!("{} (pre-release)", self.tag_name),
+            false => self.tag_name.clone()
+        }
+    }
+}
+
+impl Asset {
+    /// Checks that a download matches the asset's size and SHA-256 digest.
+    /// Assets without a digest can't be checked, so they're refused.
+    /// bytes: The downloaded file.
+    pub fn verify(&self, bytes: &[u8]) -> anyhow::Result<()> {
+        if bytes.len() as u64 != self.size {
+            return Err(anyhow!("The download of {} is {} bytes, expected {}.", self.name, bytes.len(), self.size));
+        }
+
+        let expected = self.digest.as_deref()
+            .and_then(|digest| digest.strip_prefix("sha256:"))
+            .ok_or_else(|| anyhow!("{} has no SHA-256 digest to check the download against.", self.name))?;
+        let actual: String = ring::digest::digest(&ring::digest::SHA256, bytes).as_ref().iter()
+            .map(|byte| format!("{:02x}", byte))
+            .collect();
+        if !actual.eq_ignore_ascii_case(expected) {
+            return Err(anyhow!("The download of {} doesn't match its digest.", self.name));
+        }
+
+        Ok(())
+    }
+}
+
+/// Parses a version like "v1.2.3" into (major, minor, patch).
+/// Any suffix after the patch number is ignored. (e.g. "-beta")
+/// version: The version to parse.
+pub fn parse_version(version: &str) -> Option<(u32, u32, u32)> {
+    let mut parts = version.trim_start_matches('v').splitn(3, '.');
+    let major = parts.next()?.parse().ok()?;
+    let minor = parts.next()?.parse().ok()?;
+    let patch: String = parts.next()?.chars()
+        .take_while(char::is_ascii_digit)
+        .collect();
+
+    Some((major, minor, patch.parse().ok()?))
+}
+
+/// Finds the newest release on a channel, if it is newer than this build.
+/// channel: The release channel. ("stable" or "prerelease")
+pub fn check(channel: &str) -> anyhow::Result<Option<Release>> {
+    let current = parse_version(env!("CARGO_PKG_VERSION"));
+
+    // Stable builds only follow full releases.
+    let release = if channel.eq_ignore_ascii_case("prerelease") {
+        let releases: Vec<Release> = get(&format!("https://api.github.com/repos/{}/releases", REPOSITORY))?
+            .into_json()?;
+        releases.into_iter().next()
+    } else {
+        Some(get(&format!("https://api.github.com/repos/{}/releases/latest", REPOSITORY))?
+            .into_json()?)
+    };
+
+    Ok(release.filter(|release| release.version() > current))
+}
+
+/// Logs a warning if a newer release exists.
+/// Failures are ignored, since this isn't required to play.
+/// channel: The release channel.
+pub fn notify(channel: &str) {
+    if let Ok(Some(release)) = check(channel) {
+        warn!("A newer release ({}) is available. Run 'update' to install it.", release.describe());
+    }
+}
+
+/// Checks for a newer release, and installs it once the user confirms.
+/// The current binary is kept next to the new one, with an `.old` extension.
+/// channel: The release channel.
+pub fn update(channel: &str) -> anyhow::Result<()> {
+    let Some(release) = check(channel)? else {
+        info!("This is the newest release. (v{})", env!("CARGO_PKG_VERSION"));
+        return Ok(());
+    };
+
+    let asset = release.binary()
+        .ok_or_else(|| anyhow!("Release {} has no build for this platform.", release.tag_name))?;
+
+    // Ask before replacing anything.
+    print!("Update from v{} to {}? [y/N] ", env!("CARGO_PKG_VERSION"), release.describe());
+    stdout().flush()?;
+
+    let mut answer = String::new();
+    stdin().read_line(&mut answer)?;
+    if !answer.trim().eq_ignore_ascii_case("y") {
+        info!("The update was cancelled.");
+        return Ok(());
+    }
+
+    // Download the new binary next to the current one, checking it before anything is replaced.
+    let mut bytes = Vec::new();
+    get(&asset.browser_download_url)?
+        .into_reader()
+        .read_to_end(&mut bytes)?;
+    asset.verify(&bytes)?;
+
+    let current = std::env::current_exe()?;
+    let download = current.with_extension("new");
+    fs::write(&download, bytes)?;
+
+    // Running binaries can be renamed, but not overwritten, on Windows.
+    // Renaming fails if the binary from the last update is still there.
+    let old = current.with_extension("old");
+    if old.exists() {
+        fs::remove_file(&old)?;
+    }
+    fs::rename(&current, &old)?;
+
+    // Put the current binary back if the new one can't take its place.
+    if let Err(error) = fs::rename(&download, &current) {
+        if let Err(restore) = fs::rename(&old, &current) {
+            warn!("Unable to restore {}: {}", current.display(), restore);
+        }
+        return Err(error.into());
+    }
+
+    info!("Updated to {}. Restart to use it.", release.tag_name);
+    Ok(())
+}
+
+/// Sends a GET request, as GitHub requires a user agent.
+/// url: The URL to request.
+fn get(url: &str) -> anyhow::Result<ureq::Response> {
+    Ok(ureq::get(url)
+        .set("User-Agent", concat!("word-terminator/", env!("CARGO_PKG_VERSION")))
+        .call()?)
+}
+
+#[cfg(test)]
+mod test {
+    use crate::update::{parse_version, Asset};
+
+    #[test]
+    pub fn parses_versions() {
+        assert_eq!(parse_version("v1.2.3"), Some((1, 2, 3)));
+        assert_eq!(parse_version("0.10.0-beta"), Some((0, 10, 0)));
+        assert_eq!(parse_version("latest"), None);
+        assert!(parse_version("v0.2.0") > parse_version("v0.1.9"));
+    }
+
+    #[test]
+    pub fn verifies_downloads() {
+        let asset = |size: u64, digest: Option<&str>| Asset {
+            name: "word-terminator.exe".to_string(),
+            browser_download_url: String::new(),
+            size,
+            digest: digest.map(String::from)
+        };
+        let digest = "sha256:ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad";
+
+        assert!(asset(3, Some(digest)).verify(b"abc").is_ok());
+        assert!(asset(3, Some(digest)).verify(b"abd").is_err());
+        assert!(asset(4, Some(digest)).verify(b"abc").is_err());
+        assert!(asset(3, None).verify(b"abc").is_err());
+    }
+}